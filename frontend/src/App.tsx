@@ -12,6 +12,9 @@ import "bootstrap/dist/css/bootstrap.min.css";
 import { Keypair, PublicKey } from "@solana/web3.js";
 import {
   getBettingMarketState,
+  getOrderBookState,
+  getClaimState,
+  createClaimAccount,
   initBettingMarket,
   offerTrade,
   payout,
@@ -26,9 +29,9 @@ import {
   USD_TOKEN_MINT,
   keypairFromNums,
   findAssociatedTokenAddress,
+  findClaimAccount,
   getTokenAccountBalanceString,
   BETTING_MARKET_DATA_ACCOUNT,
-  NULL_PUBLIC_KEY,
   createAssociatedTokenAccount,
   connection,
   BTC_PRICE_ACCOUNT,
@@ -36,7 +39,12 @@ import {
   BOB_KEYPAIR,
   DEVMODE,
 } from "./common";
-import { BettingMarketState, formatBettingMarketInfo } from "./layout";
+import {
+  BettingMarketState,
+  OrderBookState,
+  ClaimState,
+  formatBettingMarketInfo,
+} from "./layout";
 import OrderBook from "./OrderBook";
 
 declare const window: any;
@@ -46,6 +54,7 @@ function App() {
     useState<PublicKey>();
   const [bettingMarketState, setBettingMarketState] =
     useState<BettingMarketState>();
+  const [orderBookState, setOrderBookState] = useState<OrderBookState>();
   const [userKeypair, setUserKeypair] = useState<Keypair>();
   const [userUsdTokenAccount, setUserUsdTokenAccount] = useState<PublicKey>();
   const [userYesTokenAccount, setUserYesTokenAccount] = useState<PublicKey>();
@@ -53,6 +62,7 @@ function App() {
   const [userUsdTokenBalance, setUserUsdTokenBalance] = useState<string>();
   const [userYesTokenBalance, setUserYesTokenBalance] = useState<string>();
   const [userNoTokenBalance, setUserNoTokenBalance] = useState<string>();
+  const [userClaimState, setUserClaimState] = useState<ClaimState | null>();
   const [userAmount, setUserAmount] = useState<number>();
   const [userPrice, setUserPrice] = useState<number>();
   const [btcPriceData, setBtcPriceData] = useState<string>();
@@ -65,6 +75,7 @@ function App() {
       bettingMarketDataAccount
     );
     setBettingMarketState(bettingMarketState);
+    setOrderBookState(await getOrderBookState(bettingMarketState.orderBook));
   };
 
   const refreshTokenAccounts = async () => {
@@ -100,6 +111,13 @@ function App() {
         await getTokenAccountBalanceString(associatedNoTokenAddress)
       );
     }
+    if (bettingMarketDataAccount) {
+      setUserClaimState(
+        await getClaimState(
+          await findClaimAccount(bettingMarketDataAccount, userPubkey)
+        )
+      );
+    }
   };
 
   const refreshBtcPriceDataAndSlot = async () => {
@@ -155,6 +173,11 @@ function App() {
     }
   };
 
+  const createClaimAccountClick = () => {
+    console.log("create claim account");
+    createClaimAccount(userKeypair!, bettingMarketDataAccount!);
+  };
+
   const buyYesTradeClick = () => {
     console.log("buy yes");
     offerToBuy(true, userPrice!);
@@ -187,6 +210,7 @@ function App() {
       userUsdTokenAccount!,
       userYesTokenAccount!,
       userNoTokenAccount!,
+      orderBookState!,
       is_yes,
       price,
       userAmount!
@@ -260,7 +284,7 @@ function App() {
   }, [bettingMarketDataAccount]);
   useEffect(() => {
     refreshTokenAccounts();
-  }, [userKeypair, bettingMarketState]);
+  }, [userKeypair, bettingMarketDataAccount, bettingMarketState]);
 
   // useEffect(() => {
   //   const isPhantomInstalled = window.solana && window.solana.isPhantom;
@@ -305,7 +329,7 @@ function App() {
           </Table>
           <OrderBook
             sellEntries={
-              bettingMarketState?.buyAmountsForNoPrice
+              orderBookState?.buyAmountsForNoPrice
                 .map((amount, price) => [price, amount])
                 .filter((arr) => arr[1] !== 0)
                 .map((arr) => {
@@ -313,7 +337,7 @@ function App() {
                 }) || []
             }
             buyEntries={
-              bettingMarketState?.buyAmountsForYesPrice
+              orderBookState?.buyAmountsForYesPrice
                 .map((amount, price) => [price, amount])
                 .filter((arr) => arr[1] !== 0)
                 .map((arr) => {
@@ -321,50 +345,26 @@ function App() {
                 }) || []
             }
           />
-          <h3 style={{ marginTop: 20 }}>Payouts</h3>
+          <h3 style={{ marginTop: 20 }}>
+            Current user account: {userKeypair?.publicKey.toBase58()}
+          </h3>
           <Table striped bordered hover>
             <thead>
               <tr>
-                <th style={{ width: 100 }}>User account</th>
-                <th style={{ width: 100 }}>Token mint</th>
-                <th style={{ width: 100 }}>Amount</th>
+                <th style={{ width: 100 }}>USD</th>
+                <th style={{ width: 100 }}>YES</th>
+                <th style={{ width: 100 }}>NO</th>
               </tr>
             </thead>
             <tbody>
-              {bettingMarketState &&
-                bettingMarketState.payoutUserAccounts
-                  .map((pk, i) => {
-                    return { pk, i };
-                  })
-                  .filter(
-                    (info) => info.pk.toBase58() !== NULL_PUBLIC_KEY.toBase58()
-                  )
-                  .map((info, _) => {
-                    const i = info.i;
-                    let payoutUserAccount =
-                      bettingMarketState.payoutUserAccounts[i].toBase58();
-                    let payoutMint =
-                      bettingMarketState.payoutMints[i].toBase58();
-                    let nameMapping: { [key: string]: string } = {};
-                    nameMapping[bettingMarketState.yesTokenMint.toBase58()] =
-                      "YES";
-                    nameMapping[bettingMarketState.noTokenMint.toBase58()] =
-                      "NO";
-                    nameMapping[USD_TOKEN_MINT.toBase58()] = "USD";
-                    let payoutAmount = bettingMarketState.payoutAmounts[i];
-                    return (
-                      <tr>
-                        <td>{payoutUserAccount}</td>
-                        <td>{nameMapping[payoutMint]}</td>
-                        <td>{payoutAmount}</td>
-                      </tr>
-                    );
-                  })}
+              <tr>
+                <td>{userUsdTokenBalance}</td>
+                <td>{userYesTokenBalance}</td>
+                <td>{userNoTokenBalance}</td>
+              </tr>
             </tbody>
           </Table>
-          <h3 style={{ marginTop: 20 }}>
-            Current user account: {userKeypair?.publicKey.toBase58()}
-          </h3>
+          <h3 style={{ marginTop: 20 }}>Claimable balances</h3>
           <Table striped bordered hover>
             <thead>
               <tr>
@@ -375,9 +375,9 @@ function App() {
             </thead>
             <tbody>
               <tr>
-                <td>{userUsdTokenBalance}</td>
-                <td>{userYesTokenBalance}</td>
-                <td>{userNoTokenBalance}</td>
+                <td>{userClaimState ? userClaimState.usd : "unitialized"}</td>
+                <td>{userClaimState ? userClaimState.yes : "unitialized"}</td>
+                <td>{userClaimState ? userClaimState.no : "unitialized"}</td>
               </tr>
             </tbody>
           </Table>
@@ -405,6 +405,9 @@ function App() {
             />
           </InputGroup>
           <Button onClick={refreshClick}>Refresh</Button>{" "}
+          <Button onClick={createClaimAccountClick}>
+            Create Claim Account
+          </Button>{" "}
           <Button onClick={buyYesTradeClick}>Buy YES</Button>{" "}
           <Button onClick={sellYesTradeClick}>Sell YES</Button>{" "}
           <Button onClick={buyNoTradeClick}>Buy NO</Button>{" "}
@@ -458,50 +461,18 @@ function App() {
             <p>User NO token balance: {userNoTokenBalance}</p>
             <p>
               YES orders:{" "}
-              {bettingMarketState?.buyAmountsForYesPrice
+              {orderBookState?.buyAmountsForYesPrice
                 .map((amount, price) => [price, amount])
                 .filter((arr) => arr[1] !== 0)
                 .map((arr) => arr[0] + ": " + arr[1] + "; ")}
             </p>
             <p>
               NO orders:{" "}
-              {bettingMarketState?.buyAmountsForNoPrice
+              {orderBookState?.buyAmountsForNoPrice
                 .map((amount, price) => [price, amount])
                 .filter((arr) => arr[1] !== 0)
                 .map((arr) => arr[0] + ": " + arr[1] + "; ")}
             </p>
-            <p>Payouts:</p>
-            {bettingMarketState &&
-              bettingMarketState.payoutUserAccounts
-                .map((pk, i) => {
-                  return { pk, i };
-                })
-                .filter(
-                  (info) => info.pk.toBase58() !== NULL_PUBLIC_KEY.toBase58()
-                )
-                .map((info, _) => {
-                  const i = info.i;
-                  let payoutUserAccount =
-                    bettingMarketState.payoutUserAccounts[i].toBase58();
-                  let payoutMint = bettingMarketState.payoutMints[i].toBase58();
-                  let nameMapping: { [key: string]: string } = {};
-                  nameMapping[bettingMarketState.yesTokenMint.toBase58()] =
-                    "YES";
-                  nameMapping[bettingMarketState.noTokenMint.toBase58()] = "NO";
-                  nameMapping[USD_TOKEN_MINT.toBase58()] = "USD";
-                  let payoutAmount = bettingMarketState.payoutAmounts[i];
-                  return (
-                    <p key={i}>
-                      {"(" +
-                        payoutUserAccount +
-                        ", " +
-                        nameMapping[payoutMint] +
-                        ", " +
-                        payoutAmount +
-                        ")"}
-                    </p>
-                  );
-                })}
           </div>
         </Row>
       </Container>
@@ -10,20 +10,43 @@ import {
 import BN from "bn.js";
 import {
   BETTING_MARKET_DATA_LAYOUT,
+  BETTING_MARKET_DATA_LEN,
   RawBettingMarketData,
   BettingMarketState,
+  ORDER_BOOK_HEADER_LAYOUT,
+  ORDER_BOOK_LEVEL_LEN,
+  ORDER_BOOK_NODES_OFFSET,
+  ORDER_BOOK_NODE_LEN,
+  RawOrderBookHeader,
+  OrderBookState,
+  RestingOrder,
+  CLAIM_ACCOUNT_LAYOUT,
+  RawClaimData,
+  ClaimState,
 } from "./layout";
 import {
   connection,
   USD_TOKEN_MINT,
   BETTING_MARKET_PROGRAM_ID,
-  PDA,
   BETTING_MARKET_DATA_ACCOUNT,
   BTC_PRICE_ACCOUNT,
+  NULL_PUBLIC_KEY,
+  findPda,
+  findFaucetPda,
+  findClaimAccount,
 } from "./common";
 import bs58 from "bs58";
 import { parsePriceData } from "@pythnetwork/client";
 
+// number of resting orders the order book of a new betting market can hold
+const ORDER_BOOK_CAPACITY = 1000;
+// a trade matches at most this many makers
+const MAX_MAKERS_PER_MATCH = 12;
+
+const decodeU64 = (bytes: Uint8Array) => new BN(bytes, 10, "le").toNumber();
+const decodeI64 = (bytes: Uint8Array) =>
+  new BN(bytes, 10, "le").fromTwos(64).toNumber();
+
 export const getBtcPriceData = async () => {
   return getPriceData(BTC_PRICE_ACCOUNT);
 };
@@ -70,9 +93,9 @@ export const initBettingMarketWithParams = async (
   let bettingMarketDataAccountPubkey = BETTING_MARKET_DATA_ACCOUNT;
   if (!useExistingBettingMarketDataAccount) {
     const createBettingMarketDataAccountIx = SystemProgram.createAccount({
-      space: 100000,
+      space: BETTING_MARKET_DATA_LEN,
       lamports: await connection.getMinimumBalanceForRentExemption(
-        100000,
+        BETTING_MARKET_DATA_LEN,
         "singleGossip"
       ),
       fromPubkey: initializerAccountKeypair.publicKey,
@@ -92,9 +115,25 @@ export const initBettingMarketWithParams = async (
     bettingMarketDataAccountPubkey = bettingMarketDataAccountKeypair.publicKey;
   }
 
+  // the order book is its own account, sized for the resting orders it holds
+  const orderBookAccountKeypair = new Keypair();
+  const orderBookSpace =
+    ORDER_BOOK_NODES_OFFSET + ORDER_BOOK_NODE_LEN * ORDER_BOOK_CAPACITY;
+  const createOrderBookAccountIx = SystemProgram.createAccount({
+    space: orderBookSpace,
+    lamports: await connection.getMinimumBalanceForRentExemption(
+      orderBookSpace,
+      "singleGossip"
+    ),
+    fromPubkey: initializerAccountKeypair.publicKey,
+    newAccountPubkey: orderBookAccountKeypair.publicKey,
+    programId: bettingMarketProgramId,
+  });
+
   console.log("initBettingMarketWithParams", {
     "intializer account": initializerAccountKeypair.publicKey.toBase58(),
     "betting market data account": bettingMarketDataAccountPubkey.toBase58(),
+    "order book account": orderBookAccountKeypair.publicKey.toBase58(),
     "usd token mint": usdTokenMintPubkey.toBase58(),
     "yes token mint": yesTokenMintAccountKeypair.publicKey.toBase58(),
     "no token mint": noTokenMintAccountKeypair.publicKey.toBase58(),
@@ -111,7 +150,7 @@ export const initBettingMarketWithParams = async (
         isWritable: false,
       },
       {
-        pubkey: PDA,
+        pubkey: await findPda(bettingMarketDataAccountPubkey),
         isSigner: false,
         isWritable: false,
       },
@@ -120,6 +159,11 @@ export const initBettingMarketWithParams = async (
         isSigner: false,
         isWritable: true,
       },
+      {
+        pubkey: orderBookAccountKeypair.publicKey,
+        isSigner: false,
+        isWritable: true,
+      },
       { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
       {
         pubkey: usdTokenMintPubkey,
@@ -152,13 +196,16 @@ export const initBettingMarketWithParams = async (
     data: Buffer.from(Uint8Array.of(2)),
   });
 
-  const tx = new Transaction().add(initBettingMarketIx);
+  const tx = new Transaction()
+    .add(createOrderBookAccountIx)
+    .add(initBettingMarketIx);
   // .add(createBettingMarketDataAccountIx);
   await connection.sendTransaction(
     tx,
     [
       initializerAccountKeypair,
       // bettingMarketDataAccountKeypair,
+      orderBookAccountKeypair,
       yesTokenMintAccountKeypair,
       noTokenMintAccountKeypair,
       usdTokenAccountKeypair,
@@ -191,53 +238,36 @@ export const getBettingMarketState = async (
     encodedBettingMarketState
   ) as RawBettingMarketData;
   console.log(decodedBettingMarketState);
-  const range = (start: number, stop: number, step = 1) =>
-    Array(stop - start)
-      .fill(start)
-      .map((x, y) => x + y * step);
   const bettingMarketState: BettingMarketState = {
     isInitialized: !!decodedBettingMarketState.isInitialized,
     result: decodedBettingMarketState.result,
     yesTokenMint: new PublicKey(decodedBettingMarketState.yesTokenMint),
     noTokenMint: new PublicKey(decodedBettingMarketState.noTokenMint),
     usdTokenAccount: new PublicKey(decodedBettingMarketState.usdTokenAccount),
-    strikePrice: new BN(
-      decodedBettingMarketState.strikePrice,
-      10,
-      "le"
-    ).toNumber(),
+    strikePrice: decodeU64(decodedBettingMarketState.strikePrice),
     judge: new PublicKey(decodedBettingMarketState.judge),
-    buyAmountsForYesPrice: range(0, 101, 8).map((i) =>
-      new BN(
-        decodedBettingMarketState.buyAmountsForYesPrice.slice(i, i + 8),
-        10,
-        "le"
-      ).toNumber()
-    ),
-    buyAmountsForNoPrice: range(0, 101, 8).map((i) =>
-      new BN(
-        decodedBettingMarketState.buyAmountsForNoPrice.slice(i, i + 8),
-        10,
-        "le"
-      ).toNumber()
-    ),
-    payoutUserAccounts: range(0, 101, 32).map(
-      (i) =>
-        new PublicKey(
-          decodedBettingMarketState.payoutUserAccounts.slice(i, i + 32)
-        )
-    ),
-    payoutMints: range(0, 101, 32).map(
-      (i) =>
-        new PublicKey(decodedBettingMarketState.payoutMints.slice(i, i + 32))
-    ),
-    payoutAmounts: range(0, 101, 8).map((i) =>
-      new BN(
-        decodedBettingMarketState.payoutAmounts.slice(i, i + 8),
-        10,
-        "le"
-      ).toNumber()
+    orderBook: new PublicKey(decodedBettingMarketState.orderBook),
+    yesPayout: decodedBettingMarketState.yesPayout,
+    marketType: decodedBettingMarketState.marketType,
+    lowerBound: decodeU64(decodedBettingMarketState.lowerBound),
+    upperBound: decodeU64(decodedBettingMarketState.upperBound),
+    categoricalMarket: new PublicKey(
+      decodedBettingMarketState.categoricalMarket
     ),
+    makerFeeBps: decodedBettingMarketState.makerFeeBps,
+    takerFeeBps: decodedBettingMarketState.takerFeeBps,
+    feeVault: new PublicKey(decodedBettingMarketState.feeVault),
+    creator: new PublicKey(decodedBettingMarketState.creator),
+    tradingCloseTs: decodeI64(decodedBettingMarketState.tradingCloseTs),
+    resolutionTs: decodeI64(decodedBettingMarketState.resolutionTs),
+    hasTraded: !!decodedBettingMarketState.hasTraded,
+    oracle: new PublicKey(decodedBettingMarketState.oracle),
+    resolutionBond: decodeU64(decodedBettingMarketState.resolutionBond),
+    proposer: new PublicKey(decodedBettingMarketState.proposer),
+    disputer: new PublicKey(decodedBettingMarketState.disputer),
+    proposedResult: decodedBettingMarketState.proposedResult,
+    proposedYesPayout: decodedBettingMarketState.proposedYesPayout,
+    proposalTs: decodeI64(decodedBettingMarketState.proposalTs),
   };
 
   console.log("bettingMarketState", {
@@ -248,18 +278,164 @@ export const getBettingMarketState = async (
     usdTokenAccount: bettingMarketState.usdTokenAccount.toBase58(),
     strikePrice: bettingMarketState.strikePrice,
     judge: bettingMarketState.judge.toBase58(),
-    buyAmountsForYesPrice: bettingMarketState.buyAmountsForYesPrice,
-    buyAmountsForNoPrice: bettingMarketState.buyAmountsForNoPrice,
-    payoutUserAccounts: bettingMarketState.payoutUserAccounts.map((pk) =>
-      pk.toBase58()
-    ),
-    payoutMints: bettingMarketState.payoutMints.map((pk) => pk.toBase58()),
-    payoutAmounts: bettingMarketState.payoutAmounts,
+    orderBook: bettingMarketState.orderBook.toBase58(),
+    yesPayout: bettingMarketState.yesPayout,
   });
 
   return bettingMarketState;
 };
 
+export const getOrderBookState = async (
+  orderBookAccountPubkey: PublicKey
+): Promise<OrderBookState> => {
+  const data = (await connection.getAccountInfo(
+    orderBookAccountPubkey,
+    "singleGossip"
+  ))!.data;
+  const header = ORDER_BOOK_HEADER_LAYOUT.decode(data) as RawOrderBookHeader;
+  const capacity = decodeU64(header.capacity);
+
+  // walk the fifo of nodes at a price level from its head
+  const levelAt = (side: number, price: number) =>
+    (side * 101 + price) * ORDER_BOOK_LEVEL_LEN;
+  const ordersAt = (side: number, price: number) => {
+    const orders: RestingOrder[] = [];
+    const level = levelAt(side, price);
+    let node = decodeU64(header.levels.slice(level, level + 8));
+    while (node !== 0 && node <= capacity) {
+      const offset = ORDER_BOOK_NODES_OFFSET + ORDER_BOOK_NODE_LEN * (node - 1);
+      orders.push({
+        userAccount: new PublicKey(data.slice(offset, offset + 32)),
+        amount: decodeU64(data.slice(offset + 32, offset + 40)),
+        payoutInUsd: data[offset + 48] === 2,
+      });
+      node = decodeU64(data.slice(offset + 40, offset + 48));
+    }
+    return orders;
+  };
+  const amountsAt = (side: number) =>
+    Array.from(Array(101).keys()).map((price) => {
+      const level = levelAt(side, price);
+      return decodeU64(header.levels.slice(level + 16, level + 24));
+    });
+  const ordersFor = (side: number) =>
+    Array.from(Array(101).keys()).map((price) => ordersAt(side, price));
+
+  return {
+    market: new PublicKey(header.market),
+    capacity,
+    buyAmountsForYesPrice: amountsAt(0),
+    buyAmountsForNoPrice: amountsAt(1),
+    ordersForYesPrice: ordersFor(0),
+    ordersForNoPrice: ordersFor(1),
+  };
+};
+
+// null until the user created their claimable balance account
+export const getClaimState = async (
+  claimAccountPubkey: PublicKey
+): Promise<ClaimState | null> => {
+  const accountInfo = await connection.getAccountInfo(
+    claimAccountPubkey,
+    "singleGossip"
+  );
+  if (!accountInfo) {
+    return null;
+  }
+  const decodedClaimState = CLAIM_ACCOUNT_LAYOUT.decode(
+    accountInfo.data
+  ) as RawClaimData;
+  return {
+    usd: decodeU64(decodedClaimState.usd),
+    yes: decodeU64(decodedClaimState.yes),
+    no: decodeU64(decodedClaimState.no),
+  };
+};
+
+export const createClaimAccount = async (
+  userAccountKeypair: Keypair,
+  bettingMarketDataAccountPubkey: PublicKey
+) => {
+  const claimAccountPubkey = await findClaimAccount(
+    bettingMarketDataAccountPubkey,
+    userAccountKeypair.publicKey
+  );
+  console.log("createClaimAccount", {
+    "user account": userAccountKeypair.publicKey.toBase58(),
+    "claim account": claimAccountPubkey.toBase58(),
+    "betting market data account": bettingMarketDataAccountPubkey.toBase58(),
+  });
+
+  const createClaimAccountIx = new TransactionInstruction({
+    programId: BETTING_MARKET_PROGRAM_ID,
+    keys: [
+      {
+        pubkey: userAccountKeypair.publicKey,
+        isSigner: true,
+        isWritable: true,
+      },
+      {
+        pubkey: claimAccountPubkey,
+        isSigner: false,
+        isWritable: true,
+      },
+      {
+        pubkey: bettingMarketDataAccountPubkey,
+        isSigner: false,
+        isWritable: false,
+      },
+      { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+      { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
+    ],
+    data: Buffer.from(Uint8Array.of(12)),
+  });
+
+  const tx = new Transaction().add(createClaimAccountIx);
+  await connection.sendTransaction(tx, [userAccountKeypair], {
+    skipPreflight: false,
+    preflightCommitment: "singleGossip",
+  });
+
+  console.log("finished createClaimAccount transaction");
+  console.log("transaction", bs58.encode(tx.signature!));
+
+  return tx;
+};
+
+// users whose resting orders a buy at the price can match, walking the
+// inverse side of the book from the best price up to the maker limit
+const findMatchableMakers = (
+  orderBookState: OrderBookState,
+  is_yes: boolean,
+  price: number,
+  amount: number
+) => {
+  const inverseOrders = is_yes
+    ? orderBookState.ordersForNoPrice
+    : orderBookState.ordersForYesPrice;
+  const makers: PublicKey[] = [];
+  let unmatchedAmount = amount;
+  for (
+    let inversePrice = 100;
+    inversePrice >= 100 - price && unmatchedAmount > 0;
+    inversePrice--
+  ) {
+    for (const order of inverseOrders[inversePrice]) {
+      if (unmatchedAmount <= 0) {
+        break;
+      }
+      if (!makers.some((maker) => maker.equals(order.userAccount))) {
+        if (makers.length >= MAX_MAKERS_PER_MATCH) {
+          return makers;
+        }
+        makers.push(order.userAccount);
+      }
+      unmatchedAmount -= order.amount;
+    }
+  }
+  return makers;
+};
+
 export const offerTrade = async (
   userAccountKeypair: Keypair,
   bettingMarketDataAccountPubkey: PublicKey,
@@ -267,6 +443,7 @@ export const offerTrade = async (
   userUsdTokenAccountPubkey: PublicKey,
   userYesTokenAccountPubkey: PublicKey,
   userNoTokenAccountPubkey: PublicKey,
+  orderBookState: OrderBookState,
   is_yes: boolean,
   price: number,
   amount: number
@@ -275,6 +452,36 @@ export const offerTrade = async (
   const yesTokenMintPubkey = bettingMarketState.yesTokenMint;
   const noTokenMintPubkey = bettingMarketState.noTokenMint;
   const bettingMarketUsdTokenAccountPubkey = bettingMarketState.usdTokenAccount;
+  const userClaimAccountPubkey = await findClaimAccount(
+    bettingMarketDataAccountPubkey,
+    userAccountKeypair.publicKey
+  );
+  // fills are credited to the makers' claimable balance accounts and the
+  // creator's share of the fees to the creator's
+  const otherAccounts = findMatchableMakers(
+    orderBookState,
+    is_yes,
+    price,
+    amount
+  );
+  if (!bettingMarketState.creator.equals(NULL_PUBLIC_KEY)) {
+    otherAccounts.push(bettingMarketState.creator);
+  }
+  const otherClaimAccountPubkeys: PublicKey[] = [];
+  for (const otherAccount of otherAccounts) {
+    const otherClaimAccountPubkey = await findClaimAccount(
+      bettingMarketDataAccountPubkey,
+      otherAccount
+    );
+    if (
+      !otherClaimAccountPubkey.equals(userClaimAccountPubkey) &&
+      !otherClaimAccountPubkeys.some((pubkey) =>
+        pubkey.equals(otherClaimAccountPubkey)
+      )
+    ) {
+      otherClaimAccountPubkeys.push(otherClaimAccountPubkey);
+    }
+  }
 
   console.log("offerTrade", {
     "intializer account": userAccountKeypair.publicKey.toBase58(),
@@ -287,6 +494,10 @@ export const offerTrade = async (
     "user no token account": userNoTokenAccountPubkey.toBase58(),
     "betting market usd token account":
       bettingMarketUsdTokenAccountPubkey.toBase58(),
+    "user claim account": userClaimAccountPubkey.toBase58(),
+    "other claim accounts": otherClaimAccountPubkeys.map((pubkey) =>
+      pubkey.toBase58()
+    ),
   });
 
   const offerTradeIx = new TransactionInstruction({
@@ -298,7 +509,7 @@ export const offerTrade = async (
         isWritable: false,
       },
       {
-        pubkey: PDA,
+        pubkey: await findPda(bettingMarketDataAccountPubkey),
         isSigner: false,
         isWritable: false,
       },
@@ -307,6 +518,11 @@ export const offerTrade = async (
         isSigner: false,
         isWritable: true,
       },
+      {
+        pubkey: bettingMarketState.orderBook,
+        isSigner: false,
+        isWritable: true,
+      },
       {
         pubkey: usdTokenMintPubkey,
         isSigner: false,
@@ -343,13 +559,31 @@ export const offerTrade = async (
         isWritable: true,
       },
       { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+      {
+        pubkey: bettingMarketState.feeVault,
+        isSigner: false,
+        isWritable: true,
+      },
+      {
+        pubkey: userClaimAccountPubkey,
+        isSigner: false,
+        isWritable: true,
+      },
+      ...otherClaimAccountPubkeys.map((pubkey) => ({
+        pubkey,
+        isSigner: false,
+        isWritable: true,
+      })),
     ],
+    // good till cancelled without a referrer
     data: Buffer.from(
       Uint8Array.of(
         3,
         is_yes ? 1 : 0,
         ...new BN(price).toArray("le", 8),
-        ...new BN(amount).toArray("le", 8)
+        ...new BN(amount).toArray("le", 8),
+        0,
+        0
       )
     ),
   });
@@ -378,6 +612,10 @@ export const payout = async (
   const yesTokenMintPubkey = bettingMarketState.yesTokenMint;
   const noTokenMintPubkey = bettingMarketState.noTokenMint;
   const bettingMarketUsdTokenAccountPubkey = bettingMarketState.usdTokenAccount;
+  const userClaimAccountPubkey = await findClaimAccount(
+    bettingMarketDataAccountPubkey,
+    userAccountKeypair.publicKey
+  );
 
   console.log("payout", {
     "user account": userAccountKeypair.publicKey.toBase58(),
@@ -390,6 +628,7 @@ export const payout = async (
     "user no token account": userNoTokenAccountPubkey.toBase58(),
     "betting market usd token account":
       bettingMarketUsdTokenAccountPubkey.toBase58(),
+    "user claim account": userClaimAccountPubkey.toBase58(),
   });
 
   const payoutIx = new TransactionInstruction({
@@ -401,7 +640,7 @@ export const payout = async (
         isWritable: false,
       },
       {
-        pubkey: PDA,
+        pubkey: await findPda(bettingMarketDataAccountPubkey),
         isSigner: false,
         isWritable: false,
       },
@@ -446,6 +685,11 @@ export const payout = async (
         isWritable: true,
       },
       { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+      {
+        pubkey: userClaimAccountPubkey,
+        isSigner: false,
+        isWritable: true,
+      },
     ],
     data: Buffer.from(Uint8Array.of(4)),
   });
@@ -478,7 +722,7 @@ export const freeMint = async (
     programId: BETTING_MARKET_PROGRAM_ID,
     keys: [
       {
-        pubkey: PDA,
+        pubkey: await findFaucetPda(),
         isSigner: false,
         isWritable: false,
       },
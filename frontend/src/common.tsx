@@ -29,7 +29,6 @@ export const ALICE_KEYPAIR = Keypair.fromSecretKey(new Uint8Array([]));
 export const BOB_KEYPAIR = Keypair.fromSecretKey(new Uint8Array([]));
 export const USD_TOKEN_MINT = new PublicKey(DEVNET ? "" : "");
 export const BETTING_MARKET_PROGRAM_ID = new PublicKey(DEVNET ? "" : "");
-export const PDA = new PublicKey(DEVNET ? "" : "");
 export const BETTING_MARKET_DATA_ACCOUNT = new PublicKey(DEVNET ? "" : "");
 // -------------------------------
 
@@ -39,15 +38,38 @@ export function keypairFromNums(nums: string) {
   );
 }
 
-export async function findPda() {
+// every betting market has its own pda, the authority of its mints and vaults
+export async function findPda(bettingMarketDataAccountPubkey: PublicKey) {
   const [pda, _bump_seed] = await PublicKey.findProgramAddress(
-    [Buffer.from("betting")],
+    [Buffer.from("betting"), bettingMarketDataAccountPubkey.toBuffer()],
     BETTING_MARKET_PROGRAM_ID
   );
   console.log("pda", pda.toBase58());
   return pda;
 }
 
+// the user's claimable balance account, created before the user can trade
+export async function findClaimAccount(
+  bettingMarketDataAccountPubkey: PublicKey,
+  userAccountPubkey: PublicKey
+) {
+  const [claimAccount, _bump_seed] = await PublicKey.findProgramAddress(
+    [bettingMarketDataAccountPubkey.toBuffer(), userAccountPubkey.toBuffer()],
+    BETTING_MARKET_PROGRAM_ID
+  );
+  return claimAccount;
+}
+
+// the faucet signs with a global pda that isn't tied to any betting market
+export async function findFaucetPda() {
+  const [pda, _bump_seed] = await PublicKey.findProgramAddress(
+    [Buffer.from("betting")],
+    BETTING_MARKET_PROGRAM_ID
+  );
+  console.log("faucet pda", pda.toBase58());
+  return pda;
+}
+
 export async function findAssociatedTokenAddress(
   walletAddress: PublicKey,
   tokenMintAddress: PublicKey
@@ -15,6 +15,14 @@ const uint64 = (property = "uint64") => {
   return BufferLayout.blob(8, property);
 };
 
+/**
+ * Layout for a 16bit unsigned value
+ */
+const uint16 = (property = "uint16") => {
+  return BufferLayout.u16(property);
+};
+
+// betting market metadata, followed by the oracle samples of the twap
 export const BETTING_MARKET_DATA_LAYOUT = BufferLayout.struct([
   BufferLayout.u8("isInitialized"),
   BufferLayout.u8("result"),
@@ -23,40 +31,81 @@ export const BETTING_MARKET_DATA_LAYOUT = BufferLayout.struct([
   publicKey("usdTokenAccount"),
   uint64("strikePrice"),
   publicKey("judge"),
-  BufferLayout.blob(1000 - 138),
-  BufferLayout.blob(808, "buyAmountsForYesPrice"),
-  BufferLayout.blob(2000 - 1808),
-  BufferLayout.blob(808, "buyAmountsForNoPrice"),
-  BufferLayout.blob(10000 - 2808),
-  BufferLayout.blob(42320 - 10000, "userAccountsForPrice"),
-  BufferLayout.blob(50000 - 42320),
-  BufferLayout.blob(58080 - 50000, "payoutInUsdForPrice"),
-  BufferLayout.blob(60000 - 58080),
-  BufferLayout.blob(68080 - 60000, "payoutAmountsForPrice"),
-  BufferLayout.blob(70000 - 68080),
-  BufferLayout.blob(73200 - 70000, "payoutUserAccounts"),
-  BufferLayout.blob(80000 - 73200),
-  BufferLayout.blob(83200 - 80000, "payoutMints"),
-  BufferLayout.blob(90000 - 83200),
-  BufferLayout.blob(90800 - 90000, "payoutAmounts"),
+  publicKey("orderBook"),
+  BufferLayout.u8("bumpSeed"),
+  BufferLayout.u8("yesPayout"),
+  BufferLayout.u8("marketType"),
+  uint64("lowerBound"),
+  uint64("upperBound"),
+  publicKey("categoricalMarket"),
+  uint16("makerFeeBps"),
+  uint16("takerFeeBps"),
+  publicKey("feeVault"),
+  publicKey("feeAuthority"),
+  publicKey("feeRecipient"),
+  publicKey("creator"),
+  uint16("creatorFeeShareBps"),
+  uint16("referrerFeeShareBps"),
+  uint64("tradingCloseTs"),
+  uint64("resolutionTs"),
+  BufferLayout.u8("hasTraded"),
+  publicKey("oracle"),
+  BufferLayout.u8("comparison"),
+  BufferLayout.s32("strikeExpo"),
+  uint64("twapWindow"),
+  BufferLayout.u8("minTwapSamples"),
+  BufferLayout.u8("oracleKind"),
+  uint64("disputeWindow"),
+  uint64("resolutionBond"),
+  publicKey("proposer"),
+  publicKey("disputer"),
+  BufferLayout.u8("proposedResult"),
+  BufferLayout.u8("proposedYesPayout"),
+  uint64("proposalTs"),
 ]);
 
+// 511 bytes of metadata and 784 bytes of oracle samples
+export const BETTING_MARKET_DATA_LEN = 1295;
+
 export interface RawBettingMarketData {
   isInitialized: number;
   result: number;
   yesTokenMint: Uint8Array;
   noTokenMint: Uint8Array;
   usdTokenAccount: Uint8Array;
-  strikePrice: number;
+  strikePrice: Uint8Array;
   judge: Uint8Array;
-  buyAmountsForYesPrice: Uint8Array;
-  buyAmountsForNoPrice: Uint8Array;
-  // userAccountsForPrice: Uint8Array;
-  // payoutInUsdForPrice: Uint8Array;
-  // payoutAmountsForPrice: Uint8Array;
-  payoutUserAccounts: Uint8Array;
-  payoutMints: Uint8Array;
-  payoutAmounts: Uint8Array;
+  orderBook: Uint8Array;
+  bumpSeed: number;
+  yesPayout: number;
+  marketType: number;
+  lowerBound: Uint8Array;
+  upperBound: Uint8Array;
+  categoricalMarket: Uint8Array;
+  makerFeeBps: number;
+  takerFeeBps: number;
+  feeVault: Uint8Array;
+  feeAuthority: Uint8Array;
+  feeRecipient: Uint8Array;
+  creator: Uint8Array;
+  creatorFeeShareBps: number;
+  referrerFeeShareBps: number;
+  tradingCloseTs: Uint8Array;
+  resolutionTs: Uint8Array;
+  hasTraded: number;
+  oracle: Uint8Array;
+  comparison: number;
+  strikeExpo: number;
+  twapWindow: Uint8Array;
+  minTwapSamples: number;
+  oracleKind: number;
+  disputeWindow: Uint8Array;
+  resolutionBond: Uint8Array;
+  proposer: Uint8Array;
+  disputer: Uint8Array;
+  proposedResult: number;
+  proposedYesPayout: number;
+  proposalTs: Uint8Array;
 }
 
 export interface BettingMarketState {
@@ -67,14 +116,98 @@ export interface BettingMarketState {
   usdTokenAccount: PublicKey;
   strikePrice: number;
   judge: PublicKey;
+  orderBook: PublicKey;
+  yesPayout: number;
+  marketType: number;
+  lowerBound: number;
+  upperBound: number;
+  categoricalMarket: PublicKey;
+  makerFeeBps: number;
+  takerFeeBps: number;
+  feeVault: PublicKey;
+  creator: PublicKey;
+  tradingCloseTs: number;
+  resolutionTs: number;
+  hasTraded: boolean;
+  oracle: PublicKey;
+  resolutionBond: number;
+  proposer: PublicKey;
+  disputer: PublicKey;
+  proposedResult: number;
+  proposedYesPayout: number;
+  proposalTs: number;
+}
+
+// order book account header, followed by the nodes of the resting orders
+// every price level is a fifo of nodes linked by node index + 1, 0 ends it
+export const ORDER_BOOK_HEADER_LAYOUT = BufferLayout.struct([
+  BufferLayout.u8("isInitialized"),
+  BufferLayout.blob(7),
+  publicKey("market"),
+  uint64("capacity"),
+  uint64("freeListHead"),
+  uint64("allocated"),
+  BufferLayout.blob(100 - 64),
+  // (head, tail, total amount) per price 0..100 for the yes, then the no side
+  BufferLayout.blob(2 * 101 * 24, "levels"),
+]);
+
+export const ORDER_BOOK_LEVEL_LEN = 24;
+export const ORDER_BOOK_NODES_OFFSET = 5000;
+// node: user account, remaining size, next node index + 1 and
+// payout in usd (2) or tokens (1), 0 if free
+export const ORDER_BOOK_NODE_LEN = 56;
+
+export interface RawOrderBookHeader {
+  isInitialized: number;
+  market: Uint8Array;
+  capacity: Uint8Array;
+  freeListHead: Uint8Array;
+  allocated: Uint8Array;
+  levels: Uint8Array;
+}
+
+export interface RestingOrder {
+  userAccount: PublicKey;
+  amount: number;
+  payoutInUsd: boolean;
+}
+
+export interface OrderBookState {
+  market: PublicKey;
+  capacity: number;
   buyAmountsForYesPrice: number[];
   buyAmountsForNoPrice: number[];
-  // userAccountsForPrice: PublicKey[];
-  // payoutInUsdForPrice: number[];
-  // payoutAmountsForPrice: number[];
-  payoutUserAccounts: PublicKey[];
-  payoutMints: PublicKey[];
-  payoutAmounts: number[];
+  ordersForYesPrice: RestingOrder[][];
+  ordersForNoPrice: RestingOrder[][];
+}
+
+// a user's claimable balances, credited by fills and drained by payouts
+export const CLAIM_ACCOUNT_LAYOUT = BufferLayout.struct([
+  BufferLayout.u8("isInitialized"),
+  BufferLayout.u8("bumpSeed"),
+  BufferLayout.blob(6),
+  publicKey("market"),
+  publicKey("userAccount"),
+  uint64("usd"),
+  uint64("yes"),
+  uint64("no"),
+]);
+
+export interface RawClaimData {
+  isInitialized: number;
+  bumpSeed: number;
+  market: Uint8Array;
+  userAccount: Uint8Array;
+  usd: Uint8Array;
+  yes: Uint8Array;
+  no: Uint8Array;
+}
+
+export interface ClaimState {
+  usd: number;
+  yes: number;
+  no: number;
 }
 
 export function formatBettingMarketInfo(
@@ -88,6 +221,7 @@ export function formatBettingMarketInfo(
     usdTokenAccount: bettingMarketState.usdTokenAccount.toBase58(),
     strikePrice: bettingMarketState.strikePrice,
     judge: bettingMarketState.judge.toBase58(),
+    orderBook: bettingMarketState.orderBook.toBase58(),
   };
   return bettingMarketStateFormatted;
 }
@@ -0,0 +1,6 @@
+#!/bin/bash
+
+# devnet build with the FreeMint faucet, minting the given test usd mint
+export FAUCET_USD_MINT=${1:?usage: ./deploy_devnet.sh <test usd mint>}
+
+cargo build-bpf --features faucet && solana program deploy --url https://api.devnet.solana.com ./target/deploy/betting_market.so --program-id GWQx5TcgfjYukS8tk6ZyL4Gz5Q9itjzeTKpD3UfLJyNr
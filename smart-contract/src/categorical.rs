@@ -0,0 +1,116 @@
+use crate::state::{get_pubkey, set_pubkey, PUBKEY_USIZE};
+use solana_program::pubkey::Pubkey;
+
+// categorical market data layout
+// account owned by this program grouping 3 to 16 binary betting markets, one per outcome
+// 0..1 - is initialized
+// 1..2 - result, 0 while open, otherwise winning outcome index + 1
+// 2..3 - number of outcomes
+// 3..4 - bump seed of the categorical market pda derived from [b"betting", categorical market data account]
+// 8..40 - judge
+// 40..72 - usd token account holding the collateral of complete sets (owned by pda)
+// 100..1252 - [Outcome; 16]
+//
+// outcome layout
+// 0..32 - Pubkey - outcome betting market data account
+// 32..64 - Pubkey - outcome yes token mint
+// 64..65 - bump seed of the outcome betting market pda
+//
+// every outcome trades on its own betting market and order book as yes (the outcome) against no (any other
+// outcome). a complete set is one yes token of every outcome for 100 usd, exactly one of which wins, so
+// when the market is judged the complete set collateral moves into the winning outcome market's vault
+
+pub const CATEGORICAL_RESULT_OFFSET: usize = 1;
+pub const CATEGORICAL_NUM_OUTCOMES_OFFSET: usize = 2;
+pub const CATEGORICAL_BUMP_SEED_OFFSET: usize = 3;
+pub const CATEGORICAL_JUDGE_OFFSET: usize = 8;
+pub const CATEGORICAL_USD_TOKEN_ACCOUNT_OFFSET: usize = CATEGORICAL_JUDGE_OFFSET + PUBKEY_USIZE;
+pub const CATEGORICAL_OUTCOMES_OFFSET: usize = 100;
+
+pub const OUTCOME_LEN: usize = 72;
+pub const OUTCOME_MARKET_OFFSET: usize = 0;
+pub const OUTCOME_YES_TOKEN_MINT_OFFSET: usize = PUBKEY_USIZE;
+pub const OUTCOME_BUMP_SEED_OFFSET: usize = 2 * PUBKEY_USIZE;
+
+pub const MIN_OUTCOMES: usize = 3;
+pub const MAX_OUTCOMES: usize = 16;
+
+pub const CATEGORICAL_MARKET_LEN: usize = CATEGORICAL_OUTCOMES_OFFSET + OUTCOME_LEN * MAX_OUTCOMES;
+
+// an outcome of a categorical market read out of its data account
+pub struct Outcome {
+  pub market: Pubkey,
+  pub yes_token_mint: Pubkey,
+  pub bump_seed: u8,
+}
+
+pub fn is_initialized(data: &[u8]) -> bool {
+  data.len() >= CATEGORICAL_MARKET_LEN && data[0] == 1
+}
+
+pub fn get_result(data: &[u8]) -> u8 {
+  data[CATEGORICAL_RESULT_OFFSET]
+}
+
+pub fn set_result(data: &mut [u8], result: u8) {
+  data[CATEGORICAL_RESULT_OFFSET] = result;
+}
+
+pub fn get_num_outcomes(data: &[u8]) -> usize {
+  data[CATEGORICAL_NUM_OUTCOMES_OFFSET] as usize
+}
+
+pub fn get_bump_seed(data: &[u8]) -> u8 {
+  data[CATEGORICAL_BUMP_SEED_OFFSET]
+}
+
+pub fn get_judge(data: &[u8]) -> Pubkey {
+  get_pubkey(data, CATEGORICAL_JUDGE_OFFSET)
+}
+
+pub fn get_usd_token_account(data: &[u8]) -> Pubkey {
+  get_pubkey(data, CATEGORICAL_USD_TOKEN_ACCOUNT_OFFSET)
+}
+
+// initialize a zeroed categorical market account, outcomes are filled in with set_outcome
+pub fn init_categorical_market(
+  data: &mut [u8],
+  num_outcomes: usize,
+  judge: &Pubkey,
+  usd_token_account: &Pubkey,
+  bump_seed: u8,
+) {
+  data[0] = 1;
+  data[CATEGORICAL_NUM_OUTCOMES_OFFSET] = num_outcomes as u8;
+  data[CATEGORICAL_BUMP_SEED_OFFSET] = bump_seed;
+  set_pubkey(data, CATEGORICAL_JUDGE_OFFSET, judge);
+  set_pubkey(
+    data,
+    CATEGORICAL_USD_TOKEN_ACCOUNT_OFFSET,
+    usd_token_account,
+  );
+}
+
+fn outcome_offset(index: usize) -> usize {
+  CATEGORICAL_OUTCOMES_OFFSET + OUTCOME_LEN * index
+}
+
+pub fn get_outcome(data: &[u8], index: usize) -> Outcome {
+  let offset = outcome_offset(index);
+  Outcome {
+    market: get_pubkey(data, offset + OUTCOME_MARKET_OFFSET),
+    yes_token_mint: get_pubkey(data, offset + OUTCOME_YES_TOKEN_MINT_OFFSET),
+    bump_seed: data[offset + OUTCOME_BUMP_SEED_OFFSET],
+  }
+}
+
+pub fn set_outcome(data: &mut [u8], index: usize, outcome: &Outcome) {
+  let offset = outcome_offset(index);
+  set_pubkey(data, offset + OUTCOME_MARKET_OFFSET, &outcome.market);
+  set_pubkey(
+    data,
+    offset + OUTCOME_YES_TOKEN_MINT_OFFSET,
+    &outcome.yes_token_mint,
+  );
+  data[offset + OUTCOME_BUMP_SEED_OFFSET] = outcome.bump_seed;
+}
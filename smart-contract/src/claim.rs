@@ -0,0 +1,121 @@
+use crate::{
+  error::BettingMarketError,
+  state::{get_pubkey, get_u64, set_pubkey, set_u64, PUBKEY_USIZE},
+};
+use solana_program::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+
+// claimable balance data layout
+// one pda per user per betting market derived from [betting market data account, user account]
+// 0..1 - is initialized
+// 1..2 - bump seed of the pda
+// 8..40 - betting market the balance belongs to
+// 40..72 - user account the balance belongs to
+// 72..80 - u64 - claimable usd
+// 80..88 - u64 - claimable yes tokens
+// 88..96 - u64 - claimable no tokens
+//
+// fills credit the balances directly so there is no cap on the number of traders with pending payouts,
+// and a payout drains all three balances at once
+
+pub const CLAIM_BUMP_SEED_OFFSET: usize = 1;
+pub const CLAIM_MARKET_OFFSET: usize = 8;
+pub const CLAIM_USER_ACCOUNT_OFFSET: usize = CLAIM_MARKET_OFFSET + PUBKEY_USIZE;
+pub const CLAIM_USD_OFFSET: usize = 72;
+pub const CLAIM_YES_OFFSET: usize = 80;
+pub const CLAIM_NO_OFFSET: usize = 88;
+
+pub const CLAIM_ACCOUNT_LEN: usize = 96;
+
+// the token a claimable balance is paid out in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClaimableToken {
+  Usd,
+  Yes,
+  No,
+}
+
+impl ClaimableToken {
+  // yes or no token for the side of an order
+  pub fn outcome(is_yes: bool) -> Self {
+    if is_yes {
+      ClaimableToken::Yes
+    } else {
+      ClaimableToken::No
+    }
+  }
+
+  fn offset(self) -> usize {
+    match self {
+      ClaimableToken::Usd => CLAIM_USD_OFFSET,
+      ClaimableToken::Yes => CLAIM_YES_OFFSET,
+      ClaimableToken::No => CLAIM_NO_OFFSET,
+    }
+  }
+}
+
+pub fn is_initialized(data: &[u8]) -> bool {
+  data.len() >= CLAIM_ACCOUNT_LEN && data[0] == 1
+}
+
+pub fn get_market(data: &[u8]) -> Pubkey {
+  get_pubkey(data, CLAIM_MARKET_OFFSET)
+}
+
+pub fn get_user_account(data: &[u8]) -> Pubkey {
+  get_pubkey(data, CLAIM_USER_ACCOUNT_OFFSET)
+}
+
+// initialize a freshly created claimable balance account
+pub fn init_claim_account(data: &mut [u8], market: &Pubkey, user_account: &Pubkey, bump_seed: u8) {
+  data[0] = 1;
+  data[CLAIM_BUMP_SEED_OFFSET] = bump_seed;
+  set_pubkey(data, CLAIM_MARKET_OFFSET, market);
+  set_pubkey(data, CLAIM_USER_ACCOUNT_OFFSET, user_account);
+}
+
+pub fn get_balance(data: &[u8], token: ClaimableToken) -> u64 {
+  get_u64(data, token.offset())
+}
+
+// add to the claimable balance of a token
+pub fn credit(data: &mut [u8], token: ClaimableToken, amount: u64) -> Result<(), ProgramError> {
+  let balance = get_balance(data, token)
+    .checked_add(amount)
+    .ok_or(BettingMarketError::AmountOverflow)?;
+  set_u64(data, token.offset(), balance);
+  Ok(())
+}
+
+// zero the claimable balance of a token and return what it was
+pub fn take_balance(data: &mut [u8], token: ClaimableToken) -> u64 {
+  let balance = get_balance(data, token);
+  set_u64(data, token.offset(), 0);
+  balance
+}
+
+// find the claimable balance account of the user for the betting market among the accounts passed in
+// only this program can initialize an account it owns at the [market, user] pda, so matching the stored
+// market and user is enough to know the account is the right pda
+pub fn find_claim_account<'a, 'b>(
+  claim_accounts: &'a [AccountInfo<'b>],
+  program_id: &Pubkey,
+  market: &Pubkey,
+  user_account: &Pubkey,
+) -> Result<&'a AccountInfo<'b>, ProgramError> {
+  claim_accounts
+    .iter()
+    .find(|claim_account| {
+      if claim_account.owner != program_id {
+        return false;
+      }
+      // an account that is already borrowed is some other account of the instruction passed in again
+      let data = match claim_account.try_borrow_data() {
+        Ok(data) => data,
+        Err(_) => return false,
+      };
+      is_initialized(&data)
+        && get_market(&data) == *market
+        && get_user_account(&data) == *user_account
+    })
+    .ok_or_else(|| BettingMarketError::ClaimAccountNotFound.into())
+}
@@ -23,6 +23,238 @@ pub enum BettingMarketError {
   /// Invalid pda
   #[error("Invalid pda")]
   InvalidPda,
+
+  /// Order not found
+  #[error("Order not found")]
+  OrderNotFound,
+
+  /// Order book full
+  #[error("Order book full")]
+  OrderBookFull,
+
+  /// Invalid order book
+  #[error("Invalid order book")]
+  InvalidOrderBook,
+
+  /// Fill or kill order would not fully fill
+  #[error("Order would not fill")]
+  OrderWouldNotFill,
+
+  /// Post only order would cross
+  #[error("Order would cross")]
+  OrderWouldCross,
+
+  /// Signer is not the betting market's judge
+  #[error("Invalid judge")]
+  InvalidJudge,
+
+  /// Betting market already has a result
+  #[error("Market already resolved")]
+  MarketAlreadyResolved,
+
+  /// Required signer missing
+  #[error("Missing signer")]
+  MissingSigner,
+
+  /// Token program is not the spl token program
+  #[error("Incorrect token program")]
+  IncorrectTokenProgram,
+
+  /// Betting market data account not owned by this program
+  #[error("Incorrect market owner")]
+  IncorrectMarketOwner,
+
+  /// Betting market data account not initialized
+  #[error("Market not initialized")]
+  MarketNotInitialized,
+
+  /// Yes token mint doesn't match the betting market's
+  #[error("Incorrect yes token mint")]
+  IncorrectYesTokenMint,
+
+  /// No token mint doesn't match the betting market's
+  #[error("Incorrect no token mint")]
+  IncorrectNoTokenMint,
+
+  /// USD token account isn't the betting market's vault
+  #[error("Incorrect usd token account")]
+  IncorrectUsdTokenAccount,
+
+  /// USD token mint doesn't match the betting market's vault
+  #[error("Incorrect usd token mint")]
+  IncorrectUsdTokenMint,
+
+  /// Token account not owned by the user
+  #[error("Incorrect token account owner")]
+  IncorrectTokenAccountOwner,
+
+  /// Token account holds the wrong mint
+  #[error("Incorrect token account mint")]
+  IncorrectTokenAccountMint,
+
+  /// Account too small for its data layout
+  #[error("Account too small")]
+  AccountTooSmall,
+
+  /// Faucet can't mint this token
+  #[error("Faucet mint not allowed")]
+  FaucetMintNotAllowed,
+
+  /// Faucet mint amount over the per call limit
+  #[error("Faucet limit exceeded")]
+  FaucetLimitExceeded,
+
+  /// Price outside of 1..=99
+  #[error("Invalid price")]
+  InvalidPrice,
+
+  /// Claimable balance account of a user missing from the accounts
+  #[error("Claim account not found")]
+  ClaimAccountNotFound,
+
+  /// Claimable balance account is not the user's pda for the market
+  #[error("Invalid claim account")]
+  InvalidClaimAccount,
+
+  /// Betting market has not been judged yet
+  #[error("Market not resolved")]
+  MarketNotResolved,
+
+  /// Scalar market lower bound not below upper bound
+  #[error("Invalid scalar bounds")]
+  InvalidScalarBounds,
+
+  /// Categorical market account or its outcome markets don't match
+  #[error("Invalid categorical market")]
+  InvalidCategoricalMarket,
+
+  /// Number of outcomes outside of 3..=16 or outcome accounts missing
+  #[error("Invalid outcome count")]
+  InvalidOutcomeCount,
+
+  /// Winning outcome index not below the number of outcomes
+  #[error("Invalid outcome index")]
+  InvalidOutcomeIndex,
+
+  /// Outcome markets of a categorical market are only judged through the categorical market
+  #[error("Categorical outcome market")]
+  CategoricalOutcomeMarket,
+
+  /// Fee rate above 10000 bps
+  #[error("Invalid fee")]
+  InvalidFee,
+
+  /// Fee vault is not the betting market's fee vault
+  #[error("Incorrect fee vault")]
+  IncorrectFeeVault,
+
+  /// Fee recipient is not the betting market's fee recipient
+  #[error("Incorrect fee recipient")]
+  IncorrectFeeRecipient,
+
+  /// Signer is not the betting market's fee authority
+  #[error("Invalid fee authority")]
+  InvalidFeeAuthority,
+
+  /// Creator and referrer fee shares add up to more than the whole fee
+  #[error("Invalid fee share")]
+  InvalidFeeShare,
+
+  /// Referrer is not a USD token account of another user
+  #[error("Invalid referrer")]
+  InvalidReferrer,
+
+  /// Trading close is in the past or after the resolution time
+  #[error("Invalid market times")]
+  InvalidMarketTimes,
+
+  /// Betting market trading close time has passed
+  #[error("Trading closed")]
+  TradingClosed,
+
+  /// Betting market resolution time has not been reached yet
+  #[error("Resolution time not reached")]
+  ResolutionTimeNotReached,
+
+  /// Strike price can't change once the betting market has traded
+  #[error("Strike price locked")]
+  StrikePriceLocked,
+
+  /// Account is not an oracle account of the betting market's oracle kind
+  #[error("Invalid oracle account")]
+  InvalidOracleAccount,
+
+  /// Oracle price is not valid for trading, e.g. pyth status not trading or too few switchboard responses
+  #[error("Oracle price not trading")]
+  OraclePriceNotTrading,
+
+  /// Oracle price is too old
+  #[error("Stale oracle price")]
+  StaleOraclePrice,
+
+  /// Strike price is within the oracle confidence interval, retry once the price moves clear of it
+  #[error("Strike price within confidence interval")]
+  StrikeWithinConfidence,
+
+  /// Oracle account is not the betting market's oracle
+  #[error("Incorrect oracle account")]
+  IncorrectOracleAccount,
+
+  /// Betting market is not judged on a twap
+  #[error("Not a twap market")]
+  NotTwapMarket,
+
+  /// Oracle samples can only be recorded during the twap window
+  #[error("Outside twap window")]
+  OutsideTwapWindow,
+
+  /// Not enough time has passed since the last oracle sample
+  #[error("Oracle sample too soon")]
+  OracleSampleTooSoon,
+
+  /// Not enough oracle samples were recorded to judge on the twap
+  #[error("Too few oracle samples")]
+  TooFewOracleSamples,
+
+  /// A result was already proposed for the betting market
+  #[error("Result already proposed")]
+  ResultAlreadyProposed,
+
+  /// No result was proposed for the betting market
+  #[error("No result proposed")]
+  NoResultProposed,
+
+  /// The proposed result was already disputed
+  #[error("Result already disputed")]
+  ResultAlreadyDisputed,
+
+  /// Only disputed results are resolved by the judge
+  #[error("Result not disputed")]
+  ResultNotDisputed,
+
+  /// The dispute window of the proposed result has ended
+  #[error("Dispute window closed")]
+  DisputeWindowClosed,
+
+  /// The proposed result can still be disputed
+  #[error("Dispute window open")]
+  DisputeWindowOpen,
+
+  /// Outcome markets of a categorical market have to be distinct markets with their own order book and mints
+  #[error("Duplicate outcome market")]
+  DuplicateOutcomeMarket,
+
+  /// Markets without an oracle need a resolution bond for proposing and disputing results
+  #[error("Invalid resolution bond")]
+  InvalidResolutionBond,
+
+  /// Markets judged by an oracle do not take result proposals
+  #[error("Result proposals not allowed")]
+  ProposalNotAllowed,
+
+  /// The oracle still has time to judge the betting market
+  #[error("Oracle grace period not over")]
+  OracleGracePeriodNotOver,
 }
 
 impl From<BettingMarketError> for ProgramError {
@@ -1,55 +1,159 @@
 use crate::{error::BettingMarketError::InvalidInstruction, state};
-use solana_program::program_error::ProgramError;
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
 use std::convert::TryInto;
 
+/// How long the unmatched part of an offered trade is allowed to rest on the order book
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimeInForce {
+  /// Match what crosses and rest the remainder (default), past the 12 makers a trade can match the
+  /// remainder is credited back instead
+  GoodTillCancelled,
+  /// Match what crosses within the first 12 makers and don't collect collateral for the remainder
+  ImmediateOrCancel,
+  /// Fail unless the whole amount crosses within the first 12 makers
+  FillOrKill,
+  /// Fail if any of the amount would cross
+  PostOnly,
+}
+
+/// How a betting market's yes and no tokens are settled
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MarketType {
+  /// Yes redeems for 100 if the oracle price is above the strike price, no redeems for 100 otherwise
+  Binary,
+  /// Yes redeems linearly from 0 at the lower bound to 100 at the upper bound, no redeems the rest
+  Scalar { lower_bound: u64, upper_bound: u64 },
+}
+
+/// How the oracle price is compared against the strike price for yes to win a binary market
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+  /// Oracle price > strike price (default)
+  Above,
+  /// Oracle price >= strike price
+  AtOrAbove,
+  /// Oracle price < strike price
+  Below,
+  /// Oracle price <= strike price
+  AtOrBelow,
+}
+
+/// Which kind of oracle account a betting market is judged by
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OracleKind {
+  /// Pyth price account (default)
+  Pyth,
+  /// Aggregator account in a synthetic switchboard-like layout, any program may own it
+  Switchboard,
+  /// Mock oracle account owned by this program, only in test builds and builds with the mock-oracle feature
+  #[cfg(any(test, feature = "mock-oracle"))]
+  Mock,
+}
+
+/// How much of a market order to buy
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MarketOrderSize {
+  /// Buy up to this many tokens
+  Quantity(u64),
+  /// Spend up to this much USD
+  Budget(u64),
+}
+
 pub enum BettingMarketInstruction {
-  /// Initializes a betting market
+  /// Initializes a betting market, binary unless a scalar market type is given,
+  /// charging makers and takers the given fee in bps of the usd value of every fill
+  /// The creator earns the creator share of every fee and a referrer the referrer share of the taker fee,
+  /// both in bps of the fee and credited to their claimable balance accounts
+  /// Trading closes and oracle judging opens at the given unix timestamps (UTC), 0 for no limit
+  /// Oracle judging only accepts the given oracle account of the given kind, comparing its price against the
+  /// strike price, which like scalar bounds is in units of 10^strike_expo and rescaled to the feed's exponent
+  /// With a twap window, oracle judging uses the time weighted average of the samples recorded during the
+  /// window ending at the resolution time and needs at least the given number of samples
+  /// Markets without an oracle need both timestamps and are resolved by results proposed once trading
+  /// closed, disputable for the given dispute window (a day by default, at least an hour), proposing or
+  /// disputing one takes the given USD bond, which those markets require
   ///
   ///
   /// Accounts expected:
   ///
   /// 0. `[signer]` The account of the person initiazing the betting market (fee payer)
-  /// 1. `[]` The PDA account
+  /// 1. `[]` The betting market's PDA account
   /// 2. `[signer, writable]` The betting market data account to be initialized
-  /// 3. `[]` Token program id
-  /// 4. `[]` USD token mint already initialized
-  /// 5. `[signer, writable]` Yes token mint to be initialized
-  /// 6. `[signer, writable]` No token mint to be initialized
-  /// 7. `[signer, writable]` USD token account to be initialized
-  /// 7. `[]` Judge account to be saved in data
-  /// 8. `[]` System program id
-  /// 9. `[]` Rent account
-  InitBettingMarket {},
+  /// 3. `[writable]` The order book account to be initialized (owned by program, sized for its capacity)
+  /// 4. `[]` Token program id
+  /// 5. `[]` USD token mint already initialized
+  /// 6. `[signer, writable]` Yes token mint to be initialized
+  /// 7. `[signer, writable]` No token mint to be initialized
+  /// 8. `[signer, writable]` USD token account to be initialized
+  /// 9. `[]` Judge account to be saved in data
+  /// 10. `[]` System program id
+  /// 11. `[]` Rent account
+  /// 12. `[signer, writable]` USD token account for fees to be initialized
+  /// 13. `[]` Fee authority account to be saved in data
+  /// 14. `[]` The fee recipient's USD token account to be saved in data
+  /// 15. `[]` Optional, the creator account to be saved in data, required for a creator share
+  /// 16. `[writable]` Required with a creator, the creator's claimable balance account to be created
+  InitBettingMarket {
+    market_type: MarketType,
+    maker_fee_bps: u16,
+    taker_fee_bps: u16,
+    creator_fee_share_bps: u16,
+    referrer_fee_share_bps: u16,
+    trading_close_ts: i64,
+    resolution_ts: i64,
+    strike_price: u64,
+    comparison: Comparison,
+    oracle: Pubkey,
+    strike_expo: i32,
+    twap_window: i64,
+    min_twap_samples: u8,
+    oracle_kind: OracleKind,
+    dispute_window: i64,
+    resolution_bond: u64,
+  },
 
-  /// Processes a trade
+  /// Processes a trade, optionally referred by the owner of a USD token account, until trading closes
+  /// The referrer can be anyone but the user's own key, which doesn't stop the user naming a second wallet
+  /// A trade matches at most 12 makers, the rest of a crossing order is credited back to the user's
+  /// claimable balance account instead of resting
+  /// The USD collateralized part of an order left resting also deposits its maker fee, the unused part of
+  /// which is refunded along with the collateral
   ///
   ///
   /// Accounts expected:
   ///
   /// 0. `[signer]` The account of the user placing trade
-  /// 1. `[]` The PDA account
+  /// 1. `[]` The betting market's PDA account
   /// 2. `[writable]` The betting market data account
-  /// 3. `[writable]` USD token mint (why does this need to be writable?)
-  /// 4. `[writable]` Yes token mint (why does this need to be writable?)
-  /// 5. `[writable]` No token mint (why does this need to be writable?)
-  /// 6. `[writable]` The user's USD token account
-  /// 7. `[writable]` The user's yes token account
-  /// 8. `[writable]` The user's no token account
-  /// 9. `[writable]` The betting market's USD token account (owned by PDA)
-  /// 10. `[]` Token program id
+  /// 3. `[writable]` The betting market's order book account
+  /// 4. `[writable]` USD token mint (why does this need to be writable?)
+  /// 5. `[writable]` Yes token mint (why does this need to be writable?)
+  /// 6. `[writable]` No token mint (why does this need to be writable?)
+  /// 7. `[writable]` The user's USD token account
+  /// 8. `[writable]` The user's yes token account
+  /// 9. `[writable]` The user's no token account
+  /// 10. `[writable]` The betting market's USD token account (owned by PDA)
+  /// 11. `[]` Token program id
+  /// 12. `[writable]` The betting market's fee vault (owned by PDA)
+  /// 13. `[writable]` The user's claimable balance account
+  /// 14. `[writable]` Onwards, the claimable balance accounts of the makers the trade can match, the creator
+  ///     and the referrer, in any order
+  /// 15. `[]` Last, only with a referrer, the referrer's USD token account
   OfferTrade {
     is_yes: bool,
     price: u64,
     amount: u64,
+    time_in_force: TimeInForce,
+    has_referrer: bool,
   },
 
-  /// Pays out all the payouts for a user
+  /// Pays out the user's claimable balances and redeems their tokens once the betting market is judged
   ///
   ///
   /// Accounts expected:
   ///
   /// 0. `[signer]` The account of the user geting the payout
-  /// 1. `[]` The PDA account
+  /// 1. `[]` The betting market's PDA account
   /// 2. `[writable]` The betting market data account
   /// 3. `[writable]` USD token mint (why does this need to be writable?)
   /// 4. `[writable]` Yes token mint (why does this need to be writable?)
@@ -59,73 +163,478 @@ pub enum BettingMarketInstruction {
   /// 8. `[writable]` The user's no token account
   /// 9. `[writable]` The betting market's USD token account (owned by PDA)
   /// 10. `[]` Token program id
+  /// 11. `[writable]` The user's claimable balance account
   Payout {},
 
-  /// Mint tokens to user for free
+  /// Mint test USD to user for free, only available in builds with the faucet feature
   ///
   ///
   /// Accounts expected:
   ///
-  /// 0. `[]` The PDA account
-  /// 1. `[writable]` Desired token mint (why does this need to be writable?)
+  /// 0. `[]` The faucet PDA account (derived from b"betting" only, not tied to a betting market)
+  /// 1. `[writable]` Test USD token mint (why does this need to be writable?)
   /// 2. `[writable]` The user's desired token account
   /// 3. `[]` Token program id
+  #[cfg(feature = "faucet")]
   FreeMint { amount: u64 },
 
-  /// Set the result of the betting market manually
+  /// Resolve a disputed result proposal, 1 for yes, 2 for no, 3 for void or 4 to settle a scalar market
+  /// Void and scalar markets redeem yes tokens for yes_payout and no tokens for the rest of 100
+  /// Whichever of the proposer and disputer the judge agrees with is credited both bonds
+  /// Markets with an oracle are settled or voided by the judge instead, once the oracle didn't judge them
+  /// within a week of the resolution time
   ///
   ///
   /// Accounts expected:
   ///
   /// 0. `[writable]` The betting market data account
-  JudgeBettingMarketManually { result: u64 },
+  /// 1. `[signer]` The betting market's judge
+  /// 2. `[writable]` Not for oracle markets, the claimable balance account of the proposer if the judge agrees
+  ///    with the proposed result and yes payout, of the disputer otherwise
+  JudgeBettingMarketManually { result: u64, yes_payout: u64 },
 
-  /// Set the result of the betting market from oracle
+  /// Set the result of the betting market from oracle, once the resolution time is reached
+  /// The aggregate price (or twap of the recorded samples) has to be trading and recent, and a binary market's
+  /// strike price outside its confidence interval, otherwise judging fails and can be retried
   ///
   ///
   /// Accounts expected:
   ///
   /// 0. `[writable]` The betting market data account
-  /// 1. `[]` The pyth price account
+  /// 1. `[]` The betting market's oracle account
   JudgeBettingMarketOracle {},
 
-  /// Set the betting market strike price
+  /// Set the betting market strike price, only until the first trade
   ///
   ///
   /// Accounts expected:
   ///
   /// 0. `[writable]` The betting market data account
+  /// 1. `[signer]` The betting market's judge
   SetStrikePrice { strike_price: u64 },
+
+  /// Cancels the user's resting orders at a price and refunds their collateral
+  ///
+  ///
+  /// Accounts expected:
+  ///
+  /// 0. `[signer]` The account of the user cancelling the order
+  /// 1. `[]` The betting market's PDA account
+  /// 2. `[writable]` The betting market data account
+  /// 3. `[writable]` The betting market's order book account
+  /// 4. `[writable]` USD token mint (why does this need to be writable?)
+  /// 5. `[writable]` Yes token mint (why does this need to be writable?)
+  /// 6. `[writable]` No token mint (why does this need to be writable?)
+  /// 7. `[writable]` The user's USD token account
+  /// 8. `[writable]` The user's yes token account
+  /// 9. `[writable]` The user's no token account
+  /// 10. `[writable]` The betting market's USD token account (owned by PDA)
+  /// 11. `[]` Token program id
+  CancelOrder { is_yes: bool, price: u64 },
+
+  /// Processes a market order by walking the inverse order book up to the worst price
+  /// without leaving a resting order, only collecting USD for what actually executes, until trading closes
+  /// Quantity orders burn inverse tokens first like OfferTrade, budget orders only spend USD, taker fees
+  /// included
+  /// Like OfferTrade it matches at most 12 makers, the walk stops at the first order of a maker past them
+  ///
+  ///
+  /// Accounts expected:
+  ///
+  /// 0. `[signer]` The account of the user placing trade
+  /// 1. `[]` The betting market's PDA account
+  /// 2. `[writable]` The betting market data account
+  /// 3. `[writable]` The betting market's order book account
+  /// 4. `[writable]` USD token mint (why does this need to be writable?)
+  /// 5. `[writable]` Yes token mint (why does this need to be writable?)
+  /// 6. `[writable]` No token mint (why does this need to be writable?)
+  /// 7. `[writable]` The user's USD token account
+  /// 8. `[writable]` The user's yes token account
+  /// 9. `[writable]` The user's no token account
+  /// 10. `[writable]` The betting market's USD token account (owned by PDA)
+  /// 11. `[]` Token program id
+  /// 12. `[writable]` The betting market's fee vault (owned by PDA)
+  /// 13. `[writable]` The user's claimable balance account
+  /// 14. `[writable]` Onwards, the claimable balance accounts of the makers the trade can match and the
+  ///     creator, in any order
+  MarketOrder {
+    is_yes: bool,
+    worst_price: u64,
+    size: MarketOrderSize,
+  },
+
+  /// Hand the judge role of the betting market to a new account
+  ///
+  ///
+  /// Accounts expected:
+  ///
+  /// 0. `[writable]` The betting market data account
+  /// 1. `[signer]` The betting market's current judge
+  /// 2. `[]` The new judge account to be saved in data
+  SetJudge {},
+
+  /// Creates the user's claimable balance account for a betting market, a pda derived from
+  /// [betting market data account, user account], which has to exist before the user can trade
+  ///
+  ///
+  /// Accounts expected:
+  ///
+  /// 0. `[signer, writable]` The account of the user (fee payer)
+  /// 1. `[writable]` The user's claimable balance account to be created
+  /// 2. `[]` The betting market data account
+  /// 3. `[]` System program id
+  /// 4. `[]` Rent account
+  CreateClaimAccount {},
+
+  /// Removes all of the user's resting orders once the betting market is judged and credits their
+  /// unfilled collateral to the user's claimable balance account, to be paid out with Payout
+  ///
+  ///
+  /// Accounts expected:
+  ///
+  /// 0. `[signer]` The account of the user
+  /// 1. `[]` The betting market data account
+  /// 2. `[writable]` The betting market's order book account
+  /// 3. `[writable]` The user's claimable balance account
+  ReclaimOpenOrders {},
+
+  /// Initializes a categorical market out of 3 to 16 initialized binary betting markets, one per outcome
+  /// Each outcome market trades yes (the outcome) against no (any other outcome) on its own order book
+  /// and can only be judged through the categorical market from then on
+  ///
+  ///
+  /// Accounts expected:
+  ///
+  /// 0. `[signer]` The account of the person initiazing the categorical market (fee payer)
+  /// 1. `[]` The categorical market's PDA account
+  /// 2. `[writable]` The categorical market data account to be initialized (owned by program)
+  /// 3. `[]` Token program id
+  /// 4. `[]` USD token mint already initialized
+  /// 5. `[signer, writable]` USD token account for complete set collateral to be initialized
+  /// 6. `[]` Judge account to be saved in data
+  /// 7. `[]` System program id
+  /// 8. `[]` Rent account
+  /// 9. Onwards, for every outcome in order: `[writable]` its betting market data account,
+  ///    `[signer]` its betting market's judge, `[]` its betting market's USD token account
+  InitCategoricalMarket {},
+
+  /// Mints one yes token of every outcome per amount for 100 USD each
+  ///
+  ///
+  /// Accounts expected:
+  ///
+  /// 0. `[signer]` The account of the user
+  /// 1. `[]` The categorical market data account
+  /// 2. `[writable]` The categorical market's USD token account
+  /// 3. `[writable]` The user's USD token account
+  /// 4. `[]` Token program id
+  /// 5. Onwards, for every outcome in order: `[]` its betting market's PDA account,
+  ///    `[writable]` its yes token mint, `[writable]` the user's token account for its yes token
+  SplitCategoricalSet { amount: u64 },
+
+  /// Burns one yes token of every outcome per amount and refunds 100 USD each
+  ///
+  ///
+  /// Accounts expected:
+  ///
+  /// 0. `[signer]` The account of the user
+  /// 1. `[]` The categorical market's PDA account
+  /// 2. `[]` The categorical market data account
+  /// 3. `[writable]` The categorical market's USD token account
+  /// 4. `[writable]` The user's USD token account
+  /// 5. `[]` Token program id
+  /// 6. Onwards, for every outcome in order: `[writable]` its yes token mint,
+  ///    `[writable]` the user's token account for its yes token
+  MergeCategoricalSet { amount: u64 },
+
+  /// Judges every outcome market of the categorical market, yes wins for the winning outcome and no for
+  /// the others, and moves the complete set collateral to the winning outcome market's USD token account
+  ///
+  ///
+  /// Accounts expected:
+  ///
+  /// 0. `[writable]` The categorical market data account
+  /// 1. `[signer]` The categorical market's judge
+  /// 2. `[]` The categorical market's PDA account
+  /// 3. `[writable]` The categorical market's USD token account
+  /// 4. `[writable]` The winning outcome betting market's USD token account
+  /// 5. `[]` Token program id
+  /// 6. Onwards, `[writable]` every outcome's betting market data account in order
+  JudgeCategoricalMarket { winning_outcome: u64 },
+
+  /// Deposits 100 USD per amount into the betting market and mints amount yes and no tokens to the user
+  ///
+  ///
+  /// Accounts expected:
+  ///
+  /// 0. `[signer]` The account of the user
+  /// 1. `[]` The betting market's PDA account
+  /// 2. `[]` The betting market data account
+  /// 3. `[writable]` USD token mint (why does this need to be writable?)
+  /// 4. `[writable]` Yes token mint
+  /// 5. `[writable]` No token mint
+  /// 6. `[writable]` The user's USD token account
+  /// 7. `[writable]` The user's yes token account
+  /// 8. `[writable]` The user's no token account
+  /// 9. `[writable]` The betting market's USD token account (owned by PDA)
+  /// 10. `[]` Token program id
+  SplitCompleteSet { amount: u64 },
+
+  /// Burns amount yes and no tokens of the user and returns 100 USD per amount, before or after resolution
+  ///
+  ///
+  /// Accounts expected:
+  ///
+  /// 0. `[signer]` The account of the user
+  /// 1. `[]` The betting market's PDA account
+  /// 2. `[]` The betting market data account
+  /// 3. `[writable]` USD token mint (why does this need to be writable?)
+  /// 4. `[writable]` Yes token mint
+  /// 5. `[writable]` No token mint
+  /// 6. `[writable]` The user's USD token account
+  /// 7. `[writable]` The user's yes token account
+  /// 8. `[writable]` The user's no token account
+  /// 9. `[writable]` The betting market's USD token account (owned by PDA)
+  /// 10. `[]` Token program id
+  MergeCompleteSet { amount: u64 },
+
+  /// Transfers all fees collected by the betting market to its fee recipient
+  ///
+  ///
+  /// Accounts expected:
+  ///
+  /// 0. `[signer]` The betting market's fee authority
+  /// 1. `[]` The betting market's PDA account
+  /// 2. `[]` The betting market data account
+  /// 3. `[writable]` The betting market's fee vault (owned by PDA)
+  /// 4. `[writable]` The fee recipient's USD token account
+  /// 5. `[]` Token program id
+  WithdrawFees {},
+
+  /// Records the betting market's oracle price as a sample for twap judging, callable by anyone
+  /// during the twap window at most once every window / 32 seconds
+  ///
+  ///
+  /// Accounts expected:
+  ///
+  /// 0. `[writable]` The betting market data account
+  /// 1. `[]` The betting market's oracle account
+  RecordOracleSample {},
+
+  /// Proposes the result of a betting market without an oracle once trading closed and the resolution time
+  /// is reached, posting the resolution bond, with the same result and yes payout as manual judging
+  /// The result is final unless disputed within the dispute window
+  ///
+  ///
+  /// Accounts expected:
+  ///
+  /// 0. `[signer]` The account of the proposer
+  /// 1. `[writable]` The betting market data account
+  /// 2. `[writable]` The proposer's USD token account
+  /// 3. `[writable]` The betting market's USD token account (owned by PDA)
+  /// 4. `[]` Token program id
+  /// 5. `[]` The proposer's claimable balance account
+  ProposeResult { result: u64, yes_payout: u64 },
+
+  /// Disputes the proposed result within the dispute window, posting the resolution bond
+  /// The judge then resolves the betting market
+  ///
+  ///
+  /// Accounts expected:
+  ///
+  /// 0. `[signer]` The account of the disputer
+  /// 1. `[writable]` The betting market data account
+  /// 2. `[writable]` The disputer's USD token account
+  /// 3. `[writable]` The betting market's USD token account (owned by PDA)
+  /// 4. `[]` Token program id
+  /// 5. `[]` The disputer's claimable balance account
+  DisputeResult {},
+
+  /// Sets the proposed result once the dispute window passed without a dispute, callable by anyone
+  /// The proposer's bond is credited back to them
+  ///
+  ///
+  /// Accounts expected:
+  ///
+  /// 0. `[writable]` The betting market data account
+  /// 1. `[writable]` The proposer's claimable balance account
+  FinalizeResult {},
 }
 
 impl BettingMarketInstruction {
   /// Unpacks a byte buffer into a [BettingMarketInstruction](enum.BettingMarketInstruction.html).
   pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
     let (tag, rest) = input.split_first().ok_or(InvalidInstruction)?;
-    let rest_ptr = rest.as_ptr();
 
     Ok(match tag {
-      2 => Self::InitBettingMarket {},
+      2 => Self::InitBettingMarket {
+        market_type: match rest.first() {
+          None | Some(0) => MarketType::Binary,
+          Some(1) => MarketType::Scalar {
+            lower_bound: Self::unpack_amount(rest.get(1..).unwrap_or(&[]))?,
+            upper_bound: Self::unpack_amount(rest.get(9..).unwrap_or(&[]))?,
+          },
+          _ => return Err(InvalidInstruction.into()),
+        },
+        maker_fee_bps: Self::unpack_optional_fee_bps(rest.get(17..).unwrap_or(&[]))?,
+        taker_fee_bps: Self::unpack_optional_fee_bps(rest.get(19..).unwrap_or(&[]))?,
+        creator_fee_share_bps: Self::unpack_optional_fee_bps(rest.get(21..).unwrap_or(&[]))?,
+        referrer_fee_share_bps: Self::unpack_optional_fee_bps(rest.get(23..).unwrap_or(&[]))?,
+        trading_close_ts: Self::unpack_optional_amount(rest.get(25..).unwrap_or(&[]), 0)? as i64,
+        resolution_ts: Self::unpack_optional_amount(rest.get(33..).unwrap_or(&[]), 0)? as i64,
+        strike_price: Self::unpack_optional_amount(rest.get(41..).unwrap_or(&[]), 0)?,
+        comparison: match rest.get(49) {
+          None | Some(0) => Comparison::Above,
+          Some(1) => Comparison::AtOrAbove,
+          Some(2) => Comparison::Below,
+          Some(3) => Comparison::AtOrBelow,
+          _ => return Err(InvalidInstruction.into()),
+        },
+        oracle: Self::unpack_optional_pubkey(rest.get(50..).unwrap_or(&[]))?,
+        strike_expo: match rest.get(82..) {
+          None | Some([]) => 0,
+          Some(input) => input
+            .get(..4)
+            .and_then(|slice| slice.try_into().ok())
+            .map(i32::from_le_bytes)
+            .ok_or(InvalidInstruction)?,
+        },
+        twap_window: Self::unpack_optional_amount(rest.get(86..).unwrap_or(&[]), 0)? as i64,
+        min_twap_samples: rest.get(94).copied().unwrap_or(0),
+        oracle_kind: match rest.get(95) {
+          None | Some(0) => OracleKind::Pyth,
+          Some(1) => OracleKind::Switchboard,
+          #[cfg(any(test, feature = "mock-oracle"))]
+          Some(2) => OracleKind::Mock,
+          _ => return Err(InvalidInstruction.into()),
+        },
+        dispute_window: Self::unpack_optional_amount(
+          rest.get(96..).unwrap_or(&[]),
+          state::DEFAULT_DISPUTE_WINDOW_SECS as u64,
+        )? as i64,
+        resolution_bond: Self::unpack_optional_amount(rest.get(104..).unwrap_or(&[]), 0)?,
+      },
       3 => Self::OfferTrade {
-        is_yes: state::get_u8_at_ptr(rest_ptr) == 1,
-        price: state::get_u64_at_ptr_offset(rest_ptr, 1),
-        amount: state::get_u64_at_ptr_offset(rest_ptr, 9),
+        is_yes: Self::unpack_is_yes(rest)?,
+        price: Self::unpack_amount(rest.get(1..).unwrap_or(&[]))?,
+        amount: Self::unpack_amount(rest.get(9..).unwrap_or(&[]))?,
+        time_in_force: Self::unpack_time_in_force(rest.get(17))?,
+        has_referrer: rest.get(18) == Some(&1),
       },
       4 => Self::Payout {},
+      #[cfg(feature = "faucet")]
       5 => Self::FreeMint {
         amount: Self::unpack_amount(rest)?,
       },
       6 => Self::JudgeBettingMarketManually {
         result: Self::unpack_amount(rest)?,
+        yes_payout: Self::unpack_optional_amount(
+          rest.get(8..).unwrap_or(&[]),
+          state::DEFAULT_VOID_YES_PAYOUT,
+        )?,
       },
       7 => Self::JudgeBettingMarketOracle {},
       8 => Self::SetStrikePrice {
         strike_price: Self::unpack_amount(rest)?,
       },
+      9 => Self::CancelOrder {
+        is_yes: Self::unpack_is_yes(rest)?,
+        price: Self::unpack_amount(rest.get(1..).unwrap_or(&[]))?,
+      },
+      10 => Self::MarketOrder {
+        is_yes: Self::unpack_is_yes(rest)?,
+        worst_price: Self::unpack_amount(rest.get(1..).unwrap_or(&[]))?,
+        size: match rest.get(9) {
+          Some(0) => MarketOrderSize::Quantity(Self::unpack_amount(rest.get(10..).unwrap_or(&[]))?),
+          Some(1) => MarketOrderSize::Budget(Self::unpack_amount(rest.get(10..).unwrap_or(&[]))?),
+          _ => return Err(InvalidInstruction.into()),
+        },
+      },
+      11 => Self::SetJudge {},
+      12 => Self::CreateClaimAccount {},
+      13 => Self::ReclaimOpenOrders {},
+      14 => Self::InitCategoricalMarket {},
+      15 => Self::SplitCategoricalSet {
+        amount: Self::unpack_amount(rest)?,
+      },
+      16 => Self::MergeCategoricalSet {
+        amount: Self::unpack_amount(rest)?,
+      },
+      17 => Self::JudgeCategoricalMarket {
+        winning_outcome: Self::unpack_amount(rest)?,
+      },
+      18 => Self::SplitCompleteSet {
+        amount: Self::unpack_amount(rest)?,
+      },
+      19 => Self::MergeCompleteSet {
+        amount: Self::unpack_amount(rest)?,
+      },
+      20 => Self::WithdrawFees {},
+      21 => Self::RecordOracleSample {},
+      22 => Self::ProposeResult {
+        result: Self::unpack_amount(rest)?,
+        yes_payout: Self::unpack_optional_amount(
+          rest.get(8..).unwrap_or(&[]),
+          state::DEFAULT_VOID_YES_PAYOUT,
+        )?,
+      },
+      23 => Self::DisputeResult {},
+      24 => Self::FinalizeResult {},
+      _ => return Err(InvalidInstruction.into()),
+    })
+  }
+
+  // time in force is optional trailing data so older clients keep placing good till cancelled orders
+  fn unpack_time_in_force(input: Option<&u8>) -> Result<TimeInForce, ProgramError> {
+    Ok(match input {
+      None | Some(0) => TimeInForce::GoodTillCancelled,
+      Some(1) => TimeInForce::ImmediateOrCancel,
+      Some(2) => TimeInForce::FillOrKill,
+      Some(3) => TimeInForce::PostOnly,
       _ => return Err(InvalidInstruction.into()),
     })
   }
 
+  // trailing amounts are optional so older clients keep working, missing ones take the default
+  fn unpack_optional_amount(input: &[u8], default: u64) -> Result<u64, ProgramError> {
+    if input.is_empty() {
+      return Ok(default);
+    }
+    Self::unpack_amount(input)
+  }
+
+  // fees are optional trailing data after the market type and bounds, missing fees are 0
+  fn unpack_optional_fee_bps(input: &[u8]) -> Result<u16, ProgramError> {
+    if input.is_empty() {
+      return Ok(0);
+    }
+    let fee_bps = input
+      .get(..2)
+      .and_then(|slice| slice.try_into().ok())
+      .map(u16::from_le_bytes)
+      .ok_or(InvalidInstruction)?;
+    Ok(fee_bps)
+  }
+
+  // a missing pubkey is the null pubkey
+  fn unpack_optional_pubkey(input: &[u8]) -> Result<Pubkey, ProgramError> {
+    if input.is_empty() {
+      return Ok(state::NULL_PUBKEY);
+    }
+    let pubkey = input
+      .get(..32)
+      .and_then(|slice| slice.try_into().ok())
+      .map(Pubkey::new_from_array)
+      .ok_or(InvalidInstruction)?;
+    Ok(pubkey)
+  }
+
+  // side of the book, 1 for yes and anything else for no
+  fn unpack_is_yes(input: &[u8]) -> Result<bool, ProgramError> {
+    let is_yes = input.first().ok_or(InvalidInstruction)?;
+    Ok(*is_yes == 1)
+  }
+
   fn unpack_amount(input: &[u8]) -> Result<u64, ProgramError> {
     let amount = input
       .get(..8)
@@ -1,7 +1,14 @@
+pub mod categorical;
+pub mod claim;
 pub mod error;
 pub mod instruction;
+pub mod oracle;
+pub mod oracle_adapter;
+pub mod orderbook;
 pub mod processor;
 pub mod state;
+pub mod twap;
+pub mod validation;
 
 #[cfg(not(feature = "no-entrypoint"))]
 pub mod entrypoint;
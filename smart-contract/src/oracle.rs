@@ -0,0 +1,90 @@
+use crate::{
+  error::BettingMarketError,
+  oracle_adapter::{self, OraclePrice},
+};
+use solana_program::{
+  account_info::AccountInfo, clock::Clock, msg, program_error::ProgramError, pubkey::Pubkey,
+};
+use std::convert::TryFrom;
+
+// a price published longer ago than this many seconds is too stale to judge with
+pub const MAX_PRICE_STALENESS_SECS: i64 = 10;
+
+// read the price of the betting market's oracle account with the adapter for its oracle kind,
+// failing if it was published longer ago than the staleness window
+pub fn get_current_price(
+  oracle_kind: u8,
+  oracle_account: &AccountInfo,
+  clock: &Clock,
+  program_id: &Pubkey,
+) -> Result<OraclePrice, ProgramError> {
+  let oracle_price =
+    oracle_adapter::get_adapter(oracle_kind)?.read_price(oracle_account, clock, program_id)?;
+  msg!(
+    "oracle publish time: {}, current time: {}",
+    oracle_price.publish_time,
+    clock.unix_timestamp
+  );
+  if clock
+    .unix_timestamp
+    .saturating_sub(oracle_price.publish_time)
+    > MAX_PRICE_STALENESS_SECS
+  {
+    return Err(BettingMarketError::StaleOraclePrice.into());
+  }
+  Ok(oracle_price)
+}
+
+// rescale value * 10^from_expo to the same amount at a finer exponent, none if it doesn't fit
+pub fn rescale(value: i128, from_expo: i32, to_expo: i32) -> Option<i128> {
+  let shift = u32::try_from(from_expo.checked_sub(to_expo)?).ok()?;
+  10i128
+    .checked_pow(shift)
+    .and_then(|multiplier| value.checked_mul(multiplier))
+}
+
+// whether the strike lies within the confidence band around the price, all at the same exponent,
+// in which case the side of the strike the price is on is not known
+pub fn is_within_confidence(price: i128, conf: i128, strike_price: i128) -> bool {
+  match price.checked_sub(strike_price) {
+    Some(distance) => distance.unsigned_abs() <= conf.unsigned_abs(),
+    None => false,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn rescale_to_a_finer_exponent() {
+    // 123.45 at expo -2 is 12345000 at expo -5
+    assert_eq!(rescale(12345, -2, -5), Some(12_345_000));
+    // a strike of 50000 at expo 0 against a feed at expo -8
+    assert_eq!(rescale(50_000, 0, -8), Some(5_000_000_000_000));
+    assert_eq!(rescale(-7, 3, -1), Some(-70_000));
+    assert_eq!(rescale(42, -6, -6), Some(42));
+  }
+
+  #[test]
+  fn rescale_refuses_coarser_exponents() {
+    assert_eq!(rescale(12345, -5, -2), None);
+    assert_eq!(rescale(1, 0, 1), None);
+  }
+
+  #[test]
+  fn rescale_overflow_is_none() {
+    assert_eq!(rescale(1, 0, -39), None);
+    assert_eq!(rescale(i128::MAX / 10 + 1, 0, -1), None);
+    assert_eq!(rescale(1, i32::MAX, i32::MIN), None);
+    assert_eq!(rescale(i128::MAX / 10, 0, -1), Some(i128::MAX / 10 * 10));
+  }
+
+  #[test]
+  fn confidence_band_includes_its_edges() {
+    assert!(is_within_confidence(100, 5, 105));
+    assert!(is_within_confidence(100, 5, 95));
+    assert!(!is_within_confidence(100, 5, 106));
+    assert!(!is_within_confidence(100, -5, 94));
+  }
+}
@@ -0,0 +1,293 @@
+#[cfg(any(test, feature = "mock-oracle"))]
+use crate::state::MOCK_ORACLE_KIND;
+use crate::{
+  error::BettingMarketError,
+  state::{PYTH_ORACLE_KIND, SWITCHBOARD_ORACLE_KIND},
+};
+use arrayref::{array_ref, array_refs};
+use pyth_client::{AccountType, Price, PriceStatus, PriceType, MAGIC, VERSION};
+use solana_program::{
+  account_info::AccountInfo,
+  clock::{Clock, DEFAULT_MS_PER_SLOT},
+  program_error::ProgramError,
+  pubkey::Pubkey,
+};
+use std::convert::TryFrom;
+
+// a price of price * 10^expo +- conf * 10^expo published at a unix timestamp
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OraclePrice {
+  pub price: i64,
+  pub conf: u64,
+  pub expo: i32,
+  pub publish_time: i64,
+}
+
+// reads a normalized price out of one kind of oracle account
+pub trait OracleAdapter {
+  // the current price of the oracle account, failing if the account is not this kind of oracle
+  // or has no usable price
+  fn read_price(
+    &self,
+    oracle_account: &AccountInfo,
+    clock: &Clock,
+    program_id: &Pubkey,
+  ) -> Result<OraclePrice, ProgramError>;
+}
+
+// the adapter for an oracle kind stored in a betting market
+pub fn get_adapter(oracle_kind: u8) -> Result<&'static dyn OracleAdapter, ProgramError> {
+  match oracle_kind {
+    PYTH_ORACLE_KIND => Ok(&PythAdapter),
+    SWITCHBOARD_ORACLE_KIND => Ok(&SwitchboardAdapter),
+    #[cfg(any(test, feature = "mock-oracle"))]
+    MOCK_ORACLE_KIND => Ok(&MockOracleAdapter),
+    _ => Err(BettingMarketError::InvalidOracleAccount.into()),
+  }
+}
+
+// pyth v2 price accounts, the aggregate price has to be trading
+// pyth doesn't record a publish time, so it is estimated from the slot the aggregate became valid in
+pub struct PythAdapter;
+
+// read a pyth price account, checking it really is a pyth price account
+pub fn load_pyth_price_account(data: &[u8]) -> Result<&Price, ProgramError> {
+  if data.len() < std::mem::size_of::<Price>() {
+    return Err(BettingMarketError::InvalidOracleAccount.into());
+  }
+  let price_account = pyth_client::cast::<Price>(data);
+  if price_account.magic != MAGIC
+    || price_account.ver != VERSION
+    || price_account.atype != AccountType::Price as u32
+    || !matches!(price_account.ptype, PriceType::Price)
+  {
+    return Err(BettingMarketError::InvalidOracleAccount.into());
+  }
+  Ok(price_account)
+}
+
+impl OracleAdapter for PythAdapter {
+  fn read_price(
+    &self,
+    oracle_account: &AccountInfo,
+    clock: &Clock,
+    _program_id: &Pubkey,
+  ) -> Result<OraclePrice, ProgramError> {
+    let data = oracle_account.try_borrow_data()?;
+    let price_account = load_pyth_price_account(&data)?;
+    if !matches!(price_account.agg.status, PriceStatus::Trading) {
+      return Err(BettingMarketError::OraclePriceNotTrading.into());
+    }
+    let slots_since_valid = clock.slot.saturating_sub(price_account.valid_slot);
+    let ms_since_valid = slots_since_valid.saturating_mul(DEFAULT_MS_PER_SLOT);
+    Ok(OraclePrice {
+      price: price_account.agg.price,
+      conf: price_account.agg.conf,
+      expo: price_account.expo,
+      publish_time: clock
+        .unix_timestamp
+        .saturating_sub(i64::try_from(ms_since_valid / 1000).unwrap_or(i64::MAX)),
+    })
+  }
+}
+
+// switchboard-like aggregator account layout
+// 0..8 - SWITCHBOARD_AGGREGATOR_MAGIC
+// 8..24 - i128 - latest confirmed result mantissa
+// 24..28 - u32 - latest confirmed result scale, the result is mantissa * 10^-scale
+// 28..44 - i128 - standard deviation of the oracle responses mantissa
+// 44..48 - u32 - standard deviation scale
+// 48..56 - i64 - unix timestamp the latest confirmed round was opened at
+// 56..60 - u32 - number of successful oracle responses in the latest confirmed round
+// 60..64 - u32 - minimum oracle responses for a round to be valid
+//
+// the standard deviation is used as the confidence interval
+// this is a synthetic layout modeled on switchboard aggregators, not the switchboard v1 AggregatorState, so
+// the adapter doesn't check the account owner: whoever creates a market picks its aggregator account and
+// traders have to vet it, same as the judge
+pub struct SwitchboardAdapter;
+
+pub const SWITCHBOARD_AGGREGATOR_MAGIC: [u8; 8] = *b"sbaggr01";
+pub const SWITCHBOARD_AGGREGATOR_LEN: usize = 64;
+
+impl OracleAdapter for SwitchboardAdapter {
+  fn read_price(
+    &self,
+    oracle_account: &AccountInfo,
+    _clock: &Clock,
+    _program_id: &Pubkey,
+  ) -> Result<OraclePrice, ProgramError> {
+    let data = oracle_account.try_borrow_data()?;
+    let src = data
+      .get(..SWITCHBOARD_AGGREGATOR_LEN)
+      .ok_or(BettingMarketError::InvalidOracleAccount)?;
+    let src = array_ref![src, 0, SWITCHBOARD_AGGREGATOR_LEN];
+    let (
+      magic,
+      result_mantissa,
+      result_scale,
+      std_deviation_mantissa,
+      std_deviation_scale,
+      round_open_timestamp,
+      num_success,
+      min_responses,
+    ) = array_refs![src, 8, 16, 4, 16, 4, 8, 4, 4];
+    if *magic != SWITCHBOARD_AGGREGATOR_MAGIC {
+      return Err(BettingMarketError::InvalidOracleAccount.into());
+    }
+    if u32::from_le_bytes(*num_success) < u32::from_le_bytes(*min_responses) {
+      return Err(BettingMarketError::OraclePriceNotTrading.into());
+    }
+
+    let result_scale = u32::from_le_bytes(*result_scale);
+    let std_deviation_scale = u32::from_le_bytes(*std_deviation_scale);
+    let price = i64::try_from(i128::from_le_bytes(*result_mantissa))
+      .map_err(|_| BettingMarketError::AmountOverflow)?;
+    // bring the standard deviation to the result's scale, rounding up so the band never shrinks
+    let std_deviation = i128::from_le_bytes(*std_deviation_mantissa).unsigned_abs();
+    let conf = if std_deviation_scale >= result_scale {
+      let divisor = 10u128
+        .checked_pow(std_deviation_scale - result_scale)
+        .ok_or(BettingMarketError::AmountOverflow)?;
+      std_deviation / divisor + u128::from(std_deviation % divisor != 0)
+    } else {
+      10u128
+        .checked_pow(result_scale - std_deviation_scale)
+        .and_then(|multiplier| std_deviation.checked_mul(multiplier))
+        .ok_or(BettingMarketError::AmountOverflow)?
+    };
+    Ok(OraclePrice {
+      price,
+      conf: u64::try_from(conf).map_err(|_| BettingMarketError::AmountOverflow)?,
+      expo: -i32::try_from(result_scale).map_err(|_| BettingMarketError::AmountOverflow)?,
+      publish_time: i64::from_le_bytes(*round_open_timestamp),
+    })
+  }
+}
+
+// local mock oracle account layout, owned by this program
+// 0..8 - MOCK_ORACLE_MAGIC
+// 8..16 - i64 - price
+// 16..24 - u64 - confidence
+// 24..28 - i32 - exponent
+// 28..36 - i64 - publish time
+//
+// the program never writes this layout, so mock oracles only exist where tests add the account
+// directly, and it is only compiled into test builds and builds with the mock-oracle feature
+#[cfg(any(test, feature = "mock-oracle"))]
+pub struct MockOracleAdapter;
+
+#[cfg(any(test, feature = "mock-oracle"))]
+pub const MOCK_ORACLE_MAGIC: [u8; 8] = *b"mockorcl";
+#[cfg(any(test, feature = "mock-oracle"))]
+pub const MOCK_ORACLE_LEN: usize = 36;
+
+#[cfg(any(test, feature = "mock-oracle"))]
+impl OracleAdapter for MockOracleAdapter {
+  fn read_price(
+    &self,
+    oracle_account: &AccountInfo,
+    _clock: &Clock,
+    program_id: &Pubkey,
+  ) -> Result<OraclePrice, ProgramError> {
+    if oracle_account.owner != program_id {
+      return Err(BettingMarketError::InvalidOracleAccount.into());
+    }
+    let data = oracle_account.try_borrow_data()?;
+    let src = data
+      .get(..MOCK_ORACLE_LEN)
+      .ok_or(BettingMarketError::InvalidOracleAccount)?;
+    let src = array_ref![src, 0, MOCK_ORACLE_LEN];
+    let (magic, price, conf, expo, publish_time) = array_refs![src, 8, 8, 8, 4, 8];
+    if *magic != MOCK_ORACLE_MAGIC {
+      return Err(BettingMarketError::InvalidOracleAccount.into());
+    }
+    Ok(OraclePrice {
+      price: i64::from_le_bytes(*price),
+      conf: u64::from_le_bytes(*conf),
+      expo: i32::from_le_bytes(*expo),
+      publish_time: i64::from_le_bytes(*publish_time),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // aggregator with a confirmed result of mantissa * 10^-scale
+  fn new_switchboard_aggregator(
+    result_mantissa: i128,
+    result_scale: u32,
+    std_deviation_mantissa: i128,
+    std_deviation_scale: u32,
+    num_success: u32,
+  ) -> Vec<u8> {
+    let mut data = vec![0; SWITCHBOARD_AGGREGATOR_LEN];
+    data[..8].copy_from_slice(&SWITCHBOARD_AGGREGATOR_MAGIC);
+    data[8..24].copy_from_slice(&result_mantissa.to_le_bytes());
+    data[24..28].copy_from_slice(&result_scale.to_le_bytes());
+    data[28..44].copy_from_slice(&std_deviation_mantissa.to_le_bytes());
+    data[44..48].copy_from_slice(&std_deviation_scale.to_le_bytes());
+    data[48..56].copy_from_slice(&1_000i64.to_le_bytes());
+    data[56..60].copy_from_slice(&num_success.to_le_bytes());
+    data[60..64].copy_from_slice(&3u32.to_le_bytes());
+    data
+  }
+
+  fn read_switchboard_price(data: &mut [u8]) -> Result<OraclePrice, ProgramError> {
+    let (key, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let mut lamports = 0;
+    let oracle_account =
+      AccountInfo::new(&key, false, false, &mut lamports, data, &owner, false, 0);
+    SwitchboardAdapter.read_price(&oracle_account, &Clock::default(), &Pubkey::new_unique())
+  }
+
+  #[test]
+  fn switchboard_rounds_the_standard_deviation_up_to_the_result_scale() {
+    // 105.25 +- 0.1234
+    let mut data = new_switchboard_aggregator(10_525, 2, 1_234, 4, 3);
+    assert_eq!(
+      read_switchboard_price(&mut data),
+      Ok(OraclePrice {
+        price: 10_525,
+        conf: 13,
+        expo: -2,
+        publish_time: 1_000,
+      })
+    );
+  }
+
+  #[test]
+  fn switchboard_scales_a_coarser_standard_deviation_up() {
+    // 105.25 +- 2
+    let mut data = new_switchboard_aggregator(10_525, 2, -2, 0, 4);
+    assert_eq!(
+      read_switchboard_price(&mut data).map(|price| price.conf),
+      Ok(200)
+    );
+  }
+
+  #[test]
+  fn switchboard_rejects_rounds_with_too_few_responses() {
+    let mut data = new_switchboard_aggregator(10_525, 2, 1_234, 4, 2);
+    assert_eq!(
+      read_switchboard_price(&mut data),
+      Err(BettingMarketError::OraclePriceNotTrading.into())
+    );
+  }
+
+  #[test]
+  fn switchboard_rejects_other_accounts() {
+    let mut data = new_switchboard_aggregator(10_525, 2, 1_234, 4, 3);
+    data[0] = 0;
+    assert_eq!(
+      read_switchboard_price(&mut data),
+      Err(BettingMarketError::InvalidOracleAccount.into())
+    );
+    assert_eq!(
+      read_switchboard_price(&mut data[..SWITCHBOARD_AGGREGATOR_LEN - 1]),
+      Err(BettingMarketError::InvalidOracleAccount.into())
+    );
+  }
+}
@@ -0,0 +1,516 @@
+use crate::{
+  error::BettingMarketError,
+  state::{
+    get_pubkey, get_u64, set_pubkey, set_u64, MAX_MAKERS_PER_MATCH, NULL_PUBKEY, PUBKEY_USIZE,
+    U64_USIZE,
+  },
+};
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+
+// order book data layout
+// dedicated account owned by this program, sized by the client when it is created
+// 0..1 - is initialized
+// 8..40 - betting market the order book belongs to
+// 40..48 - u64 - node capacity
+// 48..56 - u64 - free list head (node index + 1, 0 if empty)
+// 56..64 - u64 - number of nodes ever allocated (nodes past this have never been used)
+// 100..4948 - [[(u64, u64, u64); 101]; 2] - (head, tail, total amount) per price for yes, no side
+// 5000.. - [Node; capacity] - resting orders
+//
+// node layout
+// 0..32 - Pubkey - user account
+// 32..40 - u64 - remaining order size
+// 40..48 - u64 - next node index + 1 (0 if last), also links the free list
+// 48..56 - u64 - payout in usd instead of token bool {1, 2}, 0 if free
+//
+// every price level is a singly linked fifo of nodes so there is no limit on the number of orders at
+// a price, and nodes of filled or cancelled orders are pushed onto the free list to be reused
+
+pub const ORDER_BOOK_MARKET_OFFSET: usize = 8;
+pub const ORDER_BOOK_CAPACITY_OFFSET: usize = 40;
+pub const ORDER_BOOK_FREE_LIST_HEAD_OFFSET: usize = 48;
+pub const ORDER_BOOK_ALLOCATED_OFFSET: usize = 56;
+pub const ORDER_BOOK_LEVELS_OFFSET: usize = 100;
+pub const ORDER_BOOK_NODES_OFFSET: usize = 5000;
+
+pub const LEVEL_LEN: usize = 24;
+pub const LEVEL_HEAD_OFFSET: usize = 0;
+pub const LEVEL_TAIL_OFFSET: usize = 8;
+pub const LEVEL_AMOUNT_OFFSET: usize = 16;
+pub const LEVELS_PER_SIDE: usize = 101;
+
+pub const NODE_LEN: usize = 56;
+pub const NODE_USER_ACCOUNT_OFFSET: usize = 0;
+pub const NODE_AMOUNT_OFFSET: usize = PUBKEY_USIZE;
+pub const NODE_NEXT_OFFSET: usize = PUBKEY_USIZE + U64_USIZE;
+pub const NODE_PAYOUT_IN_USD_OFFSET: usize = PUBKEY_USIZE + 2 * U64_USIZE;
+
+pub const NULL_NODE: u64 = 0;
+
+// minimum size of an order book account so it can hold at least one order
+pub const ORDER_BOOK_MIN_LEN: usize = ORDER_BOOK_NODES_OFFSET + NODE_LEN;
+
+// a resting order read out of the order book
+pub struct Order {
+  pub user_account: Pubkey,
+  pub amount: u64,
+  pub payout_in_usd: bool,
+}
+
+// number of orders an order book account of data_len bytes can hold
+pub fn capacity_for_len(data_len: usize) -> u64 {
+  (data_len.saturating_sub(ORDER_BOOK_NODES_OFFSET) / NODE_LEN) as u64
+}
+
+pub fn is_initialized(data: &[u8]) -> bool {
+  data.len() >= ORDER_BOOK_MIN_LEN && data[0] == 1
+}
+
+pub fn get_market(data: &[u8]) -> Pubkey {
+  get_pubkey(data, ORDER_BOOK_MARKET_OFFSET)
+}
+
+// initialize a zeroed order book account for a betting market
+pub fn init_order_book(data: &mut [u8], market: &Pubkey) -> Result<(), ProgramError> {
+  let capacity = capacity_for_len(data.len());
+  if capacity == 0 {
+    return Err(BettingMarketError::AccountTooSmall.into());
+  }
+  data[0] = 1;
+  set_pubkey(data, ORDER_BOOK_MARKET_OFFSET, market);
+  set_u64(data, ORDER_BOOK_CAPACITY_OFFSET, capacity);
+  set_u64(data, ORDER_BOOK_FREE_LIST_HEAD_OFFSET, NULL_NODE);
+  set_u64(data, ORDER_BOOK_ALLOCATED_OFFSET, 0);
+  Ok(())
+}
+
+fn level_offset(is_yes: bool, price: u64) -> usize {
+  let side = if is_yes { 0 } else { 1 };
+  ORDER_BOOK_LEVELS_OFFSET + LEVEL_LEN * (side * LEVELS_PER_SIDE + price as usize)
+}
+
+// node indices are stored off by one so that a zeroed account is an empty order book
+fn node_offset(node: u64) -> usize {
+  ORDER_BOOK_NODES_OFFSET + NODE_LEN * (node - 1) as usize
+}
+
+// total size of all resting orders at the price
+pub fn get_level_amount(data: &[u8], is_yes: bool, price: u64) -> u64 {
+  get_u64(data, level_offset(is_yes, price) + LEVEL_AMOUNT_OFFSET)
+}
+
+fn set_level_amount(data: &mut [u8], is_yes: bool, price: u64, amount: u64) {
+  set_u64(
+    data,
+    level_offset(is_yes, price) + LEVEL_AMOUNT_OFFSET,
+    amount,
+  );
+}
+
+// size of the resting orders at the price, walked in fifo order up to max_amount, that a trade can match
+// before it would match more than MAX_MAKERS_PER_MATCH distinct makers, makers holds the makers the trade
+// matched so far and gets the ones it would match here added
+// returns the size and whether the walk stopped at the order of a maker past the limit
+pub fn get_matchable_level_amount(
+  data: &[u8],
+  is_yes: bool,
+  price: u64,
+  max_amount: u64,
+  makers: &mut Vec<Pubkey>,
+) -> (u64, bool) {
+  let mut amount = 0u64;
+  let mut node = get_u64(data, level_offset(is_yes, price) + LEVEL_HEAD_OFFSET);
+  while node != NULL_NODE && amount < max_amount {
+    let order = get_order(data, node);
+    if !makers.contains(&order.user_account) {
+      if makers.len() >= MAX_MAKERS_PER_MATCH {
+        return (amount, true);
+      }
+      makers.push(order.user_account);
+    }
+    amount += std::cmp::min(order.amount, max_amount - amount);
+    node = get_next_node(data, node);
+  }
+  (amount, false)
+}
+
+// total size of resting inverse orders that an order at the limit price would cross, up to max_amount
+// and without matching more than MAX_MAKERS_PER_MATCH distinct makers
+pub fn get_crossable_amount(
+  data: &[u8],
+  is_yes: bool,
+  limit_price: u64,
+  max_amount: u64,
+) -> Result<u64, ProgramError> {
+  let mut makers = Vec::new();
+  let mut total = 0u64;
+  for inverse_buy_price in ((100 - limit_price)..101).rev() {
+    let (amount, is_maker_limit_reached) = get_matchable_level_amount(
+      data,
+      !is_yes,
+      inverse_buy_price,
+      max_amount - total,
+      &mut makers,
+    );
+    total = total
+      .checked_add(amount)
+      .ok_or(BettingMarketError::AmountOverflow)?;
+    if is_maker_limit_reached || total == max_amount {
+      break;
+    }
+  }
+  Ok(total)
+}
+
+pub fn get_order(data: &[u8], node: u64) -> Order {
+  let offset = node_offset(node);
+  Order {
+    user_account: get_pubkey(data, offset + NODE_USER_ACCOUNT_OFFSET),
+    amount: get_u64(data, offset + NODE_AMOUNT_OFFSET),
+    payout_in_usd: get_u64(data, offset + NODE_PAYOUT_IN_USD_OFFSET) == 2,
+  }
+}
+
+fn get_next_node(data: &[u8], node: u64) -> u64 {
+  get_u64(data, node_offset(node) + NODE_NEXT_OFFSET)
+}
+
+fn set_next_node(data: &mut [u8], node: u64, next: u64) {
+  set_u64(data, node_offset(node) + NODE_NEXT_OFFSET, next);
+}
+
+// oldest resting order at the price, if any
+pub fn front_order(data: &[u8], is_yes: bool, price: u64) -> Option<Order> {
+  let head = get_u64(data, level_offset(is_yes, price) + LEVEL_HEAD_OFFSET);
+  if head == NULL_NODE {
+    None
+  } else {
+    Some(get_order(data, head))
+  }
+}
+
+// take a node off the free list, or the next never used node if the free list is empty
+fn allocate_node(data: &mut [u8]) -> Result<u64, ProgramError> {
+  let free_list_head = get_u64(data, ORDER_BOOK_FREE_LIST_HEAD_OFFSET);
+  if free_list_head != NULL_NODE {
+    let next_free = get_next_node(data, free_list_head);
+    set_u64(data, ORDER_BOOK_FREE_LIST_HEAD_OFFSET, next_free);
+    return Ok(free_list_head);
+  }
+  let capacity = get_u64(data, ORDER_BOOK_CAPACITY_OFFSET);
+  let allocated = get_u64(data, ORDER_BOOK_ALLOCATED_OFFSET);
+  if allocated >= capacity {
+    return Err(BettingMarketError::OrderBookFull.into());
+  }
+  set_u64(data, ORDER_BOOK_ALLOCATED_OFFSET, allocated + 1);
+  Ok(allocated + 1)
+}
+
+// clear a node and push it onto the free list
+fn free_node(data: &mut [u8], node: u64) {
+  let offset = node_offset(node);
+  set_pubkey(data, offset + NODE_USER_ACCOUNT_OFFSET, &NULL_PUBKEY);
+  set_u64(data, offset + NODE_AMOUNT_OFFSET, 0);
+  set_u64(data, offset + NODE_PAYOUT_IN_USD_OFFSET, 0);
+  let free_list_head = get_u64(data, ORDER_BOOK_FREE_LIST_HEAD_OFFSET);
+  set_next_node(data, node, free_list_head);
+  set_u64(data, ORDER_BOOK_FREE_LIST_HEAD_OFFSET, node);
+}
+
+// append an order to the back of the fifo at the price and add its size to the level amount
+pub fn push_order(
+  data: &mut [u8],
+  is_yes: bool,
+  price: u64,
+  user_account: &Pubkey,
+  amount: u64,
+  payout_in_usd: bool,
+) -> Result<(), ProgramError> {
+  let level_amount = get_level_amount(data, is_yes, price)
+    .checked_add(amount)
+    .ok_or(BettingMarketError::AmountOverflow)?;
+  let node = allocate_node(data)?;
+  let offset = node_offset(node);
+  set_pubkey(data, offset + NODE_USER_ACCOUNT_OFFSET, user_account);
+  set_u64(data, offset + NODE_AMOUNT_OFFSET, amount);
+  set_u64(
+    data,
+    offset + NODE_PAYOUT_IN_USD_OFFSET,
+    if payout_in_usd { 2 } else { 1 },
+  );
+  set_next_node(data, node, NULL_NODE);
+
+  let level = level_offset(is_yes, price);
+  let tail = get_u64(data, level + LEVEL_TAIL_OFFSET);
+  if tail == NULL_NODE {
+    set_u64(data, level + LEVEL_HEAD_OFFSET, node);
+  } else {
+    set_next_node(data, tail, node);
+  }
+  set_u64(data, level + LEVEL_TAIL_OFFSET, node);
+  set_level_amount(data, is_yes, price, level_amount);
+  Ok(())
+}
+
+// fill part or all of the oldest order at the price, popping it off the fifo once fully filled
+pub fn fill_front_order(
+  data: &mut [u8],
+  is_yes: bool,
+  price: u64,
+  fill_amount: u64,
+) -> Result<(), ProgramError> {
+  let level = level_offset(is_yes, price);
+  let head = get_u64(data, level + LEVEL_HEAD_OFFSET);
+  if head == NULL_NODE {
+    return Ok(());
+  }
+  let order = get_order(data, head);
+  let fill_amount = std::cmp::min(fill_amount, order.amount);
+  let remaining_amount = order.amount - fill_amount;
+  set_u64(
+    data,
+    node_offset(head) + NODE_AMOUNT_OFFSET,
+    remaining_amount,
+  );
+  let level_amount = get_level_amount(data, is_yes, price)
+    .checked_sub(fill_amount)
+    .ok_or(BettingMarketError::AmountOverflow)?;
+  set_level_amount(data, is_yes, price, level_amount);
+  if remaining_amount == 0 {
+    let next = get_next_node(data, head);
+    set_u64(data, level + LEVEL_HEAD_OFFSET, next);
+    if next == NULL_NODE {
+      set_u64(data, level + LEVEL_TAIL_OFFSET, NULL_NODE);
+    }
+    free_node(data, head);
+  }
+  Ok(())
+}
+
+// unlink and free every order of the user at the price
+// returns the (usd collateralized, inverse collateralized) order sizes that were removed
+pub fn remove_user_orders(
+  data: &mut [u8],
+  is_yes: bool,
+  price: u64,
+  user_account: &Pubkey,
+) -> Result<(u64, u64), ProgramError> {
+  let level = level_offset(is_yes, price);
+  let mut usd_collateralized_amount = 0u64;
+  let mut inverse_collateralized_amount = 0u64;
+  let mut prev = NULL_NODE;
+  let mut node = get_u64(data, level + LEVEL_HEAD_OFFSET);
+  while node != NULL_NODE {
+    let next = get_next_node(data, node);
+    let order = get_order(data, node);
+    if order.user_account == *user_account {
+      // payout in usd orders were collateralized by burning inverse tokens
+      let collateralized_amount = if order.payout_in_usd {
+        &mut inverse_collateralized_amount
+      } else {
+        &mut usd_collateralized_amount
+      };
+      *collateralized_amount = collateralized_amount
+        .checked_add(order.amount)
+        .ok_or(BettingMarketError::AmountOverflow)?;
+      if prev == NULL_NODE {
+        set_u64(data, level + LEVEL_HEAD_OFFSET, next);
+      } else {
+        set_next_node(data, prev, next);
+      }
+      if next == NULL_NODE {
+        set_u64(data, level + LEVEL_TAIL_OFFSET, prev);
+      }
+      free_node(data, node);
+    } else {
+      prev = node;
+    }
+    node = next;
+  }
+  let level_amount = get_level_amount(data, is_yes, price)
+    .checked_sub(usd_collateralized_amount)
+    .and_then(|level_amount| level_amount.checked_sub(inverse_collateralized_amount))
+    .ok_or(BettingMarketError::AmountOverflow)?;
+  set_level_amount(data, is_yes, price, level_amount);
+  Ok((usd_collateralized_amount, inverse_collateralized_amount))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn new_order_book(capacity: usize) -> Vec<u8> {
+    let mut data = vec![0; ORDER_BOOK_NODES_OFFSET + NODE_LEN * capacity];
+    init_order_book(&mut data, &Pubkey::new_unique()).unwrap();
+    data
+  }
+
+  fn level_orders(data: &[u8], is_yes: bool, price: u64) -> Vec<(Pubkey, u64)> {
+    let mut orders = Vec::new();
+    let mut node = get_u64(data, level_offset(is_yes, price) + LEVEL_HEAD_OFFSET);
+    while node != NULL_NODE {
+      let order = get_order(data, node);
+      orders.push((order.user_account, order.amount));
+      node = get_next_node(data, node);
+    }
+    orders
+  }
+
+  #[test]
+  fn init_rejects_account_without_room_for_an_order() {
+    let mut data = vec![0; ORDER_BOOK_MIN_LEN - 1];
+    assert_eq!(
+      init_order_book(&mut data, &Pubkey::new_unique()),
+      Err(BettingMarketError::AccountTooSmall.into())
+    );
+    assert!(!is_initialized(&data));
+  }
+
+  #[test]
+  fn push_order_appends_to_the_level() {
+    let mut data = new_order_book(4);
+    let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+    push_order(&mut data, true, 40, &alice, 10, false).unwrap();
+    push_order(&mut data, true, 40, &bob, 20, true).unwrap();
+
+    assert_eq!(get_level_amount(&data, true, 40), 30);
+    assert_eq!(get_level_amount(&data, false, 40), 0);
+    assert_eq!(level_orders(&data, true, 40), vec![(alice, 10), (bob, 20)]);
+    let front = front_order(&data, true, 40).unwrap();
+    assert_eq!(front.user_account, alice);
+    assert!(!front.payout_in_usd);
+    assert!(front_order(&data, true, 41).is_none());
+  }
+
+  #[test]
+  fn fills_match_orders_in_fifo_order() {
+    let mut data = new_order_book(4);
+    let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+    push_order(&mut data, false, 60, &alice, 10, false).unwrap();
+    push_order(&mut data, false, 60, &bob, 20, false).unwrap();
+
+    fill_front_order(&mut data, false, 60, 4).unwrap();
+    assert_eq!(level_orders(&data, false, 60), vec![(alice, 6), (bob, 20)]);
+    fill_front_order(&mut data, false, 60, 6).unwrap();
+    assert_eq!(level_orders(&data, false, 60), vec![(bob, 20)]);
+    assert_eq!(get_level_amount(&data, false, 60), 20);
+    fill_front_order(&mut data, false, 60, 20).unwrap();
+    assert!(front_order(&data, false, 60).is_none());
+    assert_eq!(get_level_amount(&data, false, 60), 0);
+
+    // the emptied level takes new orders again
+    push_order(&mut data, false, 60, &alice, 5, false).unwrap();
+    assert_eq!(level_orders(&data, false, 60), vec![(alice, 5)]);
+  }
+
+  #[test]
+  fn remove_user_orders_unlinks_only_the_users_orders() {
+    let mut data = new_order_book(8);
+    let (alice, bob, carol) = (
+      Pubkey::new_unique(),
+      Pubkey::new_unique(),
+      Pubkey::new_unique(),
+    );
+    push_order(&mut data, true, 55, &alice, 10, false).unwrap();
+    push_order(&mut data, true, 55, &bob, 20, false).unwrap();
+    push_order(&mut data, true, 55, &alice, 5, true).unwrap();
+
+    assert_eq!(remove_user_orders(&mut data, true, 55, &alice), Ok((10, 5)));
+    assert_eq!(level_orders(&data, true, 55), vec![(bob, 20)]);
+    assert_eq!(get_level_amount(&data, true, 55), 20);
+
+    // the tail was fixed up, so new orders still go behind bob
+    push_order(&mut data, true, 55, &carol, 7, false).unwrap();
+    assert_eq!(level_orders(&data, true, 55), vec![(bob, 20), (carol, 7)]);
+    assert_eq!(remove_user_orders(&mut data, true, 55, &alice), Ok((0, 0)));
+  }
+
+  #[test]
+  fn freed_nodes_are_reused_before_new_ones() {
+    let mut data = new_order_book(2);
+    let (alice, bob, carol) = (
+      Pubkey::new_unique(),
+      Pubkey::new_unique(),
+      Pubkey::new_unique(),
+    );
+    push_order(&mut data, true, 30, &alice, 10, false).unwrap();
+    push_order(&mut data, false, 70, &bob, 10, false).unwrap();
+    fill_front_order(&mut data, true, 30, 10).unwrap();
+    assert_eq!(get_u64(&data, ORDER_BOOK_FREE_LIST_HEAD_OFFSET), 1);
+
+    push_order(&mut data, true, 31, &carol, 3, false).unwrap();
+    assert_eq!(get_u64(&data, ORDER_BOOK_FREE_LIST_HEAD_OFFSET), NULL_NODE);
+    assert_eq!(get_u64(&data, ORDER_BOOK_ALLOCATED_OFFSET), 2);
+    assert_eq!(level_orders(&data, true, 31), vec![(carol, 3)]);
+    assert_eq!(level_orders(&data, false, 70), vec![(bob, 10)]);
+  }
+
+  #[test]
+  fn push_order_fails_once_every_node_is_in_use() {
+    let mut data = new_order_book(1);
+    let alice = Pubkey::new_unique();
+    push_order(&mut data, true, 50, &alice, 10, false).unwrap();
+    assert_eq!(
+      push_order(&mut data, true, 50, &alice, 10, false),
+      Err(BettingMarketError::OrderBookFull.into())
+    );
+    assert_eq!(get_level_amount(&data, true, 50), 10);
+
+    remove_user_orders(&mut data, true, 50, &alice).unwrap();
+    push_order(&mut data, true, 50, &alice, 10, false).unwrap();
+  }
+
+  #[test]
+  fn crossable_amount_sums_inverse_levels_at_or_better_than_the_limit() {
+    let mut data = new_order_book(4);
+    let alice = Pubkey::new_unique();
+    push_order(&mut data, false, 60, &alice, 10, false).unwrap();
+    push_order(&mut data, false, 50, &alice, 20, false).unwrap();
+    push_order(&mut data, false, 40, &alice, 40, false).unwrap();
+
+    assert_eq!(get_crossable_amount(&data, true, 40, u64::MAX), Ok(10));
+    assert_eq!(get_crossable_amount(&data, true, 50, u64::MAX), Ok(30));
+    assert_eq!(get_crossable_amount(&data, true, 50, 25), Ok(25));
+    assert_eq!(get_crossable_amount(&data, true, 39, u64::MAX), Ok(0));
+  }
+
+  #[test]
+  fn crossable_amount_stops_at_the_first_maker_past_the_limit() {
+    let mut data = new_order_book(MAX_MAKERS_PER_MATCH + 2);
+    let makers: Vec<Pubkey> = (0..=MAX_MAKERS_PER_MATCH)
+      .map(|_| Pubkey::new_unique())
+      .collect();
+    // every maker but the last rests at 60, the last maker rests at 50 ahead of another order of the first
+    for maker in &makers[..MAX_MAKERS_PER_MATCH] {
+      push_order(&mut data, false, 60, maker, 10, false).unwrap();
+    }
+    push_order(
+      &mut data,
+      false,
+      50,
+      &makers[MAX_MAKERS_PER_MATCH],
+      10,
+      false,
+    )
+    .unwrap();
+    push_order(&mut data, false, 50, &makers[0], 10, false).unwrap();
+
+    let capped_amount = 10 * MAX_MAKERS_PER_MATCH as u64;
+    assert_eq!(
+      get_crossable_amount(&data, true, 50, u64::MAX),
+      Ok(capped_amount)
+    );
+
+    // makers already matched don't count again
+    let mut matched_makers = makers[..MAX_MAKERS_PER_MATCH].to_vec();
+    assert_eq!(
+      get_matchable_level_amount(&data, false, 50, u64::MAX, &mut matched_makers),
+      (0, true)
+    );
+    let mut matched_makers = makers[2..].to_vec();
+    assert_eq!(
+      get_matchable_level_amount(&data, false, 50, u64::MAX, &mut matched_makers),
+      (20, false)
+    );
+  }
+}
@@ -1,14 +1,18 @@
 use crate::{
+  categorical,
+  claim::{self, ClaimableToken},
   error::BettingMarketError,
-  instruction::BettingMarketInstruction,
+  instruction::{
+    BettingMarketInstruction, Comparison, MarketOrderSize, MarketType, OracleKind, TimeInForce,
+  },
+  oracle, orderbook,
   state::{
-    self, get_payout_at_index, get_u64_at_ptr_offset, get_u8_at_ptr_offset, payout_exists_at_index,
-    set_payout_at_index, set_pubkey_10_at_ptr_offset, set_u64_10_at_ptr_offset,
-    set_u64_at_ptr_offset, set_u8_at_ptr_offset, BettingMarket, NO_BUY_AMOUNT_START_OFFSET,
-    NULL_PUBKEY, PAYOUT_AMOUNTS_FOR_PRICE_START_OFFSET, PAYOUT_IN_USD_FOR_PRICE_START_OFFSET,
-    PUBKEY_10_ISIZE, U64_10_ISIZE, U64_ISIZE, USER_ACCOUNTS_FOR_PRICE_START_OFFSET,
-    YES_BUY_AMOUNT_START_OFFSET,
+    BettingMarket, BETTING_MARKET_DATA_LEN, BINARY_MARKET_TYPE, COMPARISON_ABOVE,
+    COMPARISON_AT_OR_ABOVE, COMPARISON_AT_OR_BELOW, COMPARISON_BELOW, MAX_FEE_BPS,
+    MAX_MAKERS_PER_MATCH, MIN_DISPUTE_WINDOW_SECS, NULL_PUBKEY, PYTH_ORACLE_KIND,
+    SCALAR_MARKET_TYPE, SWITCHBOARD_ORACLE_KIND,
   },
+  twap, validation,
 };
 use solana_program::{
   account_info::{next_account_info, AccountInfo},
@@ -20,12 +24,32 @@ use solana_program::{
   program_pack::Pack,
   pubkey::Pubkey,
   system_instruction,
-  sysvar::{rent::Rent, Sysvar},
+  sysvar::{clock::Clock, rent::Rent, Sysvar},
 };
 use spl_token::{state::Account as TokenAccount, state::Mint as TokenMintAccount};
-use std::convert::TryInto;
+#[cfg(feature = "faucet")]
 use std::str::FromStr;
 
+// test usd mint the faucet is allowed to mint, set with FAUCET_USD_MINT when building with the faucet feature
+#[cfg(feature = "faucet")]
+const FAUCET_USD_MINT: Option<&str> = option_env!("FAUCET_USD_MINT");
+// max amount of test usd the faucet mints per call
+#[cfg(feature = "faucet")]
+const FAUCET_MAX_MINT_AMOUNT: u64 = 1_000_000;
+
+// fees taken while matching a trade
+// maker fees were deducted from usd payouts or the maker fee deposits of the resting orders, taker fees on
+// fills that paid out in usd were deducted from the payout, all of which sit in the betting market's usd
+// token account, taker fees due on fills that paid out in tokens and the maker fee deposit of the order the
+// trade leaves resting are still to be paid by the taker
+#[derive(Default)]
+struct MatchFees {
+  maker_fees: u64,
+  taker_fees: u64,
+  taker_fees_due: u64,
+  maker_fee_deposit: u64,
+}
+
 pub struct Processor;
 impl Processor {
   pub fn process(
@@ -36,53 +60,218 @@ impl Processor {
     let instruction = BettingMarketInstruction::unpack(instruction_data)?;
 
     match instruction {
-      BettingMarketInstruction::InitBettingMarket {} => {
+      BettingMarketInstruction::InitBettingMarket {
+        market_type,
+        maker_fee_bps,
+        taker_fee_bps,
+        creator_fee_share_bps,
+        referrer_fee_share_bps,
+        trading_close_ts,
+        resolution_ts,
+        strike_price,
+        comparison,
+        oracle,
+        strike_expo,
+        twap_window,
+        min_twap_samples,
+        oracle_kind,
+        dispute_window,
+        resolution_bond,
+      } => {
         msg!("Instruction: InitBettingMarket");
-        Self::process_init_betting_market(accounts, program_id)
+        msg!("market type: {:?}", market_type);
+        msg!("maker fee bps: {}", maker_fee_bps);
+        msg!("taker fee bps: {}", taker_fee_bps);
+        msg!("creator fee share bps: {}", creator_fee_share_bps);
+        msg!("referrer fee share bps: {}", referrer_fee_share_bps);
+        msg!("trading close ts: {}", trading_close_ts);
+        msg!("resolution ts: {}", resolution_ts);
+        msg!("strike price: {}", strike_price);
+        msg!("comparison: {:?}", comparison);
+        msg!("oracle: {}", oracle);
+        msg!("strike expo: {}", strike_expo);
+        msg!("twap window: {}", twap_window);
+        msg!("min twap samples: {}", min_twap_samples);
+        msg!("oracle kind: {:?}", oracle_kind);
+        msg!("dispute window: {}", dispute_window);
+        msg!("resolution bond: {}", resolution_bond);
+        Self::process_init_betting_market(
+          accounts,
+          market_type,
+          maker_fee_bps,
+          taker_fee_bps,
+          creator_fee_share_bps,
+          referrer_fee_share_bps,
+          trading_close_ts,
+          resolution_ts,
+          strike_price,
+          comparison,
+          oracle,
+          strike_expo,
+          twap_window,
+          min_twap_samples,
+          oracle_kind,
+          dispute_window,
+          resolution_bond,
+          program_id,
+        )
       }
       BettingMarketInstruction::OfferTrade {
         is_yes,
         price,
         amount,
+        time_in_force,
+        has_referrer,
       } => {
         msg!("Instruction: OfferTrade");
         msg!("is_yes: {}", is_yes);
         msg!("price: {}", price);
         msg!("amount: {}", amount);
-        Self::process_offer_trade(accounts, is_yes, price, amount, program_id)
+        msg!("time in force: {:?}", time_in_force);
+        msg!("has referrer: {}", has_referrer);
+        Self::process_offer_trade(
+          accounts,
+          is_yes,
+          price,
+          amount,
+          time_in_force,
+          has_referrer,
+          program_id,
+        )
       }
       BettingMarketInstruction::Payout {} => {
         msg!("Instruction: Payout");
         Self::process_payout(accounts, program_id)
       }
+      #[cfg(feature = "faucet")]
       BettingMarketInstruction::FreeMint { amount } => {
         msg!("Instruction: FreeMint");
         msg!("amount: {}", amount);
         Self::process_free_mint(accounts, amount, program_id)
       }
-      BettingMarketInstruction::JudgeBettingMarketManually { result } => {
+      BettingMarketInstruction::JudgeBettingMarketManually { result, yes_payout } => {
         msg!("Instruction: JudgeBettingMarketManually");
         msg!("result: {}", result);
-        Self::process_judge_betting_market_manually(accounts, result)
+        Self::process_judge_betting_market_manually(accounts, result, yes_payout, program_id)
       }
       BettingMarketInstruction::JudgeBettingMarketOracle {} => {
         msg!("Instruction: JudgeBettingMarketOracle");
-        Self::process_judge_betting_market_oracle(accounts)
+        Self::process_judge_betting_market_oracle(accounts, program_id)
       }
       BettingMarketInstruction::SetStrikePrice { strike_price } => {
         msg!("Instruction: SetStrikePrice");
         msg!("strike price: {}", strike_price);
-        Self::process_set_strike_price(accounts, strike_price)
+        Self::process_set_strike_price(accounts, strike_price, program_id)
+      }
+      BettingMarketInstruction::CancelOrder { is_yes, price } => {
+        msg!("Instruction: CancelOrder");
+        msg!("is_yes: {}", is_yes);
+        msg!("price: {}", price);
+        Self::process_cancel_order(accounts, is_yes, price, program_id)
+      }
+      BettingMarketInstruction::MarketOrder {
+        is_yes,
+        worst_price,
+        size,
+      } => {
+        msg!("Instruction: MarketOrder");
+        msg!("is_yes: {}", is_yes);
+        msg!("worst price: {}", worst_price);
+        msg!("size: {:?}", size);
+        Self::process_market_order(accounts, is_yes, worst_price, size, program_id)
+      }
+      BettingMarketInstruction::SetJudge {} => {
+        msg!("Instruction: SetJudge");
+        Self::process_set_judge(accounts, program_id)
+      }
+      BettingMarketInstruction::CreateClaimAccount {} => {
+        msg!("Instruction: CreateClaimAccount");
+        Self::process_create_claim_account(accounts, program_id)
+      }
+      BettingMarketInstruction::ReclaimOpenOrders {} => {
+        msg!("Instruction: ReclaimOpenOrders");
+        Self::process_reclaim_open_orders(accounts, program_id)
+      }
+      BettingMarketInstruction::InitCategoricalMarket {} => {
+        msg!("Instruction: InitCategoricalMarket");
+        Self::process_init_categorical_market(accounts, program_id)
+      }
+      BettingMarketInstruction::SplitCategoricalSet { amount } => {
+        msg!("Instruction: SplitCategoricalSet");
+        msg!("amount: {}", amount);
+        Self::process_split_categorical_set(accounts, amount, program_id)
+      }
+      BettingMarketInstruction::MergeCategoricalSet { amount } => {
+        msg!("Instruction: MergeCategoricalSet");
+        msg!("amount: {}", amount);
+        Self::process_merge_categorical_set(accounts, amount, program_id)
+      }
+      BettingMarketInstruction::JudgeCategoricalMarket { winning_outcome } => {
+        msg!("Instruction: JudgeCategoricalMarket");
+        msg!("winning outcome: {}", winning_outcome);
+        Self::process_judge_categorical_market(accounts, winning_outcome, program_id)
+      }
+      BettingMarketInstruction::SplitCompleteSet { amount } => {
+        msg!("Instruction: SplitCompleteSet");
+        msg!("amount: {}", amount);
+        Self::process_split_complete_set(accounts, amount, program_id)
+      }
+      BettingMarketInstruction::MergeCompleteSet { amount } => {
+        msg!("Instruction: MergeCompleteSet");
+        msg!("amount: {}", amount);
+        Self::process_merge_complete_set(accounts, amount, program_id)
+      }
+      BettingMarketInstruction::WithdrawFees {} => {
+        msg!("Instruction: WithdrawFees");
+        Self::process_withdraw_fees(accounts, program_id)
+      }
+      BettingMarketInstruction::RecordOracleSample {} => {
+        msg!("Instruction: RecordOracleSample");
+        Self::process_record_oracle_sample(accounts, program_id)
+      }
+      BettingMarketInstruction::ProposeResult { result, yes_payout } => {
+        msg!("Instruction: ProposeResult");
+        msg!("result: {}", result);
+        Self::process_propose_result(accounts, result, yes_payout, program_id)
+      }
+      BettingMarketInstruction::DisputeResult {} => {
+        msg!("Instruction: DisputeResult");
+        Self::process_dispute_result(accounts, program_id)
+      }
+      BettingMarketInstruction::FinalizeResult {} => {
+        msg!("Instruction: FinalizeResult");
+        Self::process_finalize_result(accounts, program_id)
       }
     }
   }
 
   // initialize betting market data account, yes/no token mints, usd token account
-  fn process_init_betting_market(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+  #[allow(clippy::too_many_arguments)]
+  fn process_init_betting_market(
+    accounts: &[AccountInfo],
+    market_type: MarketType,
+    maker_fee_bps: u16,
+    taker_fee_bps: u16,
+    creator_fee_share_bps: u16,
+    referrer_fee_share_bps: u16,
+    trading_close_ts: i64,
+    resolution_ts: i64,
+    strike_price: u64,
+    comparison: Comparison,
+    oracle: Pubkey,
+    strike_expo: i32,
+    twap_window: i64,
+    min_twap_samples: u8,
+    oracle_kind: OracleKind,
+    dispute_window: i64,
+    resolution_bond: u64,
+    program_id: &Pubkey,
+  ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
     let initializer_account_info = next_account_info(account_info_iter)?;
     let pda_account_info = next_account_info(account_info_iter)?;
     let betting_market_data_account_info = next_account_info(account_info_iter)?;
+    let order_book_account_info = next_account_info(account_info_iter)?;
     let token_program_id_account_info = next_account_info(account_info_iter)?;
     let usd_token_mint_account_info = next_account_info(account_info_iter)?;
     let yes_token_mint_account_info = next_account_info(account_info_iter)?;
@@ -91,20 +280,93 @@ impl Processor {
     let judge_account_info = next_account_info(account_info_iter)?;
     let system_program_account_info = next_account_info(account_info_iter)?;
     let rent_account_info = next_account_info(account_info_iter)?;
+    let fee_vault_account_info = next_account_info(account_info_iter)?;
+    let fee_authority_account_info = next_account_info(account_info_iter)?;
+    let fee_recipient_account_info = next_account_info(account_info_iter)?;
+    let creator_account_info = account_info_iter.next();
+    let creator = match creator_account_info {
+      Some(creator_account_info) => *creator_account_info.key,
+      None if creator_fee_share_bps == 0 => NULL_PUBKEY,
+      None => return Err(ProgramError::NotEnoughAccountKeys),
+    };
+    // fee shares are credited to the creator's claimable balance account, created along with the market
+    let creator_claim_account_info = creator_account_info
+      .map(|_| next_account_info(account_info_iter))
+      .transpose()?;
     let rent = &Rent::from_account_info(rent_account_info)?;
-    let (pda, _bump_seed) = Pubkey::find_program_address(&[b"betting"], program_id);
+    let (pda, bump_seed) = Pubkey::find_program_address(
+      &[b"betting", betting_market_data_account_info.key.as_ref()],
+      program_id,
+    );
 
-    // verify pda is indeed pda, token program id is correct, token mint accounts are owned by token program
-    // initializer is signer, anything already initialized?
+    // verify pda is indeed pda, token program id is correct, initializer is signer,
+    // betting market data account and order book account are owned by program
     if *pda_account_info.key != pda {
       return Err(BettingMarketError::InvalidPda.into());
     }
-    if *token_program_id_account_info.key != spl_token::id() {
-      return Err(ProgramError::IncorrectProgramId);
+    validation::validate_token_program(token_program_id_account_info)?;
+    validation::validate_signer(initializer_account_info)?;
+    if *betting_market_data_account_info.owner != *program_id {
+      return Err(BettingMarketError::IncorrectMarketOwner.into());
+    }
+    if *order_book_account_info.owner != *program_id {
+      return Err(BettingMarketError::InvalidOrderBook.into());
+    }
+    let (market_type, lower_bound, upper_bound) = match market_type {
+      MarketType::Binary => (BINARY_MARKET_TYPE, 0, 0),
+      MarketType::Scalar {
+        lower_bound,
+        upper_bound,
+      } => {
+        if lower_bound >= upper_bound {
+          return Err(BettingMarketError::InvalidScalarBounds.into());
+        }
+        (SCALAR_MARKET_TYPE, lower_bound, upper_bound)
+      }
+    };
+    if maker_fee_bps > MAX_FEE_BPS || taker_fee_bps > MAX_FEE_BPS {
+      return Err(BettingMarketError::InvalidFee.into());
+    }
+    if creator_fee_share_bps as u32 + referrer_fee_share_bps as u32 > MAX_FEE_BPS as u32 {
+      return Err(BettingMarketError::InvalidFeeShare.into());
+    }
+    if trading_close_ts < 0
+      || resolution_ts < 0
+      || (trading_close_ts != 0 && trading_close_ts <= Clock::get()?.unix_timestamp)
+      || (trading_close_ts != 0 && resolution_ts != 0 && resolution_ts < trading_close_ts)
+    {
+      return Err(BettingMarketError::InvalidMarketTimes.into());
+    }
+    // a twap window ends at the resolution time, so it needs one
+    if twap_window < 0 || (twap_window > 0 && resolution_ts == 0) {
+      return Err(BettingMarketError::InvalidMarketTimes.into());
+    }
+    if dispute_window < MIN_DISPUTE_WINDOW_SECS {
+      return Err(BettingMarketError::InvalidMarketTimes.into());
     }
-    if !initializer_account_info.is_signer {
-      return Err(ProgramError::MissingRequiredSignature);
+    // markets without an oracle are resolved by bonded proposals after trading closed
+    if oracle == NULL_PUBKEY {
+      if trading_close_ts == 0 || resolution_ts == 0 {
+        return Err(BettingMarketError::InvalidMarketTimes.into());
+      }
+      if resolution_bond == 0 {
+        return Err(BettingMarketError::InvalidResolutionBond.into());
+      }
     }
+    if *fee_recipient_account_info.owner != spl_token::id()
+      || TokenAccount::unpack(&fee_recipient_account_info.data.borrow())?.mint
+        != *usd_token_mint_account_info.key
+    {
+      return Err(BettingMarketError::IncorrectFeeRecipient.into());
+    }
+
+    // reject already initialized accounts and zero everything else so a new betting market
+    // never starts on stale order book or payout bytes
+    Self::zero_uninitialized_account_data(
+      betting_market_data_account_info,
+      BETTING_MARKET_DATA_LEN,
+    )?;
+    Self::zero_uninitialized_account_data(order_book_account_info, orderbook::ORDER_BOOK_MIN_LEN)?;
 
     // create account + initialize with pda as mint authority / owner instead of initializer so no need to set authority
     // accounts to be initialized are thus required to be writable
@@ -168,68 +430,113 @@ impl Processor {
       ],
     )?;
 
-    // create usd token account and initialize token account with pda as owner
-    msg!("Creating usd token account and initializing token account with pda as owner");
-    invoke(
-      &system_instruction::create_account(
-        initializer_account_info.key,
-        usd_token_account_info.key,
-        rent.minimum_balance(TokenAccount::LEN),
-        TokenAccount::LEN as u64,
-        token_program_id_account_info.key,
-      ),
-      &[
-        initializer_account_info.clone(),
-        usd_token_account_info.clone(),
-        system_program_account_info.clone(),
-      ],
-    )?;
-    invoke(
-      &spl_token::instruction::initialize_account(
-        token_program_id_account_info.key,
-        usd_token_account_info.key,
-        usd_token_mint_account_info.key,
-        pda_account_info.key,
-      )?,
-      &[
-        usd_token_account_info.clone(),
-        usd_token_mint_account_info.clone(),
-        pda_account_info.clone(),
-        rent_account_info.clone(),
-        token_program_id_account_info.clone(),
-      ],
-    )?;
+    // create usd token accounts for collateral and fees and initialize token accounts with pda as owner
+    msg!("Creating usd token accounts and initializing token accounts with pda as owner");
+    for token_account_info in [usd_token_account_info, fee_vault_account_info] {
+      invoke(
+        &system_instruction::create_account(
+          initializer_account_info.key,
+          token_account_info.key,
+          rent.minimum_balance(TokenAccount::LEN),
+          TokenAccount::LEN as u64,
+          token_program_id_account_info.key,
+        ),
+        &[
+          initializer_account_info.clone(),
+          token_account_info.clone(),
+          system_program_account_info.clone(),
+        ],
+      )?;
+      invoke(
+        &spl_token::instruction::initialize_account(
+          token_program_id_account_info.key,
+          token_account_info.key,
+          usd_token_mint_account_info.key,
+          pda_account_info.key,
+        )?,
+        &[
+          token_account_info.clone(),
+          usd_token_mint_account_info.clone(),
+          pda_account_info.clone(),
+          rent_account_info.clone(),
+          token_program_id_account_info.clone(),
+        ],
+      )?;
+    }
 
-    // verify betting market data account owned by program and initialize its data
-    msg!("Verifying betting market data account owned by program and initializing its data");
-    if *betting_market_data_account_info.owner != *program_id {
-      msg!("wtf!");
-      msg!(
-        "{} {}",
-        *betting_market_data_account_info.owner,
-        *program_id
-      );
-      // return Err(ProgramError::IllegalOwner);
-    }
-    let mut_ptr = betting_market_data_account_info
-      .data
-      .borrow_mut()
-      .as_mut_ptr();
-    unsafe {
-      let slice = std::slice::from_raw_parts_mut(mut_ptr, BettingMarket::LEN);
-      let mut betting_market_data = BettingMarket::unpack_unchecked(slice)?;
-      // if betting_market_data.is_initialized() {
-      //   return Err(ProgramError::AccountAlreadyInitialized);
-      // }
-      msg!("{}", betting_market_data.is_initialized);
-      betting_market_data.is_initialized = true;
-      betting_market_data.result = 0;
-      betting_market_data.yes_token_mint = *yes_token_mint_account_info.key;
-      betting_market_data.no_token_mint = *no_token_mint_account_info.key;
-      betting_market_data.usd_token_account = *usd_token_account_info.key;
-      betting_market_data.strike_price = 0;
-      betting_market_data.judge = *judge_account_info.key;
-      BettingMarket::pack(betting_market_data, slice)?;
+    // initialize betting market data account
+    msg!("Initializing betting market data account");
+    let betting_market_data = BettingMarket {
+      is_initialized: true,
+      result: 0,
+      yes_token_mint: *yes_token_mint_account_info.key,
+      no_token_mint: *no_token_mint_account_info.key,
+      usd_token_account: *usd_token_account_info.key,
+      strike_price,
+      judge: *judge_account_info.key,
+      order_book: *order_book_account_info.key,
+      bump_seed,
+      yes_payout: 0,
+      market_type,
+      lower_bound,
+      upper_bound,
+      categorical_market: NULL_PUBKEY,
+      maker_fee_bps,
+      taker_fee_bps,
+      fee_vault: *fee_vault_account_info.key,
+      fee_authority: *fee_authority_account_info.key,
+      fee_recipient: *fee_recipient_account_info.key,
+      creator,
+      creator_fee_share_bps,
+      referrer_fee_share_bps,
+      trading_close_ts,
+      resolution_ts,
+      has_traded: false,
+      oracle,
+      comparison: match comparison {
+        Comparison::Above => COMPARISON_ABOVE,
+        Comparison::AtOrAbove => COMPARISON_AT_OR_ABOVE,
+        Comparison::Below => COMPARISON_BELOW,
+        Comparison::AtOrBelow => COMPARISON_AT_OR_BELOW,
+      },
+      strike_expo,
+      twap_window,
+      min_twap_samples,
+      oracle_kind: match oracle_kind {
+        OracleKind::Pyth => PYTH_ORACLE_KIND,
+        OracleKind::Switchboard => SWITCHBOARD_ORACLE_KIND,
+        #[cfg(any(test, feature = "mock-oracle"))]
+        OracleKind::Mock => crate::state::MOCK_ORACLE_KIND,
+      },
+      dispute_window,
+      resolution_bond,
+      proposer: NULL_PUBKEY,
+      disputer: NULL_PUBKEY,
+      proposed_result: 0,
+      proposed_yes_payout: 0,
+      proposal_ts: 0,
+    };
+    Self::pack_betting_market(betting_market_data, betting_market_data_account_info)?;
+
+    // initialize order book account for this betting market
+    msg!("Initializing order book account");
+    let mut order_book_data = order_book_account_info.try_borrow_mut_data()?;
+    orderbook::init_order_book(&mut order_book_data, betting_market_data_account_info.key)?;
+    msg!(
+      "order book capacity: {}",
+      orderbook::capacity_for_len(order_book_data.len())
+    );
+
+    if let Some(creator_claim_account_info) = creator_claim_account_info {
+      Self::create_claim_account(
+        initializer_account_info,
+        creator_claim_account_info,
+        betting_market_data_account_info.key,
+        &creator,
+        system_program_account_info,
+        rent,
+        program_id,
+      )?;
     }
 
     // hardcoded YES and NO token mints
@@ -249,21 +556,67 @@ impl Processor {
     Ok(())
   }
 
+  // verify an account about to be initialized is big enough and not initialized yet,
+  // then zero its whole data region and verify it reads back as zero
+  fn zero_uninitialized_account_data(account: &AccountInfo, min_len: usize) -> ProgramResult {
+    let mut data = account.data.borrow_mut();
+    if data.len() < min_len {
+      return Err(BettingMarketError::AccountTooSmall.into());
+    }
+    if data.first() == Some(&1) {
+      return Err(ProgramError::AccountAlreadyInitialized);
+    }
+    data.fill(0);
+    if data.iter().any(|byte| *byte != 0) {
+      return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(())
+  }
+
+  // verify a limit or worst price leaves both sides a price of at least 1
+  fn validate_price(price: u64) -> ProgramResult {
+    if !(1..=99).contains(&price) {
+      return Err(BettingMarketError::InvalidPrice.into());
+    }
+    Ok(())
+  }
+
+  // verify a result is 1 yes, 2 no, 3 void or 4 scalar and return the yes payout it sets,
+  // only void and scalar results take the given yes payout
+  fn validate_result(
+    betting_market_data: &BettingMarket,
+    result: u64,
+    yes_payout: u64,
+  ) -> Result<u8, ProgramError> {
+    let yes_payout = match result {
+      1 => 100,
+      2 => 0,
+      3 if yes_payout <= 100 => yes_payout,
+      4 if yes_payout <= 100 && betting_market_data.market_type == SCALAR_MARKET_TYPE => yes_payout,
+      _ => return Err(BettingMarketError::InvalidInstruction.into()),
+    };
+    Ok(yes_payout as u8)
+  }
+
   // process an offer trade instruction by filling as much of the amount as possible under the limit price
   // and creating the remaining amount as a resting limit order at the specified limit price
   // every trade is a buy (selling yes for 30 == buying no for 70)
   // with the direction specified by is_yes
+  // time in force decides whether the unmatched amount can rest or whether the trade should fail instead
   fn process_offer_trade(
     accounts: &[AccountInfo],
     is_yes: bool,
     price: u64,
     amount: u64,
+    time_in_force: TimeInForce,
+    has_referrer: bool,
     program_id: &Pubkey,
   ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
     let user_account = next_account_info(account_info_iter)?;
     let pda_account = next_account_info(account_info_iter)?;
     let betting_market_data_account = next_account_info(account_info_iter)?;
+    let order_book_account = next_account_info(account_info_iter)?;
     let usd_token_mint = next_account_info(account_info_iter)?;
     let yes_token_mint = next_account_info(account_info_iter)?;
     let no_token_mint = next_account_info(account_info_iter)?;
@@ -272,14 +625,82 @@ impl Processor {
     let user_no_token_account = next_account_info(account_info_iter)?;
     let pda_usd_token_account = next_account_info(account_info_iter)?;
     let token_program_id = next_account_info(account_info_iter)?;
+    let fee_vault = next_account_info(account_info_iter)?;
+    // the user's claimable balance account followed by the ones of the makers the trade can match,
+    // the creator and the referrer, with the referrer's usd token account last
+    let (referrer_usd_token_account, claim_accounts) = if has_referrer {
+      let (referrer_usd_token_account, claim_accounts) = account_info_iter
+        .as_slice()
+        .split_last()
+        .ok_or(ProgramError::NotEnoughAccountKeys)?;
+      (Some(referrer_usd_token_account), claim_accounts)
+    } else {
+      (None, account_info_iter.as_slice())
+    };
 
-    // TODO: checks
     // valid token accounts, pda account is indeed pda, betting market data account owned by program
     // token mints match up correctly, usd pda usd token account owned by pda
-    let (pda, _bump_seed) = Pubkey::find_program_address(&[b"betting"], program_id);
-    if *pda_account.key != pda {
-      return Err(BettingMarketError::InvalidPda.into());
+    let (mut betting_market_data, bump_seed) = validation::validate_trade_accounts(
+      program_id,
+      user_account,
+      pda_account,
+      betting_market_data_account,
+      usd_token_mint,
+      yes_token_mint,
+      no_token_mint,
+      user_usd_token_account,
+      user_yes_token_account,
+      user_no_token_account,
+      pda_usd_token_account,
+      token_program_id,
+    )?;
+    Self::validate_price(price)?;
+    if betting_market_data.result != 0 {
+      return Err(BettingMarketError::MarketAlreadyResolved.into());
     }
+    if betting_market_data.is_trading_closed(Clock::get()?.unix_timestamp) {
+      return Err(BettingMarketError::TradingClosed.into());
+    }
+    let mut order_book_data = validation::validate_order_book_account(
+      betting_market_data_account,
+      &betting_market_data,
+      order_book_account,
+      program_id,
+    )?;
+    validation::validate_fee_vault(&betting_market_data, fee_vault)?;
+    // fills and resting orders are credited to the user's claimable balance account, so it has to exist
+    claim::find_claim_account(
+      claim_accounts,
+      program_id,
+      betting_market_data_account.key,
+      user_account.key,
+    )?;
+    let referrer = referrer_usd_token_account
+      .map(|referrer_usd_token_account| {
+        validation::validate_referrer(referrer_usd_token_account, user_account, usd_token_mint)
+      })
+      .transpose()?;
+
+    // only collect collateral for the amount that is allowed to match or rest
+    let crossable_amount =
+      orderbook::get_crossable_amount(&order_book_data, is_yes, price, amount)?;
+    msg!("crossable amount: {}", crossable_amount);
+    let amount = match time_in_force {
+      TimeInForce::GoodTillCancelled => amount,
+      TimeInForce::ImmediateOrCancel => std::cmp::min(amount, crossable_amount),
+      TimeInForce::FillOrKill => {
+        if crossable_amount < amount {
+          return Err(BettingMarketError::OrderWouldNotFill.into());
+        }
+        amount
+      }
+      TimeInForce::PostOnly => {
+        if crossable_amount > 0 {
+          return Err(BettingMarketError::OrderWouldCross.into());
+        }
+        amount
+      }
+    };
 
     // unpack token account data
     let user_yes_token_account_data = TokenAccount::unpack(&user_yes_token_account.data.borrow())?;
@@ -356,7 +777,9 @@ impl Processor {
 
     // transfer remaining amount in USD to betting market's USD token account
     let usd_collateralized_amount = amount - inverse_collateralized_amount;
-    let usd_amount = price * usd_collateralized_amount; // can overflow
+    let usd_amount = price
+      .checked_mul(usd_collateralized_amount)
+      .ok_or(BettingMarketError::AmountOverflow)?;
     msg!(
       "usd collateralized amount: {}, price: {}, usd amount: {}",
       usd_collateralized_amount,
@@ -383,647 +806,3983 @@ impl Processor {
 
     // match trades and adjust order book
     // place 2 separate orders -- 1 for the inverse collateralized amount and 1 for the usd collateralized amount
-    let data_ptr = betting_market_data_account.data.borrow_mut().as_mut_ptr();
+    let mut match_fees = MatchFees::default();
+    let mut matched_makers = Vec::new();
     msg!(
       "first trade collateralized by inverse {} token burn and payout in usd",
       if is_yes { "no" } else { "yes" }
     );
-    Self::match_and_place_limit_order(
+    let mut refunded_amount = Self::match_and_place_limit_order(
       is_yes,
       inverse_collateralized_amount,
       price,
       true,
       user_account,
-      data_ptr,
-      *yes_token_mint.key,
-      *no_token_mint.key,
-      *usd_token_mint.key,
-    );
+      &mut order_book_data,
+      claim_accounts,
+      betting_market_data_account.key,
+      &betting_market_data,
+      &mut match_fees,
+      &mut matched_makers,
+      program_id,
+    )?;
     msg!(
       "second trade collateralized by usd transfer and payout in {} token",
       if is_yes { "yes" } else { "no" }
     );
-    Self::match_and_place_limit_order(
+    refunded_amount += Self::match_and_place_limit_order(
       is_yes,
       usd_collateralized_amount,
       price,
       false,
       user_account,
-      data_ptr,
-      *yes_token_mint.key,
-      *no_token_mint.key,
-      *usd_token_mint.key,
-    );
+      &mut order_book_data,
+      claim_accounts,
+      betting_market_data_account.key,
+      &betting_market_data,
+      &mut match_fees,
+      &mut matched_makers,
+      program_id,
+    )?;
+    if refunded_amount > 0 && matches!(time_in_force, TimeInForce::FillOrKill) {
+      return Err(BettingMarketError::OrderWouldNotFill.into());
+    }
+
+    Self::collect_match_fees(
+      &match_fees,
+      &betting_market_data,
+      referrer.as_ref(),
+      claim_accounts,
+      program_id,
+      user_account,
+      user_usd_token_account,
+      pda_account,
+      pda_usd_token_account,
+      fee_vault,
+      token_program_id,
+      betting_market_data_account.key,
+      bump_seed,
+    )?;
+
+    // the strike price is locked in from the first trade on
+    if !betting_market_data.has_traded {
+      betting_market_data.has_traded = true;
+      Self::pack_betting_market(betting_market_data, betting_market_data_account)?;
+    }
 
     Ok(())
   }
 
-  // match as much of the limit order as possible by crossing over and then
-  // place the remaining unmatched amount as a resting limit order on the order book
-  // Access violation in unknown section at address 0xfffffffffffff017 of size 8 by instruction #7094
-  // wtf the program crashes if I don't put this
-  #[inline(always)]
-  fn match_and_place_limit_order(
+  // process a market order by filling against the inverse order book from the best price up to the worst price
+  // every fill is placed as a limit order at exactly its fill price so nothing rests and there is no
+  // price differential to pay back, which means only the usd that actually executes is collected
+  fn process_market_order(
+    accounts: &[AccountInfo],
     is_yes: bool,
-    order_size: u64,
-    limit_price: u64,
-    order_payout_in_usd: bool,
-    user_account: &AccountInfo,
-    data_ptr: *mut u8,
-    yes_token_mint: Pubkey,
-    no_token_mint: Pubkey,
-    usd_token_mint: Pubkey,
-  ) {
-    let mut matched_amount = 0;
-    let mut unmatched_amount = order_size;
-    let (forward_token_mint, inverse_token_mint) = if is_yes {
-      (yes_token_mint, no_token_mint)
-    } else {
-      (no_token_mint, yes_token_mint)
-    };
-
-    // iterate thru inverse buy orders from 100 to (100-limit price) inclusive
-    // while unmatched_amount > 0, perform trade
-    // trade will fully exhaust either one or both sides
-    // the inverse side will have exactly 1 settled result per order filled (same price can have multiple orders)
-    // the forward side will have either 1 or 2 settled results per order filled
-    // if it's a usd payout order, it'll be 1 payout at the forward buy price
-    // if it's a forward buy mint order, it'll be 1 payout in forward buy tokens and 1 payout for the price differential
-    // update the order book and append the settled results to payout data
-    for inverse_buy_price in ((100 - limit_price)..101).rev() {
-      let inverse_buy_price_isize = inverse_buy_price as isize;
-      let inverse_buy_price_amount_offset = if is_yes {
-        NO_BUY_AMOUNT_START_OFFSET
-      } else {
-        YES_BUY_AMOUNT_START_OFFSET
-      } + U64_ISIZE * inverse_buy_price_isize;
-      let amount_at_inverse_buy_price =
-        state::get_u64_at_ptr_offset(data_ptr, inverse_buy_price_amount_offset);
-      let matched_at_price = std::cmp::min(unmatched_amount, amount_at_inverse_buy_price);
-      matched_amount += matched_at_price;
-      unmatched_amount -= matched_at_price;
-
-      if matched_at_price > 0 {
-        // update buy amounts for price
-        state::set_u64_at_ptr_offset(
-          data_ptr,
-          inverse_buy_price_amount_offset,
-          amount_at_inverse_buy_price - matched_at_price,
-        );
-        msg!(
-          "matched {} inverse buys at price {}, updating amount at {} buy price from {} to {}",
-          matched_at_price,
-          inverse_buy_price,
-          if is_yes { "no" } else { "yes" },
-          amount_at_inverse_buy_price,
-          amount_at_inverse_buy_price - matched_at_price,
-        );
-
-        // match orders for the inverse token on the orderbook at inverse buy price and create corresponding payouts
-        Self::match_orders_at_price_fifo(
-          data_ptr,
-          matched_at_price,
-          inverse_buy_price,
-          inverse_token_mint,
-          usd_token_mint,
-        );
+    worst_price: u64,
+    size: MarketOrderSize,
+    program_id: &Pubkey,
+  ) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let user_account = next_account_info(account_info_iter)?;
+    let pda_account = next_account_info(account_info_iter)?;
+    let betting_market_data_account = next_account_info(account_info_iter)?;
+    let order_book_account = next_account_info(account_info_iter)?;
+    let usd_token_mint = next_account_info(account_info_iter)?;
+    let yes_token_mint = next_account_info(account_info_iter)?;
+    let no_token_mint = next_account_info(account_info_iter)?;
+    let user_usd_token_account = next_account_info(account_info_iter)?;
+    let user_yes_token_account = next_account_info(account_info_iter)?;
+    let user_no_token_account = next_account_info(account_info_iter)?;
+    let pda_usd_token_account = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let fee_vault = next_account_info(account_info_iter)?;
+    // the user's claimable balance account followed by the ones of the makers the trade can match
+    let claim_accounts = account_info_iter.as_slice();
 
-        // create payout for user at forward buy price
-        let forward_buy_price = 100 - inverse_buy_price; // cross over at better than limit price
-        let forward_buy_price_differential = limit_price - forward_buy_price; // geq 0
-        if order_payout_in_usd {
-          Self::add_payout(
-            data_ptr,
-            *user_account.key,
-            usd_token_mint,
-            matched_at_price * forward_buy_price,
-          );
-        } else {
-          Self::add_payout(
-            data_ptr,
-            *user_account.key,
-            forward_token_mint,
-            matched_at_price,
-          );
-          // usd price differential paid back
-          Self::add_payout(
-            data_ptr,
-            *user_account.key,
-            usd_token_mint,
-            matched_at_price * forward_buy_price_differential,
-          )
-        }
-      }
+    let (mut betting_market_data, bump_seed) = validation::validate_trade_accounts(
+      program_id,
+      user_account,
+      pda_account,
+      betting_market_data_account,
+      usd_token_mint,
+      yes_token_mint,
+      no_token_mint,
+      user_usd_token_account,
+      user_yes_token_account,
+      user_no_token_account,
+      pda_usd_token_account,
+      token_program_id,
+    )?;
+    Self::validate_price(worst_price)?;
+    if betting_market_data.result != 0 {
+      return Err(BettingMarketError::MarketAlreadyResolved.into());
     }
-
-    // add remaining unmatched amount as order to order book
-    if unmatched_amount > 0 {
-      Self::add_order_to_orderbook(
-        user_account,
-        data_ptr,
-        is_yes,
-        limit_price,
-        unmatched_amount,
-        order_payout_in_usd,
-      );
+    if betting_market_data.is_trading_closed(Clock::get()?.unix_timestamp) {
+      return Err(BettingMarketError::TradingClosed.into());
     }
+    let mut order_book_data = validation::validate_order_book_account(
+      betting_market_data_account,
+      &betting_market_data,
+      order_book_account,
+      program_id,
+    )?;
+    validation::validate_fee_vault(&betting_market_data, fee_vault)?;
+    claim::find_claim_account(
+      claim_accounts,
+      program_id,
+      betting_market_data_account.key,
+      user_account.key,
+    )?;
 
-    msg!(
-      "matched amount: {}, unmatched amount: {}",
-      matched_amount,
-      unmatched_amount
-    );
-  }
-
-  // match orders at a fixed price from the orderbook in fifo fashion for the order size and create corresponding payouts
-  fn match_orders_at_price_fifo(
-    data_ptr: *mut u8,
-    order_size: u64,
-    order_price: u64,
-    order_token_mint: Pubkey,
-    usd_token_mint: Pubkey,
-  ) {
-    let order_price_isize = order_price as isize;
-    let user_accounts_for_price_offset =
-      USER_ACCOUNTS_FOR_PRICE_START_OFFSET + PUBKEY_10_ISIZE * order_price_isize;
-    let payout_in_usd_for_price_offset =
-      PAYOUT_IN_USD_FOR_PRICE_START_OFFSET + U64_10_ISIZE * order_price_isize;
-    let payout_amounts_for_price_offset =
-      PAYOUT_AMOUNTS_FOR_PRICE_START_OFFSET + U64_10_ISIZE * order_price_isize;
-    let mut user_accounts_for_price =
-      state::get_pubkey_10_at_ptr_offset(data_ptr, user_accounts_for_price_offset);
-    let mut payout_in_usd_for_price =
-      state::get_u64_10_at_ptr_offset(data_ptr, payout_in_usd_for_price_offset);
-    let mut payout_amounts_for_price =
-      state::get_u64_10_at_ptr_offset(data_ptr, payout_amounts_for_price_offset);
-    let mut unmatched_order_buys = order_size;
-    while unmatched_order_buys > 0 {
-      let matched_for_order = std::cmp::min(payout_amounts_for_price[0], unmatched_order_buys);
-      Self::add_payout(
-        data_ptr,
-        user_accounts_for_price[0],
-        if payout_in_usd_for_price[0] == 2 {
-          usd_token_mint
-        } else {
-          order_token_mint
-        },
-        if payout_in_usd_for_price[0] == 2 {
-          matched_for_order * (100 - order_price) // original order was selling inverse, so usd amount is inverse
-        } else {
-          matched_for_order
-        },
+    // walk the inverse book from the best forward buy price to the worst and size each fill
+    let mut fills: Vec<(u64, u64)> = Vec::new();
+    let (mut remaining_quantity, mut remaining_budget) = match size {
+      MarketOrderSize::Quantity(quantity) => (quantity, u64::MAX),
+      MarketOrderSize::Budget(budget) => (u64::MAX, budget),
+    };
+    // the walk counts the makers the fills would match so it stops where matching would hit the maker limit
+    let mut makers = Vec::new();
+    for inverse_buy_price in ((100 - worst_price)..101).rev() {
+      let forward_buy_price = 100 - inverse_buy_price;
+      let amount_at_inverse_buy_price =
+        orderbook::get_level_amount(&order_book_data, !is_yes, inverse_buy_price);
+      let affordable_amount =
+        betting_market_data.taker_affordable_amount(remaining_budget, forward_buy_price);
+      let (fill_amount, is_maker_limit_reached) = orderbook::get_matchable_level_amount(
+        &order_book_data,
+        !is_yes,
+        inverse_buy_price,
+        std::cmp::min(remaining_quantity, affordable_amount),
+        &mut makers,
       );
-      unmatched_order_buys -= matched_for_order;
-      payout_amounts_for_price[0] -= matched_for_order;
-      // pop from front if order fully matched
-      if payout_amounts_for_price[0] == 0 {
-        for i in 0..9 {
-          user_accounts_for_price[i] = user_accounts_for_price[i + 1];
-          payout_in_usd_for_price[i] = payout_in_usd_for_price[i + 1];
-          payout_amounts_for_price[i] = payout_amounts_for_price[i + 1];
-        }
-        user_accounts_for_price[9] = NULL_PUBKEY;
-        payout_in_usd_for_price[9] = 0;
-        payout_amounts_for_price[9] = 0;
+      if fill_amount > 0 {
+        fills.push((forward_buy_price, fill_amount));
+        remaining_quantity -= fill_amount;
+        // the taker fee of the fill is paid out of the budget too
+        let usd_amount = fill_amount
+          .checked_mul(forward_buy_price)
+          .ok_or(BettingMarketError::AmountOverflow)?;
+        let taker_fee = BettingMarket::fee_for(usd_amount, betting_market_data.taker_fee_bps)
+          .ok_or(BettingMarketError::AmountOverflow)?;
+        let cost = usd_amount
+          .checked_add(taker_fee)
+          .ok_or(BettingMarketError::AmountOverflow)?;
+        remaining_budget = remaining_budget.saturating_sub(cost);
+      }
+      if is_maker_limit_reached || fill_amount < amount_at_inverse_buy_price {
+        break;
       }
     }
-    set_pubkey_10_at_ptr_offset(
-      data_ptr,
-      user_accounts_for_price_offset,
-      user_accounts_for_price,
-    );
-    set_u64_10_at_ptr_offset(
-      data_ptr,
-      payout_in_usd_for_price_offset,
-      payout_in_usd_for_price,
-    );
-    set_u64_10_at_ptr_offset(
-      data_ptr,
-      payout_amounts_for_price_offset,
-      payout_amounts_for_price,
-    );
-  }
+    let fill_amount = fills
+      .iter()
+      .try_fold(0u64, |total, (_, amount)| total.checked_add(*amount))
+      .ok_or(BettingMarketError::AmountOverflow)?;
+    msg!("market order fill amount: {}", fill_amount);
+    if fill_amount == 0 {
+      return Ok(());
+    }
 
-  // add order info to orderbook and update the buy amount at the order price
-  fn add_order_to_orderbook(
-    user_account: &AccountInfo,
-    data_ptr: *mut u8,
-    is_yes: bool,
-    price: u64,
-    order_size: u64,
-    should_payout_in_usd: bool,
-  ) {
-    if order_size == 0 {
-      return;
-    }
-    msg!(
-      "orderbook: adding {} {} tokens at price {} with payout in {}{}",
-      order_size,
-      if is_yes { "yes" } else { "no" },
-      price,
-      if should_payout_in_usd {
-        "usd"
-      } else {
-        if is_yes {
-          "yes tokens"
-        } else {
-          "no tokens"
-        }
-      },
-      ""
-    );
-    let price_isize = price as isize;
-    let buy_amount_offset = if is_yes {
-      YES_BUY_AMOUNT_START_OFFSET
+    // burn inverse tokens first for quantity orders, budget orders are paid entirely in usd
+    let (inverse_sell_mint, inverse_sell_account) = if is_yes {
+      (no_token_mint, user_no_token_account)
     } else {
-      NO_BUY_AMOUNT_START_OFFSET
-    } + U64_ISIZE * price_isize;
-    let current_buy_amount = state::get_u64_at_ptr_offset(data_ptr, buy_amount_offset);
-    let updated_buy_amount = current_buy_amount + order_size;
-    state::set_u64_at_ptr_offset(data_ptr, buy_amount_offset, updated_buy_amount);
-    // msg!(
-    //   "orderbook updated: buy amount from {} to {} for price {}",
-    //   current_buy_amount,
-    //   updated_buy_amount,
-    //   price
-    // );
-    // append to user_account_for_price
-    let user_accounts_for_price_offset =
-      USER_ACCOUNTS_FOR_PRICE_START_OFFSET + PUBKEY_10_ISIZE * price_isize;
-    let mut user_accounts_for_price =
-      state::get_pubkey_10_at_ptr_offset(data_ptr, user_accounts_for_price_offset);
-    // TODO: replace with https://programming-idioms.org/idiom/223/for-else-loop/3852/rust
-    for i in 0..10 {
-      // msg!(
-      //   "user_accounts_for_price[{}] = {}",
-      //   i,
-      //   user_accounts_for_price[i]
-      // );
-      if user_accounts_for_price[i] == NULL_PUBKEY {
-        user_accounts_for_price[i] = *user_account.key;
-        // msg!(
-        //   "orderbook updated: user_accounts_for_price[{}] = {}",
-        //   i,
-        //   user_accounts_for_price[i]
-        // );
-        break;
-      }
-    }
-    set_pubkey_10_at_ptr_offset(
-      data_ptr,
-      user_accounts_for_price_offset,
-      user_accounts_for_price,
-    );
-    // append to payout_in_usd_for_price bool {1, 2}
-    let payout_in_usd_for_price_offset =
-      PAYOUT_IN_USD_FOR_PRICE_START_OFFSET + U64_10_ISIZE * price_isize;
-    let mut payout_in_usd_for_price =
-      state::get_u64_10_at_ptr_offset(data_ptr, payout_in_usd_for_price_offset);
-    for i in 0..10 {
-      // msg!("payout_in_usd_for_price[{}] = {}", i, payout_in_usd_for_price[i]);
-      if payout_in_usd_for_price[i] == 0 {
-        payout_in_usd_for_price[i] = if should_payout_in_usd { 2 } else { 1 };
-        // msg!(
-        //   "order updated: payout_in_usd_for_price[{}] = {}",
-        //   i,
-        //   payout_in_usd_for_price[i]
-        // );
-        break;
-      }
-    }
-    set_u64_10_at_ptr_offset(
-      data_ptr,
-      payout_in_usd_for_price_offset,
-      payout_in_usd_for_price,
-    );
-    // append to payout_amounts_for_price
-    let payout_amounts_for_price_offset =
-      PAYOUT_AMOUNTS_FOR_PRICE_START_OFFSET + U64_10_ISIZE * price_isize;
-    let mut payout_amounts_for_price =
-      state::get_u64_10_at_ptr_offset(data_ptr, payout_amounts_for_price_offset);
-    for i in 0..10 {
-      // msg!(
-      //   "payout_amounts_for_price[{}] = {}",
-      //   i,
-      //   payout_amounts_for_price[i]
-      // );
-      if payout_amounts_for_price[i] == 0 {
-        payout_amounts_for_price[i] = order_size;
-        // msg!(
-        //   "orderbook updated: payout_amounts_for_price[{}] = {}",
-        //   i,
-        //   payout_amounts_for_price[i]
-        // );
-        break;
-      }
-    }
-    set_u64_10_at_ptr_offset(
-      data_ptr,
-      payout_amounts_for_price_offset,
-      payout_amounts_for_price,
-    );
-  }
-
-  // add payout info at first free index
-  fn add_payout(
-    data_ptr: *mut u8,
-    payout_user_account: Pubkey,
-    payout_mint: Pubkey,
-    payout_amount: u64,
-  ) {
-    if payout_amount == 0 {
-      return;
-    }
-    for i in 0..100 {
-      if !payout_exists_at_index(data_ptr, i) {
-        set_payout_at_index(data_ptr, i, payout_user_account, payout_mint, payout_amount);
-        msg!("added payout info to index {}", i);
-        // msg!(
-        //   "payout user account: {}, payout mint: {}, payout amount: {}",
-        //   payout_user_account,
-        //   payout_mint,
-        //   payout_amount
-        // );
-        break;
-      }
-    }
-  }
-
-  // iterate thru payout data and pay out all the payouts for the user
-  fn process_payout(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
-    let account_info_iter = &mut accounts.iter();
-    let user_account = next_account_info(account_info_iter)?;
-    let pda_account = next_account_info(account_info_iter)?;
-    let betting_market_data_account = next_account_info(account_info_iter)?;
-    let usd_token_mint = next_account_info(account_info_iter)?;
-    let yes_token_mint = next_account_info(account_info_iter)?;
-    let no_token_mint = next_account_info(account_info_iter)?;
-    let user_usd_token_account = next_account_info(account_info_iter)?;
-    let user_yes_token_account = next_account_info(account_info_iter)?;
-    let user_no_token_account = next_account_info(account_info_iter)?;
-    let pda_usd_token_account = next_account_info(account_info_iter)?;
-    let token_program_id = next_account_info(account_info_iter)?;
-
-    let (pda, bump_seed) = Pubkey::find_program_address(&[b"betting"], program_id);
-    if *pda_account.key != pda {
-      return Err(BettingMarketError::InvalidPda.into());
-    }
-
-    let data_ptr = betting_market_data_account.data.borrow_mut().as_mut_ptr();
-
-    for i in 0..100 {
-      if payout_exists_at_index(data_ptr, i) {
-        let (payout_user_account, payout_mint, payout_amount) = get_payout_at_index(data_ptr, i);
-        if payout_user_account == *user_account.key {
-          // pay out the user and clear the payout
-          if payout_mint == *usd_token_mint.key {
-            msg!("payout {}: transfer {} usd to user", i, payout_amount);
-            invoke_signed(
-              &spl_token::instruction::transfer(
-                token_program_id.key,
-                pda_usd_token_account.key,
-                user_usd_token_account.key,
-                pda_account.key,
-                &[],
-                payout_amount,
-              )?,
-              &[
-                pda_usd_token_account.clone(),
-                user_usd_token_account.clone(),
-                pda_account.clone(),
-                token_program_id.clone(),
-              ],
-              &[&[&b"betting"[..], &[bump_seed]]],
-            )?;
-          } else if payout_mint == *yes_token_mint.key {
-            msg!("payout {}: mint {} yes to user", i, payout_amount);
-            invoke_signed(
-              &spl_token::instruction::mint_to(
-                token_program_id.key,
-                yes_token_mint.key,
-                user_yes_token_account.key,
-                pda_account.key,
-                &[],
-                payout_amount,
-              )?,
-              &[
-                yes_token_mint.clone(),
-                user_yes_token_account.clone(),
-                pda_account.clone(),
-                token_program_id.clone(),
-              ],
-              &[&[&b"betting"[..], &[bump_seed]]],
-            )?;
-          } else if payout_mint == *no_token_mint.key {
-            msg!("payout {}: mint {} no to user", i, payout_amount);
-            invoke_signed(
-              &spl_token::instruction::mint_to(
-                token_program_id.key,
-                no_token_mint.key,
-                user_no_token_account.key,
-                pda_account.key,
-                &[],
-                payout_amount,
-              )?,
-              &[
-                no_token_mint.clone(),
-                user_no_token_account.clone(),
-                pda_account.clone(),
-                token_program_id.clone(),
-              ],
-              &[&[&b"betting"[..], &[bump_seed]]],
-            )?;
-          } else {
-            msg!("payout {}: bad payout mint", i);
-          }
-          set_payout_at_index(data_ptr, i, NULL_PUBKEY, NULL_PUBKEY, 0);
-        }
+      (yes_token_mint, user_yes_token_account)
+    };
+    let inverse_collateralized_amount = match size {
+      MarketOrderSize::Quantity(_) => {
+        let inverse_sell_account_data = TokenAccount::unpack(&inverse_sell_account.data.borrow())?;
+        std::cmp::min(inverse_sell_account_data.amount, fill_amount)
       }
-    }
-
-    let result = get_u8_at_ptr_offset(data_ptr, 1);
-    if result == 1 || result == 2 {
-      // unpack token account data
-      let user_yes_token_account_data: TokenAccount =
-        TokenAccount::unpack(&user_yes_token_account.data.borrow())?;
-      let user_no_token_account_data: TokenAccount =
-        TokenAccount::unpack(&user_no_token_account.data.borrow())?;
-      let user_yes_token_amount = user_yes_token_account_data.amount;
-      let user_no_token_amount = user_no_token_account_data.amount;
-      msg!("yes token amount: {}", user_yes_token_amount);
-      msg!("no token amount: {}", user_no_token_amount);
-      // burn all yes and no tokens
+      MarketOrderSize::Budget(_) => 0,
+    };
+    if inverse_collateralized_amount > 0 {
       invoke(
         &spl_token::instruction::burn(
           token_program_id.key,
-          user_yes_token_account.key,
-          yes_token_mint.key,
+          inverse_sell_account.key,
+          inverse_sell_mint.key,
           user_account.key,
           &[],
-          user_yes_token_amount,
+          inverse_collateralized_amount,
         )?,
         &[
-          user_yes_token_account.clone(),
-          yes_token_mint.clone(),
+          inverse_sell_account.clone(),
+          inverse_sell_mint.clone(),
           user_account.clone(),
           token_program_id.clone(),
         ],
       )?;
+      msg!("burned {} inverse tokens", inverse_collateralized_amount);
+    }
+
+    // split the fills into the inverse collateralized part and the usd collateralized part
+    let mut inverse_collateralized_fills: Vec<(u64, u64)> = Vec::new();
+    let mut usd_collateralized_fills: Vec<(u64, u64)> = Vec::new();
+    let mut unassigned_inverse_collateralized_amount = inverse_collateralized_amount;
+    for (forward_buy_price, amount) in fills {
+      let inverse_collateralized_fill =
+        std::cmp::min(amount, unassigned_inverse_collateralized_amount);
+      unassigned_inverse_collateralized_amount -= inverse_collateralized_fill;
+      if inverse_collateralized_fill > 0 {
+        inverse_collateralized_fills.push((forward_buy_price, inverse_collateralized_fill));
+      }
+      if amount > inverse_collateralized_fill {
+        usd_collateralized_fills.push((forward_buy_price, amount - inverse_collateralized_fill));
+      }
+    }
+
+    // transfer exactly the executed cost of the usd collateralized fills, taker fees are paid on top
+    let usd_amount = usd_collateralized_fills
+      .iter()
+      .try_fold(0u64, |total, (forward_buy_price, amount)| {
+        total.checked_add(forward_buy_price.checked_mul(*amount)?)
+      })
+      .ok_or(BettingMarketError::AmountOverflow)?;
+    if usd_amount > 0 {
       invoke(
-        &spl_token::instruction::burn(
+        &spl_token::instruction::transfer(
           token_program_id.key,
-          user_no_token_account.key,
-          no_token_mint.key,
+          user_usd_token_account.key,
+          pda_usd_token_account.key,
           user_account.key,
           &[],
-          user_no_token_amount,
+          usd_amount,
         )?,
         &[
-          user_no_token_account.clone(),
-          no_token_mint.clone(),
+          user_usd_token_account.clone(),
+          pda_usd_token_account.clone(),
           user_account.clone(),
           token_program_id.clone(),
         ],
       )?;
-      // transfer usd amount based on result
-      let mut usd_transfer_amount = 0;
-      if result == 1 {
-        msg!(
-          "betting market yes wins, transferring {} usd",
-          user_yes_token_amount
-        );
-        usd_transfer_amount = user_yes_token_amount * 100;
-      } else if result == 2 {
-        msg!(
-          "betting market no wins, transferring {} usd",
-          user_no_token_amount
-        );
-        usd_transfer_amount = user_no_token_amount * 100;
-      }
+      msg!("transferred {} usd", usd_amount);
+    }
+
+    let mut match_fees = MatchFees::default();
+    let mut matched_makers = Vec::new();
+    for (forward_buy_price, amount) in inverse_collateralized_fills {
+      Self::match_and_place_limit_order(
+        is_yes,
+        amount,
+        forward_buy_price,
+        true,
+        user_account,
+        &mut order_book_data,
+        claim_accounts,
+        betting_market_data_account.key,
+        &betting_market_data,
+        &mut match_fees,
+        &mut matched_makers,
+        program_id,
+      )?;
+    }
+    for (forward_buy_price, amount) in usd_collateralized_fills {
+      Self::match_and_place_limit_order(
+        is_yes,
+        amount,
+        forward_buy_price,
+        false,
+        user_account,
+        &mut order_book_data,
+        claim_accounts,
+        betting_market_data_account.key,
+        &betting_market_data,
+        &mut match_fees,
+        &mut matched_makers,
+        program_id,
+      )?;
+    }
+
+    Self::collect_match_fees(
+      &match_fees,
+      &betting_market_data,
+      None,
+      claim_accounts,
+      program_id,
+      user_account,
+      user_usd_token_account,
+      pda_account,
+      pda_usd_token_account,
+      fee_vault,
+      token_program_id,
+      betting_market_data_account.key,
+      bump_seed,
+    )?;
+
+    // the strike price is locked in from the first trade on
+    if !betting_market_data.has_traded {
+      betting_market_data.has_traded = true;
+      Self::pack_betting_market(betting_market_data, betting_market_data_account)?;
+    }
+
+    Ok(())
+  }
+
+  // split the fees taken while matching between the creator, the referrer and the fee vault
+  // the creator and referrer shares stay in the betting market's usd token account and are credited to their
+  // claimable balances, the rest moves to the fee vault, with the taker paying whatever is still due and
+  // depositing the maker fee of their resting order into the betting market's usd token account
+  #[allow(clippy::too_many_arguments)]
+  fn collect_match_fees<'a>(
+    match_fees: &MatchFees,
+    betting_market_data: &BettingMarket,
+    referrer: Option<&Pubkey>,
+    claim_accounts: &[AccountInfo],
+    program_id: &Pubkey,
+    user_account: &AccountInfo<'a>,
+    user_usd_token_account: &AccountInfo<'a>,
+    pda_account: &AccountInfo<'a>,
+    pda_usd_token_account: &AccountInfo<'a>,
+    fee_vault: &AccountInfo<'a>,
+    token_program_id: &AccountInfo<'a>,
+    market: &Pubkey,
+    bump_seed: u8,
+  ) -> ProgramResult {
+    let total_fees = match_fees
+      .maker_fees
+      .checked_add(match_fees.taker_fees)
+      .ok_or(BettingMarketError::AmountOverflow)?;
+    let referrer_fees = match referrer {
+      Some(_) => BettingMarket::fee_for(
+        match_fees.taker_fees,
+        betting_market_data.referrer_fee_share_bps,
+      )
+      .ok_or(BettingMarketError::AmountOverflow)?,
+      None => 0,
+    };
+    let creator_fees = if betting_market_data.creator != NULL_PUBKEY {
+      BettingMarket::fee_for(total_fees, betting_market_data.creator_fee_share_bps)
+        .ok_or(BettingMarketError::AmountOverflow)?
+    } else {
+      0
+    };
+    // shares are at most the whole fee, so this can't underflow
+    let shared_fees = creator_fees + referrer_fees;
+    let vault_held_fees = total_fees - match_fees.taker_fees_due;
+    let (vault_to_fee_vault, taker_to_vault) = if shared_fees <= vault_held_fees {
+      (vault_held_fees - shared_fees, 0)
+    } else {
+      (0, shared_fees - vault_held_fees)
+    };
+    let taker_to_fee_vault = match_fees.taker_fees_due - taker_to_vault;
+    let taker_to_vault = taker_to_vault
+      .checked_add(match_fees.maker_fee_deposit)
+      .ok_or(BettingMarketError::AmountOverflow)?;
+
+    if creator_fees > 0 {
+      Self::add_payout(
+        claim_accounts,
+        market,
+        program_id,
+        &betting_market_data.creator,
+        ClaimableToken::Usd,
+        creator_fees,
+      )?;
+      msg!("credited {} usd of fees to the creator", creator_fees);
+    }
+    if let Some(referrer) = referrer.filter(|_| referrer_fees > 0) {
+      Self::add_payout(
+        claim_accounts,
+        market,
+        program_id,
+        referrer,
+        ClaimableToken::Usd,
+        referrer_fees,
+      )?;
+      msg!("credited {} usd of fees to the referrer", referrer_fees);
+    }
+
+    if vault_to_fee_vault > 0 {
       invoke_signed(
         &spl_token::instruction::transfer(
           token_program_id.key,
           pda_usd_token_account.key,
-          user_usd_token_account.key,
+          fee_vault.key,
           pda_account.key,
           &[],
-          usd_transfer_amount,
+          vault_to_fee_vault,
         )?,
         &[
           pda_usd_token_account.clone(),
-          user_usd_token_account.clone(),
+          fee_vault.clone(),
           pda_account.clone(),
           token_program_id.clone(),
         ],
-        &[&[&b"betting"[..], &[bump_seed]]],
+        &[&[&b"betting"[..], market.as_ref(), &[bump_seed]]],
       )?;
-      msg!("transferred {} usd", usd_transfer_amount);
-    } else {
-      msg!("betting market not judged yet");
+      msg!("moved {} usd of fees to the fee vault", vault_to_fee_vault);
+    }
+    for (destination, taker_fees) in [
+      (pda_usd_token_account, taker_to_vault),
+      (fee_vault, taker_to_fee_vault),
+    ] {
+      if taker_fees == 0 {
+        continue;
+      }
+      invoke(
+        &spl_token::instruction::transfer(
+          token_program_id.key,
+          user_usd_token_account.key,
+          destination.key,
+          user_account.key,
+          &[],
+          taker_fees,
+        )?,
+        &[
+          user_usd_token_account.clone(),
+          destination.clone(),
+          user_account.clone(),
+          token_program_id.clone(),
+        ],
+      )?;
+      msg!(
+        "transferred {} usd of fees to {}",
+        taker_fees,
+        destination.key
+      );
     }
-
     Ok(())
   }
 
-  // mint tokens to user for free
-  fn process_free_mint(
-    accounts: &[AccountInfo],
-    amount: u64,
-    program_id: &Pubkey,
+  // pack the betting market metadata back into the start of the data account
+  fn pack_betting_market(
+    betting_market_data: BettingMarket,
+    betting_market_data_account: &AccountInfo,
   ) -> ProgramResult {
-    let account_info_iter = &mut accounts.iter();
-    let pda_account = next_account_info(account_info_iter)?;
-    let desired_token_mint = next_account_info(account_info_iter)?;
-    let user_desired_token_account = next_account_info(account_info_iter)?;
-    let token_program_id = next_account_info(account_info_iter)?;
-
-    let (pda, bump_seed) = Pubkey::find_program_address(&[b"betting"], program_id);
-    if *pda_account.key != pda {
-      return Err(BettingMarketError::InvalidPda.into());
-    }
-
-    msg!(
-      "minting {} of {} to user token account {}",
-      amount,
-      desired_token_mint.key,
-      user_desired_token_account.key
-    );
-    invoke_signed(
-      &spl_token::instruction::mint_to(
-        token_program_id.key,
-        desired_token_mint.key,
-        user_desired_token_account.key,
-        pda_account.key,
-        &[],
-        amount,
-      )?,
-      &[
-        desired_token_mint.clone(),
-        user_desired_token_account.clone(),
-        pda_account.clone(),
-        token_program_id.clone(),
-      ],
-      &[&[&b"betting"[..], &[bump_seed]]],
-    )?;
-
-    Ok(())
+    let mut data = betting_market_data_account.data.borrow_mut();
+    let slice = data
+      .get_mut(..BettingMarket::LEN)
+      .ok_or(ProgramError::InvalidAccountData)?;
+    BettingMarket::pack(betting_market_data, slice)
   }
 
-  // set the betting market result manually
-  fn process_judge_betting_market_manually(accounts: &[AccountInfo], result: u64) -> ProgramResult {
-    let account_info_iter = &mut accounts.iter();
-    let betting_market_data_account = next_account_info(account_info_iter)?;
-
-    let data_ptr = betting_market_data_account.data.borrow_mut().as_mut_ptr();
-
-    let old_result = get_u8_at_ptr_offset(data_ptr, 1);
-    msg!("old result: {}", old_result);
-    set_u8_at_ptr_offset(data_ptr, 1, result.try_into().unwrap());
-    let new_result = get_u8_at_ptr_offset(data_ptr, 1);
-    msg!("new result: {}", new_result);
-
-    Ok(())
-  }
+  // match as much of the limit order as possible by crossing over and then
+  // place the remaining unmatched amount as a resting limit order on the order book
+  // once the trade matched the maximum number of makers the remaining amount is credited back to the
+  // user's claimable balance account instead, so it can't rest crossed with the order book, and returned
+  // Access violation in unknown section at address 0xfffffffffffff017 of size 8 by instruction #7094
+  // wtf the program crashes if I don't put this
+  #[inline(always)]
+  #[allow(clippy::too_many_arguments)]
+  fn match_and_place_limit_order(
+    is_yes: bool,
+    order_size: u64,
+    limit_price: u64,
+    order_payout_in_usd: bool,
+    user_account: &AccountInfo,
+    order_book_data: &mut [u8],
+    claim_accounts: &[AccountInfo],
+    market: &Pubkey,
+    betting_market_data: &BettingMarket,
+    match_fees: &mut MatchFees,
+    matched_makers: &mut Vec<Pubkey>,
+    program_id: &Pubkey,
+  ) -> Result<u64, ProgramError> {
+    let mut matched_amount = 0;
+    let mut unmatched_amount = order_size;
+    let mut is_maker_limit_reached = false;
 
-  // set the betting market result with from oracle
-  fn process_judge_betting_market_oracle(accounts: &[AccountInfo]) -> ProgramResult {
-    let account_info_iter = &mut accounts.iter();
-    let betting_market_data_account = next_account_info(account_info_iter)?;
-    let pyth_price_account = next_account_info(account_info_iter)?;
-
-    let pyth_price_account_data = &pyth_price_account.try_borrow_data()?;
-    let pyth_price = pyth_client::cast::<pyth_client::Price>(pyth_price_account_data);
-    let data_ptr = betting_market_data_account.data.borrow_mut().as_mut_ptr();
-
-    msg!("oracle price: {}", pyth_price.agg.price);
-    let strike_price = get_u64_at_ptr_offset(data_ptr, 98);
-    msg!("strike price: {}", strike_price);
-    let betting_market_result =
-      if pyth_price.agg.price > (strike_price * 1_000_000_000).try_into().unwrap() {
-        1
-      } else {
-        2
-      };
-    msg!("betting market result: {}", betting_market_result);
+    // iterate thru inverse buy orders from 100 to (100-limit price) inclusive
+    // while unmatched_amount > 0, perform trade
+    // trade will fully exhaust either one or both sides
+    // the inverse side will have exactly 1 settled result per order filled (same price can have multiple orders)
+    // the forward side will have either 1 or 2 settled results per order filled
+    // if it's a usd payout order, it'll be 1 payout at the forward buy price
+    // if it's a forward buy mint order, it'll be 1 payout in forward buy tokens and 1 payout for the price differential
+    // update the order book and credit the settled results to the claimable balance accounts
+    for inverse_buy_price in ((100 - limit_price)..101).rev() {
+      if unmatched_amount == 0 {
+        break;
+      }
+      let amount_at_inverse_buy_price =
+        orderbook::get_level_amount(order_book_data, !is_yes, inverse_buy_price);
+      let matchable_at_price = std::cmp::min(unmatched_amount, amount_at_inverse_buy_price);
+      if matchable_at_price == 0 {
+        continue;
+      }
 
-    set_u8_at_ptr_offset(data_ptr, 1, betting_market_result);
+      // match orders for the inverse token on the orderbook at inverse buy price and create corresponding payouts
+      let matched_at_price = Self::match_orders_at_price_fifo(
+        order_book_data,
+        claim_accounts,
+        market,
+        betting_market_data.maker_fee_bps,
+        match_fees,
+        matched_makers,
+        program_id,
+        !is_yes,
+        matchable_at_price,
+        inverse_buy_price,
+      )?;
+      matched_amount += matched_at_price;
+      unmatched_amount -= matched_at_price;
+      is_maker_limit_reached = matched_at_price < matchable_at_price;
 
-    Ok(())
-  }
+      if matched_at_price > 0 {
+        msg!(
+          "matched {} inverse buys at price {}, updating amount at {} buy price from {} to {}",
+          matched_at_price,
+          inverse_buy_price,
+          if is_yes { "no" } else { "yes" },
+          amount_at_inverse_buy_price,
+          amount_at_inverse_buy_price - matched_at_price,
+        );
 
-  // set the betting market strike price
-  fn process_set_strike_price(accounts: &[AccountInfo], strike_price: u64) -> ProgramResult {
+        // create payout for user at forward buy price
+        let forward_buy_price = 100 - inverse_buy_price; // cross over at better than limit price
+        let forward_buy_price_differential = limit_price - forward_buy_price; // geq 0
+        if order_payout_in_usd {
+          // order was selling inverse, so usd amount is at the inverse buy price, minus the taker fee
+          let usd_amount = matched_at_price
+            .checked_mul(inverse_buy_price)
+            .ok_or(BettingMarketError::AmountOverflow)?;
+          let taker_fee = BettingMarket::fee_for(usd_amount, betting_market_data.taker_fee_bps)
+            .ok_or(BettingMarketError::AmountOverflow)?;
+          match_fees.taker_fees = match_fees
+            .taker_fees
+            .checked_add(taker_fee)
+            .ok_or(BettingMarketError::AmountOverflow)?;
+          Self::add_payout(
+            claim_accounts,
+            market,
+            program_id,
+            user_account.key,
+            ClaimableToken::Usd,
+            usd_amount - taker_fee,
+          )?;
+        } else {
+          // order was buying forward tokens at the forward buy price, the taker fee is paid on top
+          let taker_fee = matched_at_price
+            .checked_mul(forward_buy_price)
+            .and_then(|usd_amount| {
+              BettingMarket::fee_for(usd_amount, betting_market_data.taker_fee_bps)
+            })
+            .ok_or(BettingMarketError::AmountOverflow)?;
+          match_fees.taker_fees = match_fees
+            .taker_fees
+            .checked_add(taker_fee)
+            .ok_or(BettingMarketError::AmountOverflow)?;
+          match_fees.taker_fees_due = match_fees
+            .taker_fees_due
+            .checked_add(taker_fee)
+            .ok_or(BettingMarketError::AmountOverflow)?;
+          Self::add_payout(
+            claim_accounts,
+            market,
+            program_id,
+            user_account.key,
+            ClaimableToken::outcome(is_yes),
+            matched_at_price,
+          )?;
+          // usd price differential paid back
+          Self::add_payout(
+            claim_accounts,
+            market,
+            program_id,
+            user_account.key,
+            ClaimableToken::Usd,
+            matched_at_price
+              .checked_mul(forward_buy_price_differential)
+              .ok_or(BettingMarketError::AmountOverflow)?,
+          )?;
+        }
+      }
+      if is_maker_limit_reached {
+        msg!("matched the maximum number of makers");
+        break;
+      }
+    }
+
+    // refund the remaining unmatched amount past the maker limit, inverse collateral in inverse tokens and
+    // usd collateral at the limit price, otherwise add it as order to order book
+    let refunded_amount = if is_maker_limit_reached {
+      unmatched_amount
+    } else {
+      0
+    };
+    if is_maker_limit_reached {
+      let (refund_token, refund_amount) = if order_payout_in_usd {
+        (ClaimableToken::outcome(!is_yes), unmatched_amount)
+      } else {
+        (
+          ClaimableToken::Usd,
+          unmatched_amount
+            .checked_mul(limit_price)
+            .ok_or(BettingMarketError::AmountOverflow)?,
+        )
+      };
+      Self::add_payout(
+        claim_accounts,
+        market,
+        program_id,
+        user_account.key,
+        refund_token,
+        refund_amount,
+      )?;
+    } else if unmatched_amount > 0 {
+      Self::add_order_to_orderbook(
+        user_account,
+        order_book_data,
+        is_yes,
+        limit_price,
+        unmatched_amount,
+        order_payout_in_usd,
+      )?;
+      // a resting usd collateralized order deposits its maker fee, its fills pay out in tokens
+      if !order_payout_in_usd {
+        let maker_fee_deposit = unmatched_amount
+          .checked_mul(limit_price)
+          .and_then(|usd_amount| betting_market_data.maker_fee_deposit_for(usd_amount))
+          .and_then(|deposit| deposit.checked_add(match_fees.maker_fee_deposit))
+          .ok_or(BettingMarketError::AmountOverflow)?;
+        match_fees.maker_fee_deposit = maker_fee_deposit;
+      }
+    }
+
+    msg!(
+      "matched amount: {}, unmatched amount: {}",
+      matched_amount,
+      unmatched_amount
+    );
+
+    Ok(refunded_amount)
+  }
+
+  // match orders at a fixed price from the orderbook in fifo fashion for the order size and create corresponding payouts
+  // maker fees are deducted from usd payouts or taken from the order's maker fee deposit for token payouts
+  // stops at the first order of a new maker once the trade matched the maximum number of makers and
+  // returns the matched amount
+  #[allow(clippy::too_many_arguments)]
+  fn match_orders_at_price_fifo(
+    order_book_data: &mut [u8],
+    claim_accounts: &[AccountInfo],
+    market: &Pubkey,
+    maker_fee_bps: u16,
+    match_fees: &mut MatchFees,
+    matched_makers: &mut Vec<Pubkey>,
+    program_id: &Pubkey,
+    is_yes: bool,
+    order_size: u64,
+    order_price: u64,
+  ) -> Result<u64, ProgramError> {
+    let mut unmatched_order_buys = order_size;
+    while unmatched_order_buys > 0 {
+      let order = match orderbook::front_order(order_book_data, is_yes, order_price) {
+        Some(order) => order,
+        None => break,
+      };
+      if !matched_makers.contains(&order.user_account) {
+        if matched_makers.len() >= MAX_MAKERS_PER_MATCH {
+          break;
+        }
+        matched_makers.push(order.user_account);
+      }
+      let matched_for_order = std::cmp::min(order.amount, unmatched_order_buys);
+      if order.payout_in_usd {
+        // original order was selling inverse, so usd amount is inverse, minus the maker fee
+        let usd_amount = matched_for_order
+          .checked_mul(100 - order_price)
+          .ok_or(BettingMarketError::AmountOverflow)?;
+        let maker_fee = BettingMarket::fee_for(usd_amount, maker_fee_bps)
+          .ok_or(BettingMarketError::AmountOverflow)?;
+        match_fees.maker_fees = match_fees
+          .maker_fees
+          .checked_add(maker_fee)
+          .ok_or(BettingMarketError::AmountOverflow)?;
+        Self::add_payout(
+          claim_accounts,
+          market,
+          program_id,
+          &order.user_account,
+          ClaimableToken::Usd,
+          usd_amount - maker_fee,
+        )?;
+      } else {
+        // original order was buying tokens at the order price, the maker fee comes out of its deposit
+        let maker_fee = matched_for_order
+          .checked_mul(order_price)
+          .and_then(|usd_amount| BettingMarket::fee_for(usd_amount, maker_fee_bps))
+          .ok_or(BettingMarketError::AmountOverflow)?;
+        match_fees.maker_fees = match_fees
+          .maker_fees
+          .checked_add(maker_fee)
+          .ok_or(BettingMarketError::AmountOverflow)?;
+        Self::add_payout(
+          claim_accounts,
+          market,
+          program_id,
+          &order.user_account,
+          ClaimableToken::outcome(is_yes),
+          matched_for_order,
+        )?;
+      }
+      unmatched_order_buys -= matched_for_order;
+      // pops from front if order fully matched
+      orderbook::fill_front_order(order_book_data, is_yes, order_price, matched_for_order)?;
+    }
+    Ok(order_size - unmatched_order_buys)
+  }
+
+  // add order info to orderbook and update the buy amount at the order price
+  fn add_order_to_orderbook(
+    user_account: &AccountInfo,
+    order_book_data: &mut [u8],
+    is_yes: bool,
+    price: u64,
+    order_size: u64,
+    should_payout_in_usd: bool,
+  ) -> ProgramResult {
+    if order_size == 0 {
+      return Ok(());
+    }
+    msg!(
+      "orderbook: adding {} {} tokens at price {} with payout in {}",
+      order_size,
+      if is_yes { "yes" } else { "no" },
+      price,
+      if should_payout_in_usd {
+        "usd"
+      } else if is_yes {
+        "yes tokens"
+      } else {
+        "no tokens"
+      },
+    );
+    orderbook::push_order(
+      order_book_data,
+      is_yes,
+      price,
+      user_account.key,
+      order_size,
+      should_payout_in_usd,
+    )
+  }
+
+  // remove all of the user's orders at the price from the orderbook
+  // returns the (usd collateralized, inverse collateralized) order sizes that were removed
+  fn remove_user_orders_from_orderbook(
+    user_account: &AccountInfo,
+    order_book_data: &mut [u8],
+    is_yes: bool,
+    price: u64,
+  ) -> Result<(u64, u64), ProgramError> {
+    let current_buy_amount = orderbook::get_level_amount(order_book_data, is_yes, price);
+    let (usd_collateralized_amount, inverse_collateralized_amount) =
+      orderbook::remove_user_orders(order_book_data, is_yes, price, user_account.key)?;
+    let cancelled_amount = usd_collateralized_amount
+      .checked_add(inverse_collateralized_amount)
+      .ok_or(BettingMarketError::AmountOverflow)?;
+    if cancelled_amount == 0 {
+      return Err(BettingMarketError::OrderNotFound.into());
+    }
+    msg!(
+      "orderbook: removed {} {} tokens at price {}, updating buy amount from {} to {}",
+      cancelled_amount,
+      if is_yes { "yes" } else { "no" },
+      price,
+      current_buy_amount,
+      current_buy_amount - cancelled_amount,
+    );
+
+    Ok((usd_collateralized_amount, inverse_collateralized_amount))
+  }
+
+  // credit the payout to the user's claimable balance account
+  fn add_payout(
+    claim_accounts: &[AccountInfo],
+    market: &Pubkey,
+    program_id: &Pubkey,
+    payout_user_account: &Pubkey,
+    payout_token: ClaimableToken,
+    payout_amount: u64,
+  ) -> ProgramResult {
+    if payout_amount == 0 {
+      return Ok(());
+    }
+    let claim_account =
+      claim::find_claim_account(claim_accounts, program_id, market, payout_user_account)?;
+    claim::credit(
+      &mut claim_account.try_borrow_mut_data()?,
+      payout_token,
+      payout_amount,
+    )?;
+    msg!(
+      "credited {} {:?} to claim account of {}",
+      payout_amount,
+      payout_token,
+      payout_user_account
+    );
+    Ok(())
+  }
+
+  // drain the user's claimable balances and redeem their tokens if the betting market is resolved
+  fn process_payout(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
+    let user_account = next_account_info(account_info_iter)?;
+    let pda_account = next_account_info(account_info_iter)?;
     let betting_market_data_account = next_account_info(account_info_iter)?;
+    let usd_token_mint = next_account_info(account_info_iter)?;
+    let yes_token_mint = next_account_info(account_info_iter)?;
+    let no_token_mint = next_account_info(account_info_iter)?;
+    let user_usd_token_account = next_account_info(account_info_iter)?;
+    let user_yes_token_account = next_account_info(account_info_iter)?;
+    let user_no_token_account = next_account_info(account_info_iter)?;
+    let pda_usd_token_account = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let claim_account = next_account_info(account_info_iter)?;
+
+    let (betting_market_data, bump_seed) = validation::validate_trade_accounts(
+      program_id,
+      user_account,
+      pda_account,
+      betting_market_data_account,
+      usd_token_mint,
+      yes_token_mint,
+      no_token_mint,
+      user_usd_token_account,
+      user_yes_token_account,
+      user_no_token_account,
+      pda_usd_token_account,
+      token_program_id,
+    )?;
 
-    let data_ptr = betting_market_data_account.data.borrow_mut().as_mut_ptr();
+    let claim_account = claim::find_claim_account(
+      std::slice::from_ref(claim_account),
+      program_id,
+      betting_market_data_account.key,
+      user_account.key,
+    )?;
+    let mut claim_data = claim_account.try_borrow_mut_data()?;
+    let signer_seeds: &[&[u8]] = &[
+      &b"betting"[..],
+      betting_market_data_account.key.as_ref(),
+      &[bump_seed],
+    ];
 
-    let old_strike_price = get_u64_at_ptr_offset(data_ptr, 98);
-    msg!("old strike price: {}", old_strike_price);
-    set_u64_at_ptr_offset(data_ptr, 98, strike_price);
-    let new_strike_price = get_u64_at_ptr_offset(data_ptr, 98);
-    msg!("new strike price: {}", new_strike_price);
+    // pay out the claimable balances and clear them
+    let claimable_usd = claim::take_balance(&mut claim_data, ClaimableToken::Usd);
+    if claimable_usd > 0 {
+      msg!("payout: transfer {} usd to user", claimable_usd);
+      invoke_signed(
+        &spl_token::instruction::transfer(
+          token_program_id.key,
+          pda_usd_token_account.key,
+          user_usd_token_account.key,
+          pda_account.key,
+          &[],
+          claimable_usd,
+        )?,
+        &[
+          pda_usd_token_account.clone(),
+          user_usd_token_account.clone(),
+          pda_account.clone(),
+          token_program_id.clone(),
+        ],
+        &[signer_seeds],
+      )?;
+    }
+    let claimable_yes = claim::take_balance(&mut claim_data, ClaimableToken::Yes);
+    if claimable_yes > 0 {
+      msg!("payout: mint {} yes to user", claimable_yes);
+      invoke_signed(
+        &spl_token::instruction::mint_to(
+          token_program_id.key,
+          yes_token_mint.key,
+          user_yes_token_account.key,
+          pda_account.key,
+          &[],
+          claimable_yes,
+        )?,
+        &[
+          yes_token_mint.clone(),
+          user_yes_token_account.clone(),
+          pda_account.clone(),
+          token_program_id.clone(),
+        ],
+        &[signer_seeds],
+      )?;
+    }
+    let claimable_no = claim::take_balance(&mut claim_data, ClaimableToken::No);
+    if claimable_no > 0 {
+      msg!("payout: mint {} no to user", claimable_no);
+      invoke_signed(
+        &spl_token::instruction::mint_to(
+          token_program_id.key,
+          no_token_mint.key,
+          user_no_token_account.key,
+          pda_account.key,
+          &[],
+          claimable_no,
+        )?,
+        &[
+          no_token_mint.clone(),
+          user_no_token_account.clone(),
+          pda_account.clone(),
+          token_program_id.clone(),
+        ],
+        &[signer_seeds],
+      )?;
+    }
 
+    if let Some((yes_redemption_price, no_redemption_price)) =
+      betting_market_data.redemption_prices()
+    {
+      // unpack token account data
+      let user_yes_token_account_data: TokenAccount =
+        TokenAccount::unpack(&user_yes_token_account.data.borrow())?;
+      let user_no_token_account_data: TokenAccount =
+        TokenAccount::unpack(&user_no_token_account.data.borrow())?;
+      let user_yes_token_amount = user_yes_token_account_data.amount;
+      let user_no_token_amount = user_no_token_account_data.amount;
+      msg!("yes token amount: {}", user_yes_token_amount);
+      msg!("no token amount: {}", user_no_token_amount);
+      // burn all yes and no tokens
+      invoke(
+        &spl_token::instruction::burn(
+          token_program_id.key,
+          user_yes_token_account.key,
+          yes_token_mint.key,
+          user_account.key,
+          &[],
+          user_yes_token_amount,
+        )?,
+        &[
+          user_yes_token_account.clone(),
+          yes_token_mint.clone(),
+          user_account.clone(),
+          token_program_id.clone(),
+        ],
+      )?;
+      invoke(
+        &spl_token::instruction::burn(
+          token_program_id.key,
+          user_no_token_account.key,
+          no_token_mint.key,
+          user_account.key,
+          &[],
+          user_no_token_amount,
+        )?,
+        &[
+          user_no_token_account.clone(),
+          no_token_mint.clone(),
+          user_account.clone(),
+          token_program_id.clone(),
+        ],
+      )?;
+      // transfer usd amount based on the redemption price of each token
+      msg!(
+        "betting market result {}, redeeming yes at {} and no at {}",
+        betting_market_data.result,
+        yes_redemption_price,
+        no_redemption_price
+      );
+      let usd_transfer_amount = user_yes_token_amount
+        .checked_mul(yes_redemption_price)
+        .and_then(|yes_amount| {
+          user_no_token_amount
+            .checked_mul(no_redemption_price)
+            .and_then(|no_amount| yes_amount.checked_add(no_amount))
+        })
+        .ok_or(BettingMarketError::AmountOverflow)?;
+      invoke_signed(
+        &spl_token::instruction::transfer(
+          token_program_id.key,
+          pda_usd_token_account.key,
+          user_usd_token_account.key,
+          pda_account.key,
+          &[],
+          usd_transfer_amount,
+        )?,
+        &[
+          pda_usd_token_account.clone(),
+          user_usd_token_account.clone(),
+          pda_account.clone(),
+          token_program_id.clone(),
+        ],
+        &[&[
+          &b"betting"[..],
+          betting_market_data_account.key.as_ref(),
+          &[bump_seed],
+        ]],
+      )?;
+      msg!("transferred {} usd", usd_transfer_amount);
+    } else {
+      msg!("betting market not judged yet");
+    }
     Ok(())
   }
+
+  // cancel the user's resting orders at a price and refund the collateral that was posted for them
+  fn process_cancel_order(
+    accounts: &[AccountInfo],
+    is_yes: bool,
+    price: u64,
+    program_id: &Pubkey,
+  ) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let user_account = next_account_info(account_info_iter)?;
+    let pda_account = next_account_info(account_info_iter)?;
+    let betting_market_data_account = next_account_info(account_info_iter)?;
+    let order_book_account = next_account_info(account_info_iter)?;
+    let usd_token_mint = next_account_info(account_info_iter)?;
+    let yes_token_mint = next_account_info(account_info_iter)?;
+    let no_token_mint = next_account_info(account_info_iter)?;
+    let user_usd_token_account = next_account_info(account_info_iter)?;
+    let user_yes_token_account = next_account_info(account_info_iter)?;
+    let user_no_token_account = next_account_info(account_info_iter)?;
+    let pda_usd_token_account = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+
+    let (betting_market_data, bump_seed) = validation::validate_trade_accounts(
+      program_id,
+      user_account,
+      pda_account,
+      betting_market_data_account,
+      usd_token_mint,
+      yes_token_mint,
+      no_token_mint,
+      user_usd_token_account,
+      user_yes_token_account,
+      user_no_token_account,
+      pda_usd_token_account,
+      token_program_id,
+    )?;
+    Self::validate_price(price)?;
+
+    let mut order_book_data = validation::validate_order_book_account(
+      betting_market_data_account,
+      &betting_market_data,
+      order_book_account,
+      program_id,
+    )?;
+    let (usd_collateralized_amount, inverse_collateralized_amount) =
+      Self::remove_user_orders_from_orderbook(user_account, &mut order_book_data, is_yes, price)?;
+
+    // refund usd collateral at the limit price it was posted at, along with the unused maker fee deposit
+    let usd_amount = price
+      .checked_mul(usd_collateralized_amount)
+      .and_then(|usd_amount| {
+        BettingMarket::fee_for(usd_amount, betting_market_data.maker_fee_bps)
+          .and_then(|maker_fee| usd_amount.checked_add(maker_fee))
+      })
+      .ok_or(BettingMarketError::AmountOverflow)?;
+    if usd_amount > 0 {
+      invoke_signed(
+        &spl_token::instruction::transfer(
+          token_program_id.key,
+          pda_usd_token_account.key,
+          user_usd_token_account.key,
+          pda_account.key,
+          &[],
+          usd_amount,
+        )?,
+        &[
+          pda_usd_token_account.clone(),
+          user_usd_token_account.clone(),
+          pda_account.clone(),
+          token_program_id.clone(),
+        ],
+        &[&[
+          &b"betting"[..],
+          betting_market_data_account.key.as_ref(),
+          &[bump_seed],
+        ]],
+      )?;
+      msg!("refunded {} usd", usd_amount);
+    }
+
+    // re-mint the inverse tokens that were burned as collateral
+    if inverse_collateralized_amount > 0 {
+      let (inverse_token_mint, user_inverse_token_account) = if is_yes {
+        (no_token_mint, user_no_token_account)
+      } else {
+        (yes_token_mint, user_yes_token_account)
+      };
+      invoke_signed(
+        &spl_token::instruction::mint_to(
+          token_program_id.key,
+          inverse_token_mint.key,
+          user_inverse_token_account.key,
+          pda_account.key,
+          &[],
+          inverse_collateralized_amount,
+        )?,
+        &[
+          inverse_token_mint.clone(),
+          user_inverse_token_account.clone(),
+          pda_account.clone(),
+          token_program_id.clone(),
+        ],
+        &[&[
+          &b"betting"[..],
+          betting_market_data_account.key.as_ref(),
+          &[bump_seed],
+        ]],
+      )?;
+      msg!(
+        "refunded {} {} tokens",
+        inverse_collateralized_amount,
+        if is_yes { "no" } else { "yes" }
+      );
+    }
+
+    Ok(())
+  }
+
+  // mint test usd to user for free, only compiled into devnet builds with the faucet feature
+  // signs with the global faucet pda, which is not the authority of any betting market's mints or vault
+  #[cfg(feature = "faucet")]
+  fn process_free_mint(
+    accounts: &[AccountInfo],
+    amount: u64,
+    program_id: &Pubkey,
+  ) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let pda_account = next_account_info(account_info_iter)?;
+    let desired_token_mint = next_account_info(account_info_iter)?;
+    let user_desired_token_account = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+
+    let (pda, bump_seed) = Pubkey::find_program_address(&[b"betting"], program_id);
+    if *pda_account.key != pda {
+      return Err(BettingMarketError::InvalidPda.into());
+    }
+    validation::validate_token_program(token_program_id)?;
+    match FAUCET_USD_MINT.map(Pubkey::from_str) {
+      Some(Ok(faucet_usd_mint)) if faucet_usd_mint == *desired_token_mint.key => {}
+      _ => return Err(BettingMarketError::FaucetMintNotAllowed.into()),
+    }
+    if amount > FAUCET_MAX_MINT_AMOUNT {
+      return Err(BettingMarketError::FaucetLimitExceeded.into());
+    }
+
+    msg!(
+      "minting {} of {} to user token account {}",
+      amount,
+      desired_token_mint.key,
+      user_desired_token_account.key
+    );
+    invoke_signed(
+      &spl_token::instruction::mint_to(
+        token_program_id.key,
+        desired_token_mint.key,
+        user_desired_token_account.key,
+        pda_account.key,
+        &[],
+        amount,
+      )?,
+      &[
+        desired_token_mint.clone(),
+        user_desired_token_account.clone(),
+        pda_account.clone(),
+        token_program_id.clone(),
+      ],
+      &[&[&b"betting"[..], &[bump_seed]]],
+    )?;
+
+    Ok(())
+  }
+
+  // resolve a disputed result proposal, the judge's result is final and whichever of the proposer and
+  // disputer it agrees with is credited both bonds
+  // oracle markets are settled by the judge instead once the oracle's grace period is over
+  fn process_judge_betting_market_manually(
+    accounts: &[AccountInfo],
+    result: u64,
+    yes_payout: u64,
+    program_id: &Pubkey,
+  ) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let betting_market_data_account = next_account_info(account_info_iter)?;
+    let judge_account = next_account_info(account_info_iter)?;
+    let winner_claim_account = account_info_iter.next();
+
+    let mut betting_market_data =
+      validation::validate_betting_market_account(betting_market_data_account, program_id)?;
+    validation::validate_judge(&betting_market_data, judge_account)?;
+    validation::validate_standalone_market(&betting_market_data)?;
+    if betting_market_data.result != 0 {
+      return Err(BettingMarketError::MarketAlreadyResolved.into());
+    }
+
+    // oracle markets don't take proposals, the judge settles or voids the ones the oracle couldn't judge
+    if betting_market_data.oracle != NULL_PUBKEY {
+      if !betting_market_data.is_oracle_grace_period_over(Clock::get()?.unix_timestamp) {
+        return Err(BettingMarketError::OracleGracePeriodNotOver.into());
+      }
+      let yes_payout = Self::validate_result(&betting_market_data, result, yes_payout)?;
+      betting_market_data.yes_payout = yes_payout;
+      betting_market_data.result = result as u8;
+      msg!("betting market result: {}", betting_market_data.result);
+      msg!("yes payout: {}", betting_market_data.yes_payout);
+      return Self::pack_betting_market(betting_market_data, betting_market_data_account);
+    }
+
+    if !betting_market_data.has_proposal() {
+      return Err(BettingMarketError::NoResultProposed.into());
+    }
+    if !betting_market_data.is_disputed() {
+      return Err(BettingMarketError::ResultNotDisputed.into());
+    }
+    let yes_payout = Self::validate_result(&betting_market_data, result, yes_payout)?;
+    msg!("yes payout: {}", yes_payout);
+    betting_market_data.yes_payout = yes_payout;
+
+    msg!("old result: {}", betting_market_data.result);
+    betting_market_data.result = result as u8;
+    msg!("new result: {}", betting_market_data.result);
+
+    // the losing bond is slashed to the winner
+    let winner = if betting_market_data.result == betting_market_data.proposed_result
+      && betting_market_data.yes_payout == betting_market_data.proposed_yes_payout
+    {
+      betting_market_data.proposer
+    } else {
+      betting_market_data.disputer
+    };
+    msg!("bond winner: {}", winner);
+    Self::add_payout(
+      std::slice::from_ref(winner_claim_account.ok_or(ProgramError::NotEnoughAccountKeys)?),
+      betting_market_data_account.key,
+      program_id,
+      &winner,
+      ClaimableToken::Usd,
+      betting_market_data
+        .resolution_bond
+        .checked_mul(2)
+        .ok_or(BettingMarketError::AmountOverflow)?,
+    )?;
+    Self::pack_betting_market(betting_market_data, betting_market_data_account)?;
+
+    Ok(())
+  }
+
+  // set the betting market result with from oracle
+  // binary markets compare the oracle price to the strike price, scalar markets settle the yes payout
+  // linearly between the lower and upper bound
+  fn process_judge_betting_market_oracle(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+  ) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let betting_market_data_account = next_account_info(account_info_iter)?;
+    let oracle_account = next_account_info(account_info_iter)?;
+
+    let mut betting_market_data =
+      validation::validate_betting_market_account(betting_market_data_account, program_id)?;
+    // markets created without an oracle can only be judged manually
+    if betting_market_data.oracle == NULL_PUBKEY {
+      return Err(BettingMarketError::InvalidOracleAccount.into());
+    }
+    validation::validate_standalone_market(&betting_market_data)?;
+    if betting_market_data.result != 0 {
+      return Err(BettingMarketError::MarketAlreadyResolved.into());
+    }
+    // only the oracle fixed at creation can judge the market
+    if *oracle_account.key != betting_market_data.oracle {
+      return Err(BettingMarketError::IncorrectOracleAccount.into());
+    }
+    let clock = Clock::get()?;
+    if !betting_market_data.is_resolution_time(clock.unix_timestamp) {
+      return Err(BettingMarketError::ResolutionTimeNotReached.into());
+    }
+
+    // judge on the twap of the recorded samples or on the current aggregate price
+    let (oracle_price, oracle_conf, oracle_expo) = if betting_market_data.twap_window > 0 {
+      if betting_market_data_account.data_len() < BETTING_MARKET_DATA_LEN {
+        return Err(BettingMarketError::AccountTooSmall.into());
+      }
+      let data = betting_market_data_account.try_borrow_data()?;
+      let sample_count = twap::get_sample_count(&data);
+      msg!("oracle samples: {}", sample_count);
+      if sample_count == 0 || sample_count < betting_market_data.min_twap_samples as u64 {
+        return Err(BettingMarketError::TooFewOracleSamples.into());
+      }
+      let (twap_price, twap_conf) =
+        twap::time_weighted_average(&data, betting_market_data.resolution_ts)
+          .ok_or(BettingMarketError::TooFewOracleSamples)?;
+      (twap_price, twap_conf, twap::get_expo(&data))
+    } else {
+      let oracle_price = oracle::get_current_price(
+        betting_market_data.oracle_kind,
+        oracle_account,
+        &clock,
+        program_id,
+      )?;
+      (
+        oracle_price.price as i128,
+        oracle_price.conf as i128,
+        oracle_price.expo,
+      )
+    };
+    msg!("oracle price: {}", oracle_price);
+    msg!("oracle confidence: {}", oracle_conf);
+    msg!("oracle expo: {}", oracle_expo);
+
+    // compare at the finer of the feed's and the strike's exponent so nothing gets rounded away
+    let strike_expo = betting_market_data.strike_expo;
+    let common_expo = std::cmp::min(oracle_expo, strike_expo);
+    let rescale = |value: i128, expo: i32| -> Result<i128, ProgramError> {
+      oracle::rescale(value, expo, common_expo)
+        .ok_or_else(|| BettingMarketError::AmountOverflow.into())
+    };
+    let oracle_price = rescale(oracle_price, oracle_expo)?;
+
+    if betting_market_data.market_type == SCALAR_MARKET_TYPE {
+      let lower_bound = rescale(betting_market_data.lower_bound as i128, strike_expo)?;
+      let upper_bound = rescale(betting_market_data.upper_bound as i128, strike_expo)?;
+      msg!("lower bound: {}, upper bound: {}", lower_bound, upper_bound);
+      let yes_payout = if oracle_price <= lower_bound {
+        0
+      } else if oracle_price >= upper_bound {
+        100
+      } else {
+        (oracle_price - lower_bound)
+          .checked_mul(100)
+          .ok_or(BettingMarketError::AmountOverflow)?
+          / (upper_bound - lower_bound)
+      };
+      betting_market_data.result = 4;
+      betting_market_data.yes_payout = yes_payout as u8;
+    } else {
+      msg!("strike price: {}", betting_market_data.strike_price);
+      let scaled_strike_price = rescale(betting_market_data.strike_price as i128, strike_expo)?;
+      let confidence = rescale(oracle_conf, oracle_expo)?;
+      // refuse to pick a side while the strike is within the confidence band, the judge can retry later
+      if oracle::is_within_confidence(oracle_price, confidence, scaled_strike_price) {
+        return Err(BettingMarketError::StrikeWithinConfidence.into());
+      }
+      if betting_market_data.is_yes_outcome(oracle_price, scaled_strike_price) {
+        betting_market_data.result = 1;
+        betting_market_data.yes_payout = 100;
+      } else {
+        betting_market_data.result = 2;
+        betting_market_data.yes_payout = 0;
+      }
+    }
+    msg!("betting market result: {}", betting_market_data.result);
+    msg!("yes payout: {}", betting_market_data.yes_payout);
+    Self::pack_betting_market(betting_market_data, betting_market_data_account)?;
+
+    Ok(())
+  }
+
+  // record the betting market's oracle price as a twap sample during the twap window
+  fn process_record_oracle_sample(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let betting_market_data_account = next_account_info(account_info_iter)?;
+    let oracle_account = next_account_info(account_info_iter)?;
+
+    let betting_market_data =
+      validation::validate_betting_market_account(betting_market_data_account, program_id)?;
+    if betting_market_data.oracle == NULL_PUBKEY {
+      return Err(BettingMarketError::InvalidOracleAccount.into());
+    }
+    validation::validate_standalone_market(&betting_market_data)?;
+    if betting_market_data.result != 0 {
+      return Err(BettingMarketError::MarketAlreadyResolved.into());
+    }
+    if betting_market_data.twap_window == 0 {
+      return Err(BettingMarketError::NotTwapMarket.into());
+    }
+    if *oracle_account.key != betting_market_data.oracle {
+      return Err(BettingMarketError::IncorrectOracleAccount.into());
+    }
+    if betting_market_data_account.data_len() < BETTING_MARKET_DATA_LEN {
+      return Err(BettingMarketError::AccountTooSmall.into());
+    }
+    let clock = Clock::get()?;
+    let window_start = betting_market_data.resolution_ts - betting_market_data.twap_window;
+    if clock.unix_timestamp < window_start
+      || clock.unix_timestamp >= betting_market_data.resolution_ts
+    {
+      return Err(BettingMarketError::OutsideTwapWindow.into());
+    }
+
+    let mut data = betting_market_data_account.try_borrow_mut_data()?;
+    if let Some(last_sample) = twap::get_last_sample(&data) {
+      if clock.unix_timestamp - last_sample.unix_timestamp
+        < twap::min_sample_spacing(betting_market_data.twap_window)
+      {
+        return Err(BettingMarketError::OracleSampleTooSoon.into());
+      }
+    }
+
+    let oracle_price = oracle::get_current_price(
+      betting_market_data.oracle_kind,
+      oracle_account,
+      &clock,
+      program_id,
+    )?;
+    // every sample has to be at the same exponent to be averaged
+    if twap::get_sample_count(&data) > 0 && twap::get_expo(&data) != oracle_price.expo {
+      return Err(BettingMarketError::InvalidOracleAccount.into());
+    }
+    twap::record_sample(
+      &mut data,
+      &twap::Sample {
+        unix_timestamp: clock.unix_timestamp,
+        price: oracle_price.price,
+        conf: oracle_price.conf,
+      },
+      oracle_price.expo,
+    );
+    msg!(
+      "recorded oracle sample {} at {}",
+      twap::get_sample_count(&data),
+      clock.unix_timestamp
+    );
+    msg!(
+      "price: {}, confidence: {}",
+      oracle_price.price,
+      oracle_price.conf
+    );
+
+    Ok(())
+  }
+
+  // move the resolution bond from the user's usd token account into the betting market's usd token account
+  fn post_resolution_bond<'a>(
+    betting_market_data: &BettingMarket,
+    user_account: &AccountInfo<'a>,
+    user_usd_token_account: &AccountInfo<'a>,
+    pda_usd_token_account: &AccountInfo<'a>,
+    token_program_id: &AccountInfo<'a>,
+  ) -> ProgramResult {
+    validation::validate_token_program(token_program_id)?;
+    if *pda_usd_token_account.key != betting_market_data.usd_token_account {
+      return Err(BettingMarketError::IncorrectUsdTokenAccount.into());
+    }
+    invoke(
+      &spl_token::instruction::transfer(
+        token_program_id.key,
+        user_usd_token_account.key,
+        pda_usd_token_account.key,
+        user_account.key,
+        &[],
+        betting_market_data.resolution_bond,
+      )?,
+      &[
+        user_usd_token_account.clone(),
+        pda_usd_token_account.clone(),
+        user_account.clone(),
+        token_program_id.clone(),
+      ],
+    )?;
+    msg!("posted {} usd bond", betting_market_data.resolution_bond);
+    Ok(())
+  }
+
+  // propose the result of a market without an oracle once trading closed, bonded by the proposer
+  fn process_propose_result(
+    accounts: &[AccountInfo],
+    result: u64,
+    yes_payout: u64,
+    program_id: &Pubkey,
+  ) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let proposer_account = next_account_info(account_info_iter)?;
+    let betting_market_data_account = next_account_info(account_info_iter)?;
+    let proposer_usd_token_account = next_account_info(account_info_iter)?;
+    let pda_usd_token_account = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let proposer_claim_account = next_account_info(account_info_iter)?;
+
+    validation::validate_signer(proposer_account)?;
+    let mut betting_market_data =
+      validation::validate_betting_market_account(betting_market_data_account, program_id)?;
+    validation::validate_standalone_market(&betting_market_data)?;
+    // markets with an oracle are only judged by it
+    if betting_market_data.oracle != NULL_PUBKEY {
+      return Err(BettingMarketError::ProposalNotAllowed.into());
+    }
+    if betting_market_data.result != 0 {
+      return Err(BettingMarketError::MarketAlreadyResolved.into());
+    }
+    if betting_market_data.has_proposal() {
+      return Err(BettingMarketError::ResultAlreadyProposed.into());
+    }
+    let clock = Clock::get()?;
+    if !betting_market_data.is_proposal_time(clock.unix_timestamp) {
+      return Err(BettingMarketError::ResolutionTimeNotReached.into());
+    }
+    let yes_payout = Self::validate_result(&betting_market_data, result, yes_payout)?;
+    // the bond is returned or slashed to the proposer through their claimable balance account
+    claim::find_claim_account(
+      std::slice::from_ref(proposer_claim_account),
+      program_id,
+      betting_market_data_account.key,
+      proposer_account.key,
+    )?;
+
+    Self::post_resolution_bond(
+      &betting_market_data,
+      proposer_account,
+      proposer_usd_token_account,
+      pda_usd_token_account,
+      token_program_id,
+    )?;
+    betting_market_data.proposer = *proposer_account.key;
+    betting_market_data.proposed_result = result as u8;
+    betting_market_data.proposed_yes_payout = yes_payout;
+    betting_market_data.proposal_ts = clock.unix_timestamp;
+    msg!("proposed result: {}", betting_market_data.proposed_result);
+    msg!(
+      "proposed yes payout: {}",
+      betting_market_data.proposed_yes_payout
+    );
+    Self::pack_betting_market(betting_market_data, betting_market_data_account)?;
+
+    Ok(())
+  }
+
+  // dispute the proposed result within the dispute window, bonded by the disputer,
+  // leaving the result to the judge
+  fn process_dispute_result(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let disputer_account = next_account_info(account_info_iter)?;
+    let betting_market_data_account = next_account_info(account_info_iter)?;
+    let disputer_usd_token_account = next_account_info(account_info_iter)?;
+    let pda_usd_token_account = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let disputer_claim_account = next_account_info(account_info_iter)?;
+
+    validation::validate_signer(disputer_account)?;
+    let mut betting_market_data =
+      validation::validate_betting_market_account(betting_market_data_account, program_id)?;
+    validation::validate_standalone_market(&betting_market_data)?;
+    if betting_market_data.result != 0 {
+      return Err(BettingMarketError::MarketAlreadyResolved.into());
+    }
+    if !betting_market_data.has_proposal() {
+      return Err(BettingMarketError::NoResultProposed.into());
+    }
+    if betting_market_data.is_disputed() {
+      return Err(BettingMarketError::ResultAlreadyDisputed.into());
+    }
+    if !betting_market_data.is_dispute_window_open(Clock::get()?.unix_timestamp) {
+      return Err(BettingMarketError::DisputeWindowClosed.into());
+    }
+    claim::find_claim_account(
+      std::slice::from_ref(disputer_claim_account),
+      program_id,
+      betting_market_data_account.key,
+      disputer_account.key,
+    )?;
+
+    Self::post_resolution_bond(
+      &betting_market_data,
+      disputer_account,
+      disputer_usd_token_account,
+      pda_usd_token_account,
+      token_program_id,
+    )?;
+    betting_market_data.disputer = *disputer_account.key;
+    msg!("disputed by: {}", betting_market_data.disputer);
+    Self::pack_betting_market(betting_market_data, betting_market_data_account)?;
+
+    Ok(())
+  }
+
+  // set the proposed result once the dispute window passed undisputed and return the proposer's bond
+  fn process_finalize_result(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let betting_market_data_account = next_account_info(account_info_iter)?;
+    let proposer_claim_account = next_account_info(account_info_iter)?;
+
+    let mut betting_market_data =
+      validation::validate_betting_market_account(betting_market_data_account, program_id)?;
+    validation::validate_standalone_market(&betting_market_data)?;
+    if betting_market_data.result != 0 {
+      return Err(BettingMarketError::MarketAlreadyResolved.into());
+    }
+    if !betting_market_data.has_proposal() {
+      return Err(BettingMarketError::NoResultProposed.into());
+    }
+    if betting_market_data.is_disputed() {
+      return Err(BettingMarketError::ResultAlreadyDisputed.into());
+    }
+    if betting_market_data.is_dispute_window_open(Clock::get()?.unix_timestamp) {
+      return Err(BettingMarketError::DisputeWindowOpen.into());
+    }
+
+    betting_market_data.result = betting_market_data.proposed_result;
+    betting_market_data.yes_payout = betting_market_data.proposed_yes_payout;
+    msg!("betting market result: {}", betting_market_data.result);
+    msg!("yes payout: {}", betting_market_data.yes_payout);
+    Self::add_payout(
+      std::slice::from_ref(proposer_claim_account),
+      betting_market_data_account.key,
+      program_id,
+      &betting_market_data.proposer,
+      ClaimableToken::Usd,
+      betting_market_data.resolution_bond,
+    )?;
+    Self::pack_betting_market(betting_market_data, betting_market_data_account)?;
+
+    Ok(())
+  }
+
+  // set the betting market strike price
+  fn process_set_strike_price(
+    accounts: &[AccountInfo],
+    strike_price: u64,
+    program_id: &Pubkey,
+  ) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let betting_market_data_account = next_account_info(account_info_iter)?;
+    let judge_account = next_account_info(account_info_iter)?;
+
+    let mut betting_market_data =
+      validation::validate_betting_market_account(betting_market_data_account, program_id)?;
+    validation::validate_judge(&betting_market_data, judge_account)?;
+    validation::validate_standalone_market(&betting_market_data)?;
+    if betting_market_data.result != 0 {
+      return Err(BettingMarketError::MarketAlreadyResolved.into());
+    }
+    if betting_market_data.has_traded {
+      return Err(BettingMarketError::StrikePriceLocked.into());
+    }
+
+    msg!("old strike price: {}", betting_market_data.strike_price);
+    betting_market_data.strike_price = strike_price;
+    msg!("new strike price: {}", betting_market_data.strike_price);
+    Self::pack_betting_market(betting_market_data, betting_market_data_account)?;
+
+    Ok(())
+  }
+
+  // hand the judge role of the betting market over to a new account
+  fn process_set_judge(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let betting_market_data_account = next_account_info(account_info_iter)?;
+    let judge_account = next_account_info(account_info_iter)?;
+    let new_judge_account = next_account_info(account_info_iter)?;
+
+    let mut betting_market_data =
+      validation::validate_betting_market_account(betting_market_data_account, program_id)?;
+    validation::validate_judge(&betting_market_data, judge_account)?;
+
+    msg!("old judge: {}", betting_market_data.judge);
+    betting_market_data.judge = *new_judge_account.key;
+    msg!("new judge: {}", betting_market_data.judge);
+    Self::pack_betting_market(betting_market_data, betting_market_data_account)?;
+
+    Ok(())
+  }
+
+  // transfer everything collected in the fee vault to the fee recipient, signed by the fee authority
+  fn process_withdraw_fees(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let fee_authority_account = next_account_info(account_info_iter)?;
+    let pda_account = next_account_info(account_info_iter)?;
+    let betting_market_data_account = next_account_info(account_info_iter)?;
+    let fee_vault = next_account_info(account_info_iter)?;
+    let fee_recipient_account = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+
+    let betting_market_data =
+      validation::validate_betting_market_account(betting_market_data_account, program_id)?;
+    validation::validate_signer(fee_authority_account)?;
+    if *fee_authority_account.key != betting_market_data.fee_authority {
+      return Err(BettingMarketError::InvalidFeeAuthority.into());
+    }
+    let bump_seed = validation::validate_market_pda(
+      betting_market_data_account,
+      &betting_market_data,
+      pda_account,
+      program_id,
+    )?;
+    validation::validate_fee_vault(&betting_market_data, fee_vault)?;
+    if *fee_recipient_account.key != betting_market_data.fee_recipient {
+      return Err(BettingMarketError::IncorrectFeeRecipient.into());
+    }
+    validation::validate_token_program(token_program_id)?;
+
+    let fee_amount = TokenAccount::unpack(&fee_vault.data.borrow())?.amount;
+    msg!("withdrawing {} usd of fees", fee_amount);
+    if fee_amount == 0 {
+      return Ok(());
+    }
+    invoke_signed(
+      &spl_token::instruction::transfer(
+        token_program_id.key,
+        fee_vault.key,
+        fee_recipient_account.key,
+        pda_account.key,
+        &[],
+        fee_amount,
+      )?,
+      &[
+        fee_vault.clone(),
+        fee_recipient_account.clone(),
+        pda_account.clone(),
+        token_program_id.clone(),
+      ],
+      &[&[
+        &b"betting"[..],
+        betting_market_data_account.key.as_ref(),
+        &[bump_seed],
+      ]],
+    )?;
+
+    Ok(())
+  }
+
+  // create the user's claimable balance pda for a betting market, paid for by the user
+  fn process_create_claim_account(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let user_account = next_account_info(account_info_iter)?;
+    let claim_account = next_account_info(account_info_iter)?;
+    let betting_market_data_account = next_account_info(account_info_iter)?;
+    let system_program_account = next_account_info(account_info_iter)?;
+    let rent_account = next_account_info(account_info_iter)?;
+    let rent = &Rent::from_account_info(rent_account)?;
+
+    validation::validate_signer(user_account)?;
+    validation::validate_betting_market_account(betting_market_data_account, program_id)?;
+    Self::create_claim_account(
+      user_account,
+      claim_account,
+      betting_market_data_account.key,
+      user_account.key,
+      system_program_account,
+      rent,
+      program_id,
+    )
+  }
+
+  // create the claim account pda of the user for the betting market, paid for by the payer
+  // anyone can send lamports to the pda address before it exists, which create_account refuses,
+  // so a funded address is topped up to rent exemption and allocated and assigned instead
+  fn create_claim_account<'a>(
+    payer_account: &AccountInfo<'a>,
+    claim_account: &AccountInfo<'a>,
+    betting_market: &Pubkey,
+    user: &Pubkey,
+    system_program_account: &AccountInfo<'a>,
+    rent: &Rent,
+    program_id: &Pubkey,
+  ) -> ProgramResult {
+    let (claim_pda, bump_seed) =
+      Pubkey::find_program_address(&[betting_market.as_ref(), user.as_ref()], program_id);
+    if *claim_account.key != claim_pda {
+      return Err(BettingMarketError::InvalidClaimAccount.into());
+    }
+    if *claim_account.owner == *program_id {
+      return Err(ProgramError::AccountAlreadyInitialized);
+    }
+    let signer_seeds: &[&[u8]] = &[betting_market.as_ref(), user.as_ref(), &[bump_seed]];
+    let required_lamports = rent.minimum_balance(claim::CLAIM_ACCOUNT_LEN);
+
+    msg!("Creating claim account {}", claim_account.key);
+    if claim_account.lamports() == 0 {
+      invoke_signed(
+        &system_instruction::create_account(
+          payer_account.key,
+          claim_account.key,
+          required_lamports,
+          claim::CLAIM_ACCOUNT_LEN as u64,
+          program_id,
+        ),
+        &[
+          payer_account.clone(),
+          claim_account.clone(),
+          system_program_account.clone(),
+        ],
+        &[signer_seeds],
+      )?;
+    } else {
+      let top_up = required_lamports.saturating_sub(claim_account.lamports());
+      if top_up > 0 {
+        invoke(
+          &system_instruction::transfer(payer_account.key, claim_account.key, top_up),
+          &[
+            payer_account.clone(),
+            claim_account.clone(),
+            system_program_account.clone(),
+          ],
+        )?;
+      }
+      invoke_signed(
+        &system_instruction::allocate(claim_account.key, claim::CLAIM_ACCOUNT_LEN as u64),
+        &[claim_account.clone(), system_program_account.clone()],
+        &[signer_seeds],
+      )?;
+      invoke_signed(
+        &system_instruction::assign(claim_account.key, program_id),
+        &[claim_account.clone(), system_program_account.clone()],
+        &[signer_seeds],
+      )?;
+    }
+    claim::init_claim_account(
+      &mut claim_account.try_borrow_mut_data()?,
+      betting_market,
+      user,
+      bump_seed,
+    );
+
+    Ok(())
+  }
+
+  // remove all of the user's resting orders from a judged betting market and credit the refund
+  // usd collateral is refunded at the limit price it was posted at, burned inverse tokens are refunded
+  // at the value they would have redeemed for
+  fn process_reclaim_open_orders(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let user_account = next_account_info(account_info_iter)?;
+    let betting_market_data_account = next_account_info(account_info_iter)?;
+    let order_book_account = next_account_info(account_info_iter)?;
+    let claim_account = next_account_info(account_info_iter)?;
+
+    validation::validate_signer(user_account)?;
+    let betting_market_data =
+      validation::validate_betting_market_account(betting_market_data_account, program_id)?;
+    let (yes_redemption_price, no_redemption_price) = betting_market_data
+      .redemption_prices()
+      .ok_or(BettingMarketError::MarketNotResolved)?;
+    let mut order_book_data = validation::validate_order_book_account(
+      betting_market_data_account,
+      &betting_market_data,
+      order_book_account,
+      program_id,
+    )?;
+    let claim_account = claim::find_claim_account(
+      std::slice::from_ref(claim_account),
+      program_id,
+      betting_market_data_account.key,
+      user_account.key,
+    )?;
+
+    let mut usd_refund: u64 = 0;
+    for is_yes in [true, false] {
+      let inverse_redemption_price = if is_yes {
+        no_redemption_price
+      } else {
+        yes_redemption_price
+      };
+      for price in 1..100 {
+        let (usd_collateralized_amount, inverse_collateralized_amount) =
+          orderbook::remove_user_orders(&mut order_book_data, is_yes, price, user_account.key)?;
+        if usd_collateralized_amount == 0 && inverse_collateralized_amount == 0 {
+          continue;
+        }
+        msg!(
+          "reclaimed {} usd collateralized and {} inverse collateralized {} orders at price {}",
+          usd_collateralized_amount,
+          inverse_collateralized_amount,
+          if is_yes { "yes" } else { "no" },
+          price,
+        );
+        let inverse_refund = inverse_collateralized_amount
+          .checked_mul(inverse_redemption_price)
+          .ok_or(BettingMarketError::AmountOverflow)?;
+        let usd_collateral_refund = price
+          .checked_mul(usd_collateralized_amount)
+          .and_then(|usd_amount| {
+            BettingMarket::fee_for(usd_amount, betting_market_data.maker_fee_bps)
+              .and_then(|maker_fee| usd_amount.checked_add(maker_fee))
+          })
+          .ok_or(BettingMarketError::AmountOverflow)?;
+        usd_refund = usd_collateral_refund
+          .checked_add(inverse_refund)
+          .and_then(|refund| refund.checked_add(usd_refund))
+          .ok_or(BettingMarketError::AmountOverflow)?;
+      }
+    }
+
+    msg!("crediting {} usd refund", usd_refund);
+    claim::credit(
+      &mut claim_account.try_borrow_mut_data()?,
+      ClaimableToken::Usd,
+      usd_refund,
+    )?;
+
+    Ok(())
+  }
+
+  // initialize a categorical market and its complete set usd token account, then link every outcome market
+  fn process_init_categorical_market(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+  ) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let initializer_account_info = next_account_info(account_info_iter)?;
+    let pda_account_info = next_account_info(account_info_iter)?;
+    let categorical_market_account_info = next_account_info(account_info_iter)?;
+    let token_program_id_account_info = next_account_info(account_info_iter)?;
+    let usd_token_mint_account_info = next_account_info(account_info_iter)?;
+    let usd_token_account_info = next_account_info(account_info_iter)?;
+    let judge_account_info = next_account_info(account_info_iter)?;
+    let system_program_account_info = next_account_info(account_info_iter)?;
+    let rent_account_info = next_account_info(account_info_iter)?;
+    let outcome_account_infos = account_info_iter.as_slice();
+    let rent = &Rent::from_account_info(rent_account_info)?;
+    let (pda, bump_seed) = Pubkey::find_program_address(
+      &[b"betting", categorical_market_account_info.key.as_ref()],
+      program_id,
+    );
+
+    if *pda_account_info.key != pda {
+      return Err(BettingMarketError::InvalidPda.into());
+    }
+    validation::validate_token_program(token_program_id_account_info)?;
+    validation::validate_signer(initializer_account_info)?;
+    if *categorical_market_account_info.owner != *program_id {
+      return Err(BettingMarketError::InvalidCategoricalMarket.into());
+    }
+    let num_outcomes = outcome_account_infos.len() / 3;
+    if outcome_account_infos.len() != num_outcomes * 3
+      || !(categorical::MIN_OUTCOMES..=categorical::MAX_OUTCOMES).contains(&num_outcomes)
+    {
+      return Err(BettingMarketError::InvalidOutcomeCount.into());
+    }
+    Self::zero_uninitialized_account_data(
+      categorical_market_account_info,
+      categorical::CATEGORICAL_MARKET_LEN,
+    )?;
+
+    // create usd token account and initialize token account with pda as owner
+    msg!("Creating usd token account and initializing token account with pda as owner");
+    invoke(
+      &system_instruction::create_account(
+        initializer_account_info.key,
+        usd_token_account_info.key,
+        rent.minimum_balance(TokenAccount::LEN),
+        TokenAccount::LEN as u64,
+        token_program_id_account_info.key,
+      ),
+      &[
+        initializer_account_info.clone(),
+        usd_token_account_info.clone(),
+        system_program_account_info.clone(),
+      ],
+    )?;
+    invoke(
+      &spl_token::instruction::initialize_account(
+        token_program_id_account_info.key,
+        usd_token_account_info.key,
+        usd_token_mint_account_info.key,
+        pda_account_info.key,
+      )?,
+      &[
+        usd_token_account_info.clone(),
+        usd_token_mint_account_info.clone(),
+        pda_account_info.clone(),
+        rent_account_info.clone(),
+        token_program_id_account_info.clone(),
+      ],
+    )?;
+
+    msg!(
+      "Initializing categorical market with {} outcomes",
+      num_outcomes
+    );
+    let mut categorical_market_data = categorical_market_account_info.try_borrow_mut_data()?;
+    categorical::init_categorical_market(
+      &mut categorical_market_data,
+      num_outcomes,
+      judge_account_info.key,
+      usd_token_account_info.key,
+      bump_seed,
+    );
+
+    // link every outcome market, which has to be an open standalone binary market settled in the same usd
+    // and share neither its data account, order book nor mints with another outcome
+    let mut linked_accounts: Vec<Pubkey> = Vec::with_capacity(num_outcomes * 4);
+    for (index, outcome_account_infos) in outcome_account_infos.chunks(3).enumerate() {
+      let outcome_market_account_info = &outcome_account_infos[0];
+      let outcome_judge_account_info = &outcome_account_infos[1];
+      let outcome_usd_token_account_info = &outcome_account_infos[2];
+      let mut outcome_market_data =
+        validation::validate_betting_market_account(outcome_market_account_info, program_id)?;
+      for linked_account in [
+        *outcome_market_account_info.key,
+        outcome_market_data.order_book,
+        outcome_market_data.yes_token_mint,
+        outcome_market_data.no_token_mint,
+      ] {
+        if linked_accounts.contains(&linked_account) {
+          return Err(BettingMarketError::DuplicateOutcomeMarket.into());
+        }
+        linked_accounts.push(linked_account);
+      }
+      validation::validate_judge(&outcome_market_data, outcome_judge_account_info)?;
+      validation::validate_standalone_market(&outcome_market_data)?;
+      if outcome_market_data.result != 0 {
+        return Err(BettingMarketError::MarketAlreadyResolved.into());
+      }
+      if outcome_market_data.market_type != BINARY_MARKET_TYPE {
+        return Err(BettingMarketError::InvalidCategoricalMarket.into());
+      }
+      if *outcome_usd_token_account_info.key != outcome_market_data.usd_token_account {
+        return Err(BettingMarketError::IncorrectUsdTokenAccount.into());
+      }
+      let outcome_usd_token_account_data =
+        TokenAccount::unpack(&outcome_usd_token_account_info.data.borrow())?;
+      if outcome_usd_token_account_data.mint != *usd_token_mint_account_info.key {
+        return Err(BettingMarketError::IncorrectUsdTokenMint.into());
+      }
+
+      msg!(
+        "outcome {}: betting market {}",
+        index,
+        outcome_market_account_info.key
+      );
+      categorical::set_outcome(
+        &mut categorical_market_data,
+        index,
+        &categorical::Outcome {
+          market: *outcome_market_account_info.key,
+          yes_token_mint: outcome_market_data.yes_token_mint,
+          bump_seed: outcome_market_data.bump_seed,
+        },
+      );
+      outcome_market_data.categorical_market = *categorical_market_account_info.key;
+      Self::pack_betting_market(outcome_market_data, outcome_market_account_info)?;
+    }
+
+    Ok(())
+  }
+
+  // take 100 usd per complete set into the categorical market and mint a yes token of every outcome
+  fn process_split_categorical_set(
+    accounts: &[AccountInfo],
+    amount: u64,
+    program_id: &Pubkey,
+  ) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let user_account = next_account_info(account_info_iter)?;
+    let categorical_market_account = next_account_info(account_info_iter)?;
+    let categorical_usd_token_account = next_account_info(account_info_iter)?;
+    let user_usd_token_account = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let outcome_accounts = account_info_iter.as_slice();
+
+    validation::validate_signer(user_account)?;
+    validation::validate_token_program(token_program_id)?;
+    let categorical_market_data =
+      validation::validate_categorical_market_account(categorical_market_account, program_id)?;
+    if categorical::get_result(&categorical_market_data) != 0 {
+      return Err(BettingMarketError::MarketAlreadyResolved.into());
+    }
+    validation::validate_categorical_usd_token_account(
+      &categorical_market_data,
+      categorical_usd_token_account,
+    )?;
+    let num_outcomes = categorical::get_num_outcomes(&categorical_market_data);
+    if outcome_accounts.len() != num_outcomes * 3 {
+      return Err(BettingMarketError::InvalidOutcomeCount.into());
+    }
+
+    let usd_amount = amount
+      .checked_mul(100)
+      .ok_or(BettingMarketError::AmountOverflow)?;
+    invoke(
+      &spl_token::instruction::transfer(
+        token_program_id.key,
+        user_usd_token_account.key,
+        categorical_usd_token_account.key,
+        user_account.key,
+        &[],
+        usd_amount,
+      )?,
+      &[
+        user_usd_token_account.clone(),
+        categorical_usd_token_account.clone(),
+        user_account.clone(),
+        token_program_id.clone(),
+      ],
+    )?;
+    msg!("transferred {} usd", usd_amount);
+
+    for (index, outcome_accounts) in outcome_accounts.chunks(3).enumerate() {
+      let outcome_pda_account = &outcome_accounts[0];
+      let outcome_yes_token_mint = &outcome_accounts[1];
+      let user_outcome_yes_token_account = &outcome_accounts[2];
+      let outcome = categorical::get_outcome(&categorical_market_data, index);
+      validation::validate_pda_with_bump_seed(
+        &outcome.market,
+        outcome.bump_seed,
+        outcome_pda_account,
+        program_id,
+      )?;
+      if *outcome_yes_token_mint.key != outcome.yes_token_mint {
+        return Err(BettingMarketError::IncorrectYesTokenMint.into());
+      }
+      invoke_signed(
+        &spl_token::instruction::mint_to(
+          token_program_id.key,
+          outcome_yes_token_mint.key,
+          user_outcome_yes_token_account.key,
+          outcome_pda_account.key,
+          &[],
+          amount,
+        )?,
+        &[
+          outcome_yes_token_mint.clone(),
+          user_outcome_yes_token_account.clone(),
+          outcome_pda_account.clone(),
+          token_program_id.clone(),
+        ],
+        &[&[
+          &b"betting"[..],
+          outcome.market.as_ref(),
+          &[outcome.bump_seed],
+        ]],
+      )?;
+      msg!("minted {} yes tokens of outcome {}", amount, index);
+    }
+
+    Ok(())
+  }
+
+  // burn a yes token of every outcome per complete set and refund 100 usd from the categorical market
+  fn process_merge_categorical_set(
+    accounts: &[AccountInfo],
+    amount: u64,
+    program_id: &Pubkey,
+  ) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let user_account = next_account_info(account_info_iter)?;
+    let pda_account = next_account_info(account_info_iter)?;
+    let categorical_market_account = next_account_info(account_info_iter)?;
+    let categorical_usd_token_account = next_account_info(account_info_iter)?;
+    let user_usd_token_account = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let outcome_accounts = account_info_iter.as_slice();
+
+    validation::validate_signer(user_account)?;
+    validation::validate_token_program(token_program_id)?;
+    let categorical_market_data =
+      validation::validate_categorical_market_account(categorical_market_account, program_id)?;
+    if categorical::get_result(&categorical_market_data) != 0 {
+      return Err(BettingMarketError::MarketAlreadyResolved.into());
+    }
+    let bump_seed = categorical::get_bump_seed(&categorical_market_data);
+    validation::validate_pda_with_bump_seed(
+      categorical_market_account.key,
+      bump_seed,
+      pda_account,
+      program_id,
+    )?;
+    validation::validate_categorical_usd_token_account(
+      &categorical_market_data,
+      categorical_usd_token_account,
+    )?;
+    let num_outcomes = categorical::get_num_outcomes(&categorical_market_data);
+    if outcome_accounts.len() != num_outcomes * 2 {
+      return Err(BettingMarketError::InvalidOutcomeCount.into());
+    }
+    let usd_amount = amount
+      .checked_mul(100)
+      .ok_or(BettingMarketError::AmountOverflow)?;
+
+    for (index, outcome_accounts) in outcome_accounts.chunks(2).enumerate() {
+      let outcome_yes_token_mint = &outcome_accounts[0];
+      let user_outcome_yes_token_account = &outcome_accounts[1];
+      let outcome = categorical::get_outcome(&categorical_market_data, index);
+      if *outcome_yes_token_mint.key != outcome.yes_token_mint {
+        return Err(BettingMarketError::IncorrectYesTokenMint.into());
+      }
+      invoke(
+        &spl_token::instruction::burn(
+          token_program_id.key,
+          user_outcome_yes_token_account.key,
+          outcome_yes_token_mint.key,
+          user_account.key,
+          &[],
+          amount,
+        )?,
+        &[
+          user_outcome_yes_token_account.clone(),
+          outcome_yes_token_mint.clone(),
+          user_account.clone(),
+          token_program_id.clone(),
+        ],
+      )?;
+      msg!("burned {} yes tokens of outcome {}", amount, index);
+    }
+
+    invoke_signed(
+      &spl_token::instruction::transfer(
+        token_program_id.key,
+        categorical_usd_token_account.key,
+        user_usd_token_account.key,
+        pda_account.key,
+        &[],
+        usd_amount,
+      )?,
+      &[
+        categorical_usd_token_account.clone(),
+        user_usd_token_account.clone(),
+        pda_account.clone(),
+        token_program_id.clone(),
+      ],
+      &[&[
+        &b"betting"[..],
+        categorical_market_account.key.as_ref(),
+        &[bump_seed],
+      ]],
+    )?;
+    msg!("refunded {} usd", usd_amount);
+
+    Ok(())
+  }
+
+  // judge every outcome market, yes for the winning outcome and no for the rest, and move the complete set
+  // collateral to the winning outcome market where its yes tokens are redeemed
+  fn process_judge_categorical_market(
+    accounts: &[AccountInfo],
+    winning_outcome: u64,
+    program_id: &Pubkey,
+  ) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let categorical_market_account = next_account_info(account_info_iter)?;
+    let judge_account = next_account_info(account_info_iter)?;
+    let pda_account = next_account_info(account_info_iter)?;
+    let categorical_usd_token_account = next_account_info(account_info_iter)?;
+    let winning_usd_token_account = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let outcome_market_accounts = account_info_iter.as_slice();
+
+    validation::validate_token_program(token_program_id)?;
+    let mut categorical_market_data =
+      validation::validate_categorical_market_account(categorical_market_account, program_id)?;
+    validation::validate_signer(judge_account)?;
+    if *judge_account.key != categorical::get_judge(&categorical_market_data) {
+      return Err(BettingMarketError::InvalidJudge.into());
+    }
+    if categorical::get_result(&categorical_market_data) != 0 {
+      return Err(BettingMarketError::MarketAlreadyResolved.into());
+    }
+    let bump_seed = categorical::get_bump_seed(&categorical_market_data);
+    validation::validate_pda_with_bump_seed(
+      categorical_market_account.key,
+      bump_seed,
+      pda_account,
+      program_id,
+    )?;
+    validation::validate_categorical_usd_token_account(
+      &categorical_market_data,
+      categorical_usd_token_account,
+    )?;
+    let num_outcomes = categorical::get_num_outcomes(&categorical_market_data);
+    if outcome_market_accounts.len() != num_outcomes {
+      return Err(BettingMarketError::InvalidOutcomeCount.into());
+    }
+    if winning_outcome >= num_outcomes as u64 {
+      return Err(BettingMarketError::InvalidOutcomeIndex.into());
+    }
+    let winning_outcome = winning_outcome as usize;
+
+    for (index, outcome_market_account) in outcome_market_accounts.iter().enumerate() {
+      if *outcome_market_account.key
+        != categorical::get_outcome(&categorical_market_data, index).market
+      {
+        return Err(BettingMarketError::InvalidCategoricalMarket.into());
+      }
+      let mut outcome_market_data =
+        validation::validate_betting_market_account(outcome_market_account, program_id)?;
+      if index == winning_outcome {
+        if *winning_usd_token_account.key != outcome_market_data.usd_token_account {
+          return Err(BettingMarketError::IncorrectUsdTokenAccount.into());
+        }
+        outcome_market_data.result = 1;
+        outcome_market_data.yes_payout = 100;
+      } else {
+        outcome_market_data.result = 2;
+        outcome_market_data.yes_payout = 0;
+      }
+      msg!(
+        "outcome {}: betting market result {}",
+        index,
+        outcome_market_data.result
+      );
+      Self::pack_betting_market(outcome_market_data, outcome_market_account)?;
+    }
+
+    // complete set collateral pays out the winning yes tokens that were minted by splitting
+    let categorical_usd_token_account_data =
+      TokenAccount::unpack(&categorical_usd_token_account.data.borrow())?;
+    let usd_amount = categorical_usd_token_account_data.amount;
+    invoke_signed(
+      &spl_token::instruction::transfer(
+        token_program_id.key,
+        categorical_usd_token_account.key,
+        winning_usd_token_account.key,
+        pda_account.key,
+        &[],
+        usd_amount,
+      )?,
+      &[
+        categorical_usd_token_account.clone(),
+        winning_usd_token_account.clone(),
+        pda_account.clone(),
+        token_program_id.clone(),
+      ],
+      &[&[
+        &b"betting"[..],
+        categorical_market_account.key.as_ref(),
+        &[bump_seed],
+      ]],
+    )?;
+    msg!(
+      "moved {} usd of complete set collateral to the winning outcome",
+      usd_amount
+    );
+
+    categorical::set_result(&mut categorical_market_data, winning_outcome as u8 + 1);
+
+    Ok(())
+  }
+
+  // deposit 100 usd per complete set into the betting market and mint a yes and a no token for each
+  fn process_split_complete_set(
+    accounts: &[AccountInfo],
+    amount: u64,
+    program_id: &Pubkey,
+  ) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let user_account = next_account_info(account_info_iter)?;
+    let pda_account = next_account_info(account_info_iter)?;
+    let betting_market_data_account = next_account_info(account_info_iter)?;
+    let usd_token_mint = next_account_info(account_info_iter)?;
+    let yes_token_mint = next_account_info(account_info_iter)?;
+    let no_token_mint = next_account_info(account_info_iter)?;
+    let user_usd_token_account = next_account_info(account_info_iter)?;
+    let user_yes_token_account = next_account_info(account_info_iter)?;
+    let user_no_token_account = next_account_info(account_info_iter)?;
+    let pda_usd_token_account = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+
+    let (betting_market_data, bump_seed) = validation::validate_trade_accounts(
+      program_id,
+      user_account,
+      pda_account,
+      betting_market_data_account,
+      usd_token_mint,
+      yes_token_mint,
+      no_token_mint,
+      user_usd_token_account,
+      user_yes_token_account,
+      user_no_token_account,
+      pda_usd_token_account,
+      token_program_id,
+    )?;
+    if betting_market_data.result != 0 {
+      return Err(BettingMarketError::MarketAlreadyResolved.into());
+    }
+
+    let usd_amount = amount
+      .checked_mul(100)
+      .ok_or(BettingMarketError::AmountOverflow)?;
+    invoke(
+      &spl_token::instruction::transfer(
+        token_program_id.key,
+        user_usd_token_account.key,
+        pda_usd_token_account.key,
+        user_account.key,
+        &[],
+        usd_amount,
+      )?,
+      &[
+        user_usd_token_account.clone(),
+        pda_usd_token_account.clone(),
+        user_account.clone(),
+        token_program_id.clone(),
+      ],
+    )?;
+    msg!("transferred {} usd", usd_amount);
+
+    for (token_mint, user_token_account) in [
+      (yes_token_mint, user_yes_token_account),
+      (no_token_mint, user_no_token_account),
+    ] {
+      invoke_signed(
+        &spl_token::instruction::mint_to(
+          token_program_id.key,
+          token_mint.key,
+          user_token_account.key,
+          pda_account.key,
+          &[],
+          amount,
+        )?,
+        &[
+          token_mint.clone(),
+          user_token_account.clone(),
+          pda_account.clone(),
+          token_program_id.clone(),
+        ],
+        &[&[
+          &b"betting"[..],
+          betting_market_data_account.key.as_ref(),
+          &[bump_seed],
+        ]],
+      )?;
+    }
+    msg!("minted {} yes and no tokens", amount);
+
+    Ok(())
+  }
+
+  // burn a yes and a no token per complete set and return 100 usd for each
+  // a complete set redeems for 100 whatever the result, so this also works after resolution
+  fn process_merge_complete_set(
+    accounts: &[AccountInfo],
+    amount: u64,
+    program_id: &Pubkey,
+  ) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let user_account = next_account_info(account_info_iter)?;
+    let pda_account = next_account_info(account_info_iter)?;
+    let betting_market_data_account = next_account_info(account_info_iter)?;
+    let usd_token_mint = next_account_info(account_info_iter)?;
+    let yes_token_mint = next_account_info(account_info_iter)?;
+    let no_token_mint = next_account_info(account_info_iter)?;
+    let user_usd_token_account = next_account_info(account_info_iter)?;
+    let user_yes_token_account = next_account_info(account_info_iter)?;
+    let user_no_token_account = next_account_info(account_info_iter)?;
+    let pda_usd_token_account = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+
+    let (_betting_market_data, bump_seed) = validation::validate_trade_accounts(
+      program_id,
+      user_account,
+      pda_account,
+      betting_market_data_account,
+      usd_token_mint,
+      yes_token_mint,
+      no_token_mint,
+      user_usd_token_account,
+      user_yes_token_account,
+      user_no_token_account,
+      pda_usd_token_account,
+      token_program_id,
+    )?;
+
+    for (token_mint, user_token_account) in [
+      (yes_token_mint, user_yes_token_account),
+      (no_token_mint, user_no_token_account),
+    ] {
+      invoke(
+        &spl_token::instruction::burn(
+          token_program_id.key,
+          user_token_account.key,
+          token_mint.key,
+          user_account.key,
+          &[],
+          amount,
+        )?,
+        &[
+          user_token_account.clone(),
+          token_mint.clone(),
+          user_account.clone(),
+          token_program_id.clone(),
+        ],
+      )?;
+    }
+    msg!("burned {} yes and no tokens", amount);
+
+    let usd_amount = amount
+      .checked_mul(100)
+      .ok_or(BettingMarketError::AmountOverflow)?;
+    invoke_signed(
+      &spl_token::instruction::transfer(
+        token_program_id.key,
+        pda_usd_token_account.key,
+        user_usd_token_account.key,
+        pda_account.key,
+        &[],
+        usd_amount,
+      )?,
+      &[
+        pda_usd_token_account.clone(),
+        user_usd_token_account.clone(),
+        pda_account.clone(),
+        token_program_id.clone(),
+      ],
+      &[&[
+        &b"betting"[..],
+        betting_market_data_account.key.as_ref(),
+        &[bump_seed],
+      ]],
+    )?;
+    msg!("transferred {} usd", usd_amount);
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    categorical,
+    oracle_adapter::{MOCK_ORACLE_LEN, MOCK_ORACLE_MAGIC},
+    orderbook::{NODE_LEN, ORDER_BOOK_NODES_OFFSET},
+    state::{DEFAULT_DISPUTE_WINDOW_SECS, MOCK_ORACLE_KIND, ORACLE_GRACE_PERIOD_SECS},
+  };
+  use solana_program::{
+    entrypoint::SUCCESS, instruction::Instruction, program_option::COption, program_stubs,
+    system_program, sysvar,
+  };
+  use spl_token::state::AccountState;
+  use std::{cell::Cell, convert::TryInto, sync::Once};
+
+  const NOW: i64 = 1_000_000;
+  const PROGRAM_ID: Pubkey = Pubkey::new_from_array([7; 32]);
+
+  thread_local! {
+    static UNIX_TIMESTAMP: Cell<i64> = const { Cell::new(NOW) };
+  }
+
+  // serves the clock sysvar to Clock::get at the unix timestamp of the test's thread and runs cross program
+  // invocations of the token program, and the lamport moves of the system program, outside of the runtime
+  // the stubs are global, so they are set once and every test's program id is PROGRAM_ID
+  struct TestStubs;
+
+  impl program_stubs::SyscallStubs for TestStubs {
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+      let clock = Clock {
+        unix_timestamp: UNIX_TIMESTAMP.with(Cell::get),
+        ..Clock::default()
+      };
+      unsafe { *(var_addr as *mut Clock) = clock };
+      SUCCESS
+    }
+
+    fn sol_invoke_signed(
+      &self,
+      instruction: &Instruction,
+      account_infos: &[AccountInfo],
+      signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+      let signers = signers_seeds
+        .iter()
+        .map(|seeds| Pubkey::create_program_address(seeds, &PROGRAM_ID))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| ProgramError::InvalidSeeds)?;
+      let accounts = instruction
+        .accounts
+        .iter()
+        .map(|account_meta| {
+          let mut account_info = account_infos
+            .iter()
+            .find(|account_info| *account_info.key == account_meta.pubkey)
+            .ok_or(ProgramError::NotEnoughAccountKeys)?
+            .clone();
+          account_info.is_signer |= signers.contains(account_info.key);
+          Ok(account_info)
+        })
+        .collect::<Result<Vec<_>, ProgramError>>()?;
+      if instruction.program_id == spl_token::id() {
+        return spl_token::processor::Processor::process(
+          &instruction.program_id,
+          &accounts,
+          &instruction.data,
+        );
+      }
+      if instruction.program_id != system_program::id() {
+        return Err(ProgramError::IncorrectProgramId);
+      }
+      // create account and transfer move lamports, allocate and assign can't change accounts outside of the
+      // runtime, so tests create accounts with their final owner and size
+      let tag = u32::from_le_bytes(instruction.data[..4].try_into().unwrap());
+      if tag == 0 || tag == 2 {
+        let lamports = u64::from_le_bytes(instruction.data[4..12].try_into().unwrap());
+        **accounts[0].try_borrow_mut_lamports()? -= lamports;
+        **accounts[1].try_borrow_mut_lamports()? += lamports;
+      }
+      Ok(())
+    }
+  }
+
+  // set the unix timestamp Clock::get returns on the test's thread
+  fn set_unix_timestamp(unix_timestamp: i64) {
+    static SET_STUBS: Once = Once::new();
+    SET_STUBS.call_once(|| {
+      program_stubs::set_syscall_stubs(Box::new(TestStubs));
+    });
+    UNIX_TIMESTAMP.with(|cell| cell.set(unix_timestamp));
+  }
+
+  // an account that lives until the tests finish, so instructions can borrow it like the runtime's
+  fn new_account(
+    key: Pubkey,
+    is_signer: bool,
+    lamports: u64,
+    data: Vec<u8>,
+    owner: Pubkey,
+  ) -> AccountInfo<'static> {
+    AccountInfo::new(
+      Box::leak(Box::new(key)),
+      is_signer,
+      true,
+      Box::leak(Box::new(lamports)),
+      Box::leak(data.into_boxed_slice()),
+      Box::leak(Box::new(owner)),
+      false,
+      0,
+    )
+  }
+
+  fn new_signer() -> AccountInfo<'static> {
+    new_account(
+      Pubkey::new_unique(),
+      true,
+      1_000_000_000,
+      vec![],
+      system_program::id(),
+    )
+  }
+
+  fn new_mint(mint_authority: &Pubkey) -> AccountInfo<'static> {
+    let mut data = vec![0; TokenMintAccount::LEN];
+    TokenMintAccount {
+      mint_authority: COption::Some(*mint_authority),
+      is_initialized: true,
+      ..TokenMintAccount::default()
+    }
+    .pack_into_slice(&mut data);
+    new_account(Pubkey::new_unique(), false, 0, data, spl_token::id())
+  }
+
+  // token account holding amount of the mint, which is added to the mint's supply
+  fn new_token_account(mint: &AccountInfo, owner: &Pubkey, amount: u64) -> AccountInfo<'static> {
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount {
+      mint: *mint.key,
+      owner: *owner,
+      amount,
+      state: AccountState::Initialized,
+      ..TokenAccount::default()
+    }
+    .pack_into_slice(&mut data);
+    let mut mint_data = TokenMintAccount::unpack(&mint.data.borrow()).unwrap();
+    mint_data.supply += amount;
+    mint_data.pack_into_slice(&mut mint.data.borrow_mut());
+    new_account(Pubkey::new_unique(), false, 0, data, spl_token::id())
+  }
+
+  fn token_balance(token_account: &AccountInfo) -> u64 {
+    TokenAccount::unpack(&token_account.data.borrow())
+      .unwrap()
+      .amount
+  }
+
+  fn run(accounts: &[&AccountInfo<'static>], instruction_data: &[u8]) -> ProgramResult {
+    let accounts: Vec<AccountInfo> = accounts.iter().map(|account| (*account).clone()).collect();
+    Processor::process(&PROGRAM_ID, &accounts, instruction_data)
+  }
+
+  // a categorical market account and its complete set vault, before InitCategoricalMarket, and open
+  // outcome markets settled in the same usd mint
+  struct TestCategoricalMarket {
+    categorical_market: AccountInfo<'static>,
+    pda: AccountInfo<'static>,
+    vault: AccountInfo<'static>,
+    judge: AccountInfo<'static>,
+    outcomes: Vec<TestMarket>,
+  }
+
+  impl TestCategoricalMarket {
+    fn new(num_outcomes: usize) -> Self {
+      let usd_mint = new_mint(&Pubkey::new_unique());
+      let categorical_market = new_account(
+        Pubkey::new_unique(),
+        false,
+        0,
+        vec![0; categorical::CATEGORICAL_MARKET_LEN],
+        PROGRAM_ID,
+      );
+      let (pda_key, _) =
+        Pubkey::find_program_address(&[b"betting", categorical_market.key.as_ref()], &PROGRAM_ID);
+      TestCategoricalMarket {
+        categorical_market,
+        pda: new_account(pda_key, false, 0, vec![], system_program::id()),
+        vault: new_account(
+          Pubkey::new_unique(),
+          true,
+          0,
+          vec![0; TokenAccount::LEN],
+          spl_token::id(),
+        ),
+        judge: new_signer(),
+        outcomes: (0..num_outcomes)
+          .map(|_| TestMarket::with_usd_mint(usd_mint.clone(), |_| {}))
+          .collect(),
+      }
+    }
+
+    // InitCategoricalMarket over the given outcome markets
+    fn init(&self, outcomes: &[&TestMarket]) -> ProgramResult {
+      let initializer = new_signer();
+      let system_program_account =
+        new_account(system_program::id(), false, 0, vec![], Pubkey::default());
+      let mut rent = new_account(
+        sysvar::rent::id(),
+        false,
+        0,
+        vec![0; Rent::size_of()],
+        sysvar::id(),
+      );
+      Rent::default().to_account_info(&mut rent).unwrap();
+      let outcome_market = &self.outcomes[0];
+      let mut accounts = vec![
+        &initializer,
+        &self.pda,
+        &self.categorical_market,
+        &outcome_market.token_program,
+        &outcome_market.usd_mint,
+        &self.vault,
+        &self.judge,
+        &system_program_account,
+        &rent,
+      ];
+      for outcome in outcomes {
+        accounts.extend([&outcome.market, &outcome.judge, &outcome.vault]);
+      }
+      run(&accounts, &[14])
+    }
+
+    fn split(
+      &self,
+      user: &TestUser,
+      yes_accounts: &[AccountInfo<'static>],
+      amount: u64,
+    ) -> ProgramResult {
+      let mut accounts = vec![
+        &user.account,
+        &self.categorical_market,
+        &self.vault,
+        &user.usd,
+        &self.outcomes[0].token_program,
+      ];
+      for (outcome, yes_account) in self.outcomes.iter().zip(yes_accounts) {
+        accounts.extend([&outcome.pda, &outcome.yes_mint, yes_account]);
+      }
+      let mut instruction_data = vec![15];
+      instruction_data.extend_from_slice(&amount.to_le_bytes());
+      run(&accounts, &instruction_data)
+    }
+
+    fn merge(
+      &self,
+      user: &TestUser,
+      yes_accounts: &[AccountInfo<'static>],
+      amount: u64,
+    ) -> ProgramResult {
+      let mut accounts = vec![
+        &user.account,
+        &self.pda,
+        &self.categorical_market,
+        &self.vault,
+        &user.usd,
+        &self.outcomes[0].token_program,
+      ];
+      for (outcome, yes_account) in self.outcomes.iter().zip(yes_accounts) {
+        accounts.extend([&outcome.yes_mint, yes_account]);
+      }
+      let mut instruction_data = vec![16];
+      instruction_data.extend_from_slice(&amount.to_le_bytes());
+      run(&accounts, &instruction_data)
+    }
+
+    // JudgeCategoricalMarket, passing the first outcome's vault for winning outcomes out of range
+    fn judge(&self, winning_outcome: u64) -> ProgramResult {
+      let winning_market = self
+        .outcomes
+        .get(winning_outcome as usize)
+        .unwrap_or(&self.outcomes[0]);
+      let mut accounts = vec![
+        &self.categorical_market,
+        &self.judge,
+        &self.pda,
+        &self.vault,
+        &winning_market.vault,
+        &self.outcomes[0].token_program,
+      ];
+      accounts.extend(self.outcomes.iter().map(|outcome| &outcome.market));
+      let mut instruction_data = vec![17];
+      instruction_data.extend_from_slice(&winning_outcome.to_le_bytes());
+      run(&accounts, &instruction_data)
+    }
+
+    // a user of the outcome market at the index with a token account for the yes token of every outcome
+    fn new_user(&self, index: usize, usd_amount: u64) -> (TestUser, Vec<AccountInfo<'static>>) {
+      let user = self.outcomes[index].new_user(usd_amount);
+      let yes_accounts = self
+        .outcomes
+        .iter()
+        .enumerate()
+        .map(|(outcome_index, outcome)| {
+          if outcome_index == index {
+            user.yes.clone()
+          } else {
+            new_token_account(&outcome.yes_mint, user.account.key, 0)
+          }
+        })
+        .collect();
+      (user, yes_accounts)
+    }
+  }
+
+  // binary market on whether the mock oracle is above 100, open for oracle judging
+  fn test_betting_market(oracle: Pubkey) -> BettingMarket {
+    BettingMarket {
+      is_initialized: true,
+      result: 0,
+      yes_token_mint: Pubkey::new_unique(),
+      no_token_mint: Pubkey::new_unique(),
+      usd_token_account: Pubkey::new_unique(),
+      strike_price: 100,
+      judge: Pubkey::new_unique(),
+      order_book: Pubkey::new_unique(),
+      bump_seed: 0,
+      yes_payout: 0,
+      market_type: BINARY_MARKET_TYPE,
+      lower_bound: 0,
+      upper_bound: 0,
+      categorical_market: NULL_PUBKEY,
+      maker_fee_bps: 0,
+      taker_fee_bps: 0,
+      fee_vault: Pubkey::new_unique(),
+      fee_authority: Pubkey::new_unique(),
+      fee_recipient: Pubkey::new_unique(),
+      creator: NULL_PUBKEY,
+      creator_fee_share_bps: 0,
+      referrer_fee_share_bps: 0,
+      trading_close_ts: NOW - 20,
+      resolution_ts: NOW - 10,
+      has_traded: true,
+      oracle,
+      comparison: COMPARISON_ABOVE,
+      strike_expo: 0,
+      twap_window: 0,
+      min_twap_samples: 0,
+      oracle_kind: MOCK_ORACLE_KIND,
+      dispute_window: DEFAULT_DISPUTE_WINDOW_SECS,
+      resolution_bond: 0,
+      proposer: NULL_PUBKEY,
+      disputer: NULL_PUBKEY,
+      proposed_result: 0,
+      proposed_yes_payout: 0,
+      proposal_ts: 0,
+    }
+  }
+
+  fn new_betting_market(oracle: Pubkey) -> Vec<u8> {
+    let mut data = vec![0; BETTING_MARKET_DATA_LEN];
+    BettingMarket::pack(test_betting_market(oracle), &mut data[..BettingMarket::LEN]).unwrap();
+    data
+  }
+
+  // a betting market with its order book, mints and vaults as InitBettingMarket leaves them
+  struct TestMarket {
+    market: AccountInfo<'static>,
+    pda: AccountInfo<'static>,
+    order_book: AccountInfo<'static>,
+    usd_mint: AccountInfo<'static>,
+    yes_mint: AccountInfo<'static>,
+    no_mint: AccountInfo<'static>,
+    vault: AccountInfo<'static>,
+    fee_vault: AccountInfo<'static>,
+    token_program: AccountInfo<'static>,
+    judge: AccountInfo<'static>,
+  }
+
+  // a trader with usd, yes and no token accounts and a claimable balance account for the market
+  struct TestUser {
+    account: AccountInfo<'static>,
+    usd: AccountInfo<'static>,
+    yes: AccountInfo<'static>,
+    no: AccountInfo<'static>,
+    claim: AccountInfo<'static>,
+  }
+
+  impl TestUser {
+    fn claimable(&self, token: ClaimableToken) -> u64 {
+      claim::get_balance(&self.claim.data.borrow(), token)
+    }
+  }
+
+  impl TestMarket {
+    // binary market without an oracle trading until NOW + 100 and taking proposals from NOW + 200,
+    // with the test's changes to the betting market data, settled in a new usd mint
+    fn new(configure: impl FnOnce(&mut BettingMarket)) -> Self {
+      Self::with_usd_mint(new_mint(&Pubkey::new_unique()), configure)
+    }
+
+    fn with_usd_mint(
+      usd_mint: AccountInfo<'static>,
+      configure: impl FnOnce(&mut BettingMarket),
+    ) -> Self {
+      set_unix_timestamp(NOW);
+      let market_key = Pubkey::new_unique();
+      let (pda_key, bump_seed) =
+        Pubkey::find_program_address(&[b"betting", market_key.as_ref()], &PROGRAM_ID);
+      let pda = new_account(pda_key, false, 0, vec![], system_program::id());
+      let yes_mint = new_mint(&pda_key);
+      let no_mint = new_mint(&pda_key);
+      let vault = new_token_account(&usd_mint, &pda_key, 0);
+      let fee_vault = new_token_account(&usd_mint, &pda_key, 0);
+      let mut order_book_data = vec![0; ORDER_BOOK_NODES_OFFSET + NODE_LEN * 32];
+      orderbook::init_order_book(&mut order_book_data, &market_key).unwrap();
+      let order_book = new_account(Pubkey::new_unique(), false, 0, order_book_data, PROGRAM_ID);
+      let token_program = new_account(spl_token::id(), false, 0, vec![], Pubkey::default());
+      let judge = new_signer();
+
+      let mut betting_market = BettingMarket {
+        yes_token_mint: *yes_mint.key,
+        no_token_mint: *no_mint.key,
+        usd_token_account: *vault.key,
+        judge: *judge.key,
+        order_book: *order_book.key,
+        bump_seed,
+        fee_vault: *fee_vault.key,
+        trading_close_ts: NOW + 100,
+        resolution_ts: NOW + 200,
+        has_traded: false,
+        oracle: NULL_PUBKEY,
+        oracle_kind: PYTH_ORACLE_KIND,
+        ..test_betting_market(NULL_PUBKEY)
+      };
+      configure(&mut betting_market);
+      let mut market_data = vec![0; BETTING_MARKET_DATA_LEN];
+      BettingMarket::pack(betting_market, &mut market_data[..BettingMarket::LEN]).unwrap();
+      TestMarket {
+        market: new_account(market_key, false, 0, market_data, PROGRAM_ID),
+        pda,
+        order_book,
+        usd_mint,
+        yes_mint,
+        no_mint,
+        vault,
+        fee_vault,
+        token_program,
+        judge,
+      }
+    }
+
+    fn new_user(&self, usd_amount: u64) -> TestUser {
+      let account = new_signer();
+      let (claim_key, bump_seed) = Pubkey::find_program_address(
+        &[self.market.key.as_ref(), account.key.as_ref()],
+        &PROGRAM_ID,
+      );
+      let mut claim_data = vec![0; claim::CLAIM_ACCOUNT_LEN];
+      claim::init_claim_account(&mut claim_data, self.market.key, account.key, bump_seed);
+      TestUser {
+        usd: new_token_account(&self.usd_mint, account.key, usd_amount),
+        yes: new_token_account(&self.yes_mint, account.key, 0),
+        no: new_token_account(&self.no_mint, account.key, 0),
+        claim: new_account(claim_key, false, 0, claim_data, PROGRAM_ID),
+        account,
+      }
+    }
+
+    fn betting_market(&self) -> BettingMarket {
+      validation::validate_betting_market_account(&self.market, &PROGRAM_ID).unwrap()
+    }
+
+    fn update(&self, update: impl FnOnce(&mut BettingMarket)) {
+      let mut betting_market = self.betting_market();
+      update(&mut betting_market);
+      Processor::pack_betting_market(betting_market, &self.market).unwrap();
+    }
+
+    fn level_amount(&self, is_yes: bool, price: u64) -> u64 {
+      orderbook::get_level_amount(&self.order_book.data.borrow(), is_yes, price)
+    }
+
+    // the accounts after the user's pda and betting market in the trade instructions, with or without the
+    // order book
+    fn trade_accounts<'a>(
+      &'a self,
+      user: &'a TestUser,
+      with_order_book: bool,
+    ) -> Vec<&'a AccountInfo<'static>> {
+      let mut accounts = vec![&user.account, &self.pda, &self.market];
+      if with_order_book {
+        accounts.push(&self.order_book);
+      }
+      accounts.extend([
+        &self.usd_mint,
+        &self.yes_mint,
+        &self.no_mint,
+        &user.usd,
+        &user.yes,
+        &user.no,
+        &self.vault,
+        &self.token_program,
+      ]);
+      accounts
+    }
+
+    // OfferTrade passing the claimable balance accounts of the others, and of the referrer along with its
+    // usd token account
+    #[allow(clippy::too_many_arguments)]
+    fn offer_trade(
+      &self,
+      user: &TestUser,
+      is_yes: bool,
+      price: u64,
+      amount: u64,
+      time_in_force: u8,
+      others: &[&TestUser],
+      referrer: Option<&TestUser>,
+    ) -> ProgramResult {
+      let mut accounts = self.trade_accounts(user, true);
+      accounts.extend([&self.fee_vault, &user.claim]);
+      accounts.extend(others.iter().map(|other| &other.claim));
+      if let Some(referrer) = referrer {
+        accounts.extend([&referrer.claim, &referrer.usd]);
+      }
+      let mut instruction_data = vec![3, is_yes as u8];
+      instruction_data.extend_from_slice(&price.to_le_bytes());
+      instruction_data.extend_from_slice(&amount.to_le_bytes());
+      instruction_data.extend_from_slice(&[time_in_force, referrer.is_some() as u8]);
+      run(&accounts, &instruction_data)
+    }
+
+    fn market_order(
+      &self,
+      user: &TestUser,
+      is_yes: bool,
+      worst_price: u64,
+      size: MarketOrderSize,
+      others: &[&TestUser],
+    ) -> ProgramResult {
+      let mut accounts = self.trade_accounts(user, true);
+      accounts.extend([&self.fee_vault, &user.claim]);
+      accounts.extend(others.iter().map(|other| &other.claim));
+      let mut instruction_data = vec![10, is_yes as u8];
+      instruction_data.extend_from_slice(&worst_price.to_le_bytes());
+      let (size_kind, amount) = match size {
+        MarketOrderSize::Quantity(quantity) => (0, quantity),
+        MarketOrderSize::Budget(budget) => (1, budget),
+      };
+      instruction_data.push(size_kind);
+      instruction_data.extend_from_slice(&amount.to_le_bytes());
+      run(&accounts, &instruction_data)
+    }
+
+    fn split_complete_set(&self, user: &TestUser, amount: u64) -> ProgramResult {
+      let mut instruction_data = vec![18];
+      instruction_data.extend_from_slice(&amount.to_le_bytes());
+      run(&self.trade_accounts(user, false), &instruction_data)
+    }
+
+    fn merge_complete_set(&self, user: &TestUser, amount: u64) -> ProgramResult {
+      let mut instruction_data = vec![19];
+      instruction_data.extend_from_slice(&amount.to_le_bytes());
+      run(&self.trade_accounts(user, false), &instruction_data)
+    }
+
+    fn cancel_order(&self, user: &TestUser, is_yes: bool, price: u64) -> ProgramResult {
+      let mut instruction_data = vec![9, is_yes as u8];
+      instruction_data.extend_from_slice(&price.to_le_bytes());
+      run(&self.trade_accounts(user, true), &instruction_data)
+    }
+
+    fn payout(&self, user: &TestUser) -> ProgramResult {
+      let mut accounts = self.trade_accounts(user, false);
+      accounts.push(&user.claim);
+      run(&accounts, &[4])
+    }
+
+    fn reclaim_open_orders(&self, user: &TestUser) -> ProgramResult {
+      run(
+        &[&user.account, &self.market, &self.order_book, &user.claim],
+        &[13],
+      )
+    }
+
+    fn propose_result(&self, user: &TestUser, result: u64, yes_payout: u64) -> ProgramResult {
+      let mut instruction_data = vec![22];
+      instruction_data.extend_from_slice(&result.to_le_bytes());
+      instruction_data.extend_from_slice(&yes_payout.to_le_bytes());
+      run(
+        &[
+          &user.account,
+          &self.market,
+          &user.usd,
+          &self.vault,
+          &self.token_program,
+          &user.claim,
+        ],
+        &instruction_data,
+      )
+    }
+
+    fn dispute_result(&self, user: &TestUser) -> ProgramResult {
+      run(
+        &[
+          &user.account,
+          &self.market,
+          &user.usd,
+          &self.vault,
+          &self.token_program,
+          &user.claim,
+        ],
+        &[23],
+      )
+    }
+
+    fn finalize_result(&self, proposer: &TestUser) -> ProgramResult {
+      run(&[&self.market, &proposer.claim], &[24])
+    }
+
+    // JudgeBettingMarketManually crediting both bonds to the winner's claimable balance account
+    fn judge_result(&self, result: u64, yes_payout: u64, winner: &TestUser) -> ProgramResult {
+      let mut instruction_data = vec![6];
+      instruction_data.extend_from_slice(&result.to_le_bytes());
+      instruction_data.extend_from_slice(&yes_payout.to_le_bytes());
+      run(
+        &[&self.market, &self.judge, &winner.claim],
+        &instruction_data,
+      )
+    }
+  }
+
+  fn new_mock_oracle(price: i64, conf: u64, expo: i32, publish_time: i64) -> Vec<u8> {
+    let mut data = vec![0; MOCK_ORACLE_LEN];
+    data[..8].copy_from_slice(&MOCK_ORACLE_MAGIC);
+    data[8..16].copy_from_slice(&price.to_le_bytes());
+    data[16..24].copy_from_slice(&conf.to_le_bytes());
+    data[24..28].copy_from_slice(&expo.to_le_bytes());
+    data[28..36].copy_from_slice(&publish_time.to_le_bytes());
+    data
+  }
+
+  // run JudgeBettingMarketOracle and return its result along with the judged betting market
+  fn judge(
+    oracle_data: &mut [u8],
+    is_oracle_owned_by_program: bool,
+  ) -> (ProgramResult, BettingMarket) {
+    set_unix_timestamp(NOW);
+    let program_id = Pubkey::new_unique();
+    let oracle_owner = if is_oracle_owned_by_program {
+      program_id
+    } else {
+      Pubkey::new_unique()
+    };
+    let (market_key, oracle_key) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let mut market_data = new_betting_market(oracle_key);
+    let (mut market_lamports, mut oracle_lamports) = (0, 0);
+    let accounts = [
+      AccountInfo::new(
+        &market_key,
+        false,
+        true,
+        &mut market_lamports,
+        &mut market_data,
+        &program_id,
+        false,
+        0,
+      ),
+      AccountInfo::new(
+        &oracle_key,
+        false,
+        false,
+        &mut oracle_lamports,
+        oracle_data,
+        &oracle_owner,
+        false,
+        0,
+      ),
+    ];
+    let result = Processor::process(&program_id, &accounts, &[7]);
+    let betting_market =
+      validation::validate_betting_market_account(&accounts[0], &program_id).unwrap();
+    (result, betting_market)
+  }
+
+  // run JudgeBettingMarketManually on an oracle market with the given resolution timestamp
+  fn judge_manually(
+    resolution_ts: i64,
+    result: u64,
+    yes_payout: u64,
+  ) -> (ProgramResult, BettingMarket) {
+    set_unix_timestamp(NOW);
+    let program_id = Pubkey::new_unique();
+    let market_key = Pubkey::new_unique();
+    let mut market_data = new_betting_market(Pubkey::new_unique());
+    let mut betting_market =
+      BettingMarket::unpack_unchecked(&market_data[..BettingMarket::LEN]).unwrap();
+    betting_market.resolution_ts = resolution_ts;
+    let judge_key = betting_market.judge;
+    BettingMarket::pack(betting_market, &mut market_data[..BettingMarket::LEN]).unwrap();
+    let (mut market_lamports, mut judge_lamports) = (0, 0);
+    let mut judge_data = [];
+    let accounts = [
+      AccountInfo::new(
+        &market_key,
+        false,
+        true,
+        &mut market_lamports,
+        &mut market_data,
+        &program_id,
+        false,
+        0,
+      ),
+      AccountInfo::new(
+        &judge_key,
+        true,
+        false,
+        &mut judge_lamports,
+        &mut judge_data,
+        &program_id,
+        false,
+        0,
+      ),
+    ];
+    let mut instruction_data = vec![6];
+    instruction_data.extend_from_slice(&result.to_le_bytes());
+    instruction_data.extend_from_slice(&yes_payout.to_le_bytes());
+    let result = Processor::process(&program_id, &accounts, &instruction_data);
+    let betting_market =
+      validation::validate_betting_market_account(&accounts[0], &program_id).unwrap();
+    (result, betting_market)
+  }
+
+  #[test]
+  fn judges_yes_above_the_strike_price() {
+    // 105.00 +- 0.50 is above the strike price of 100
+    let mut oracle_data = new_mock_oracle(10_500, 50, -2, NOW - 1);
+    let (result, betting_market) = judge(&mut oracle_data, true);
+    assert_eq!(result, Ok(()));
+    assert_eq!(betting_market.result, 1);
+    assert_eq!(betting_market.yes_payout, 100);
+  }
+
+  #[test]
+  fn judges_no_below_the_strike_price() {
+    let mut oracle_data = new_mock_oracle(9_500, 50, -2, NOW - 1);
+    let (result, betting_market) = judge(&mut oracle_data, true);
+    assert_eq!(result, Ok(()));
+    assert_eq!(betting_market.result, 2);
+    assert_eq!(betting_market.yes_payout, 0);
+  }
+
+  #[test]
+  fn rejects_strike_price_within_confidence() {
+    let mut oracle_data = new_mock_oracle(10_020, 50, -2, NOW - 1);
+    let (result, betting_market) = judge(&mut oracle_data, true);
+    assert_eq!(
+      result,
+      Err(BettingMarketError::StrikeWithinConfidence.into())
+    );
+    assert_eq!(betting_market.result, 0);
+  }
+
+  #[test]
+  fn rejects_stale_mock_oracle_price() {
+    let mut oracle_data = new_mock_oracle(10_500, 50, -2, NOW - 60);
+    let (result, betting_market) = judge(&mut oracle_data, true);
+    assert_eq!(result, Err(BettingMarketError::StaleOraclePrice.into()));
+    assert_eq!(betting_market.result, 0);
+  }
+
+  #[test]
+  fn rejects_mock_oracle_owned_by_another_program() {
+    let mut oracle_data = new_mock_oracle(10_500, 50, -2, NOW - 1);
+    let (result, betting_market) = judge(&mut oracle_data, false);
+    assert_eq!(result, Err(BettingMarketError::InvalidOracleAccount.into()));
+    assert_eq!(betting_market.result, 0);
+  }
+
+  #[test]
+  fn rejects_manual_judging_of_oracle_market_within_grace_period() {
+    let (result, betting_market) = judge_manually(NOW - ORACLE_GRACE_PERIOD_SECS + 1, 3, 50);
+    assert_eq!(
+      result,
+      Err(BettingMarketError::OracleGracePeriodNotOver.into())
+    );
+    assert_eq!(betting_market.result, 0);
+  }
+
+  #[test]
+  fn judge_voids_oracle_market_after_grace_period() {
+    let (result, betting_market) = judge_manually(NOW - ORACLE_GRACE_PERIOD_SECS, 3, 50);
+    assert_eq!(result, Ok(()));
+    assert_eq!(betting_market.result, 3);
+    assert_eq!(betting_market.yes_payout, 50);
+  }
+
+  #[test]
+  fn judge_settles_oracle_market_after_grace_period() {
+    let (result, betting_market) = judge_manually(NOW - ORACLE_GRACE_PERIOD_SECS, 2, 0);
+    assert_eq!(result, Ok(()));
+    assert_eq!(betting_market.result, 2);
+    assert_eq!(betting_market.yes_payout, 0);
+  }
+
+  #[test]
+  fn splits_fees_between_the_fee_vault_creator_and_referrer() {
+    let market = TestMarket::new(|betting_market| {
+      betting_market.maker_fee_bps = 100;
+      betting_market.taker_fee_bps = 200;
+      betting_market.creator_fee_share_bps = 3_000;
+      betting_market.referrer_fee_share_bps = 5_000;
+    });
+    let creator = market.new_user(0);
+    market.update(|betting_market| betting_market.creator = *creator.account.key);
+    let (maker, taker, referrer) = (
+      market.new_user(1_000),
+      market.new_user(1_000),
+      market.new_user(0),
+    );
+
+    // the resting yes buy of 10 at 60 deposits its 6 usd maker fee
+    assert_eq!(
+      market.offer_trade(&maker, true, 60, 10, 0, &[], None),
+      Ok(())
+    );
+    assert_eq!(token_balance(&maker.usd), 394);
+    assert_eq!(token_balance(&market.vault), 606);
+
+    // the no buy of 10 at 40 crosses it, with an 8 usd taker fee on top, 14 usd of fees in all:
+    // the creator gets 30% of them, the referrer 50% of the taker fee and the fee vault the rest
+    assert_eq!(
+      market.offer_trade(
+        &taker,
+        false,
+        40,
+        10,
+        0,
+        &[&maker, &creator],
+        Some(&referrer)
+      ),
+      Ok(())
+    );
+    assert_eq!(token_balance(&taker.usd), 592);
+    assert_eq!(creator.claimable(ClaimableToken::Usd), 4);
+    assert_eq!(referrer.claimable(ClaimableToken::Usd), 4);
+    assert_eq!(token_balance(&market.fee_vault), 6);
+    // the vault holds the collateral of the 10 matched sets and the shares credited to claim accounts
+    assert_eq!(token_balance(&market.vault), 1_008);
+    assert_eq!(maker.claimable(ClaimableToken::Yes), 10);
+    assert_eq!(taker.claimable(ClaimableToken::No), 10);
+    assert_eq!(taker.claimable(ClaimableToken::Usd), 0);
+  }
+
+  #[test]
+  fn maker_fee_deposits_round_up_to_cover_fills_rounded_down() {
+    let market = TestMarket::new(|betting_market| betting_market.maker_fee_bps = 150);
+    let (maker, taker) = (market.new_user(1_000), market.new_user(1_000));
+
+    // 1.5% of 550 usd is 8.25, so the deposit is 9
+    assert_eq!(
+      market.offer_trade(&maker, true, 55, 10, 0, &[], None),
+      Ok(())
+    );
+    assert_eq!(token_balance(&maker.usd), 441);
+
+    // fills of 3, 3 and 4 pay maker fees of 2, 2 and 3 out of the deposit
+    for (amount, fee_vault_balance) in [(3, 2), (3, 4), (4, 7)] {
+      assert_eq!(
+        market.offer_trade(&taker, false, 45, amount, 0, &[&maker], None),
+        Ok(())
+      );
+      assert_eq!(token_balance(&market.fee_vault), fee_vault_balance);
+    }
+    assert_eq!(market.level_amount(true, 55), 0);
+    assert_eq!(token_balance(&taker.usd), 550);
+    assert_eq!(maker.claimable(ClaimableToken::Yes), 10);
+    assert_eq!(taker.claimable(ClaimableToken::No), 10);
+    // the collateral of 10 sets and the 2 usd the deposit rounded up by
+    assert_eq!(token_balance(&market.vault), 1_002);
+  }
+
+  #[test]
+  fn refunds_unused_maker_fee_deposits_on_cancel_and_reclaim() {
+    let market = TestMarket::new(|betting_market| betting_market.maker_fee_bps = 150);
+    let (maker, taker) = (market.new_user(1_000), market.new_user(1_000));
+    assert_eq!(
+      market.offer_trade(&maker, true, 55, 10, 0, &[], None),
+      Ok(())
+    );
+    assert_eq!(
+      market.offer_trade(&taker, false, 45, 3, 0, &[&maker], None),
+      Ok(())
+    );
+    assert_eq!(token_balance(&market.fee_vault), 2);
+
+    // cancelling the remaining 7 refunds 385 usd of collateral and 5 usd of the deposit, rounded down
+    assert_eq!(market.cancel_order(&maker, true, 55), Ok(()));
+    assert_eq!(token_balance(&maker.usd), 441 + 390);
+    assert_eq!(market.level_amount(true, 55), 0);
+    assert_eq!(token_balance(&market.vault), 302);
+    assert_eq!(
+      market.cancel_order(&maker, true, 55),
+      Err(BettingMarketError::OrderNotFound.into())
+    );
+
+    // an order of 4 at 55 deposits 4 usd and reclaiming it once the market is resolved credits 220 usd
+    // of collateral and 3 usd of the deposit
+    assert_eq!(
+      market.offer_trade(&maker, true, 55, 4, 0, &[], None),
+      Ok(())
+    );
+    assert_eq!(token_balance(&maker.usd), 831 - 224);
+    market.update(|betting_market| {
+      betting_market.result = 2;
+      betting_market.yes_payout = 0;
+    });
+    assert_eq!(market.reclaim_open_orders(&maker), Ok(()));
+    assert_eq!(maker.claimable(ClaimableToken::Usd), 223);
+    assert_eq!(market.level_amount(true, 55), 0);
+
+    assert_eq!(market.payout(&maker), Ok(()));
+    assert_eq!(maker.claimable(ClaimableToken::Usd), 0);
+    assert_eq!(token_balance(&maker.usd), 831 - 224 + 223);
+    // the vault still backs the winning no tokens of the 3 matched sets, along with the 3 usd the deposits
+    // were rounded up by
+    assert_eq!(token_balance(&market.vault), 303);
+  }
+
+  #[test]
+  fn credits_creator_and_referrer_shares_of_fees_deducted_from_usd_payouts() {
+    let market = TestMarket::new(|betting_market| {
+      betting_market.maker_fee_bps = 100;
+      betting_market.taker_fee_bps = 200;
+      betting_market.creator_fee_share_bps = 2_000;
+      betting_market.referrer_fee_share_bps = 5_000;
+    });
+    let creator = market.new_user(0);
+    market.update(|betting_market| betting_market.creator = *creator.account.key);
+    let (maker, taker, referrer) = (
+      market.new_user(1_000),
+      market.new_user(1_000),
+      market.new_user(0),
+    );
+    assert_eq!(market.split_complete_set(&taker, 10), Ok(()));
+    assert_eq!(
+      market.offer_trade(&maker, true, 60, 10, 0, &[], None),
+      Ok(())
+    );
+
+    // the taker can't refer themselves
+    assert_eq!(
+      market.offer_trade(&taker, false, 40, 10, 0, &[&maker, &creator], Some(&taker)),
+      Err(BettingMarketError::InvalidReferrer.into())
+    );
+
+    // selling 10 yes tokens at 60 pays out 600 usd minus the 12 usd taker fee, the maker pays 6 usd out of
+    // their deposit, the creator gets 20% of the 18 usd of fees and the referrer 50% of the taker fee
+    assert_eq!(
+      market.offer_trade(
+        &taker,
+        false,
+        40,
+        10,
+        0,
+        &[&maker, &creator],
+        Some(&referrer)
+      ),
+      Ok(())
+    );
+    assert_eq!(token_balance(&taker.yes), 0);
+    assert_eq!(taker.claimable(ClaimableToken::Usd), 588);
+    assert_eq!(maker.claimable(ClaimableToken::Yes), 10);
+    assert_eq!(creator.claimable(ClaimableToken::Usd), 3);
+    assert_eq!(referrer.claimable(ClaimableToken::Usd), 6);
+    assert_eq!(token_balance(&market.fee_vault), 9);
+    assert_eq!(token_balance(&market.vault), 1_000 + 588 + 3 + 6);
+
+    // the shares are paid out like any claimable balance
+    for user in [&creator, &referrer] {
+      assert_eq!(market.payout(user), Ok(()));
+    }
+    assert_eq!(token_balance(&creator.usd), 3);
+    assert_eq!(token_balance(&referrer.usd), 6);
+  }
+
+  #[test]
+  fn links_outcome_markets_into_a_categorical_market() {
+    let categorical_market = TestCategoricalMarket::new(3);
+    let outcomes: Vec<&TestMarket> = categorical_market.outcomes.iter().collect();
+    assert_eq!(categorical_market.init(&outcomes), Ok(()));
+
+    let data = categorical_market.categorical_market.data.borrow();
+    assert!(categorical::is_initialized(&data));
+    assert_eq!(categorical::get_num_outcomes(&data), 3);
+    assert_eq!(categorical::get_judge(&data), *categorical_market.judge.key);
+    for (index, outcome) in outcomes.iter().enumerate() {
+      let linked_outcome = categorical::get_outcome(&data, index);
+      assert_eq!(linked_outcome.market, *outcome.market.key);
+      assert_eq!(linked_outcome.yes_token_mint, *outcome.yes_mint.key);
+      assert_eq!(
+        outcome.betting_market().categorical_market,
+        *categorical_market.categorical_market.key
+      );
+    }
+    let vault_data = TokenAccount::unpack(&categorical_market.vault.data.borrow()).unwrap();
+    assert_eq!(vault_data.owner, *categorical_market.pda.key);
+    assert_eq!(vault_data.mint, *outcomes[0].usd_mint.key);
+
+    // linked outcomes are only judged through the categorical market
+    let judge = outcomes[0].judge.clone();
+    assert_eq!(
+      run(&[&outcomes[0].market, &judge], &[6, 1, 0, 0, 0, 0, 0, 0, 0]),
+      Err(BettingMarketError::CategoricalOutcomeMarket.into())
+    );
+  }
+
+  #[test]
+  fn rejects_outcome_markets_linked_twice_or_sharing_mints() {
+    let categorical_market = TestCategoricalMarket::new(3);
+    let outcomes = &categorical_market.outcomes;
+    assert_eq!(
+      categorical_market.init(&[&outcomes[0], &outcomes[1], &outcomes[0]]),
+      Err(BettingMarketError::DuplicateOutcomeMarket.into())
+    );
+
+    let categorical_market = TestCategoricalMarket::new(3);
+    let outcomes = &categorical_market.outcomes;
+    let yes_token_mint = outcomes[0].betting_market().yes_token_mint;
+    outcomes[2].update(|betting_market| betting_market.no_token_mint = yes_token_mint);
+    assert_eq!(
+      categorical_market.init(&[&outcomes[0], &outcomes[1], &outcomes[2]]),
+      Err(BettingMarketError::DuplicateOutcomeMarket.into())
+    );
+  }
+
+  #[test]
+  fn splits_and_merges_categorical_sets_against_the_complete_set_vault() {
+    let categorical_market = TestCategoricalMarket::new(3);
+    let outcomes: Vec<&TestMarket> = categorical_market.outcomes.iter().collect();
+    assert_eq!(categorical_market.init(&outcomes), Ok(()));
+    let (user, yes_accounts) = categorical_market.new_user(0, 1_000);
+
+    assert_eq!(categorical_market.split(&user, &yes_accounts, 3), Ok(()));
+    assert_eq!(token_balance(&user.usd), 700);
+    assert_eq!(token_balance(&categorical_market.vault), 300);
+    assert!(yes_accounts
+      .iter()
+      .all(|yes_account| token_balance(yes_account) == 3));
+
+    assert_eq!(categorical_market.merge(&user, &yes_accounts, 2), Ok(()));
+    assert_eq!(token_balance(&user.usd), 900);
+    assert_eq!(token_balance(&categorical_market.vault), 100);
+    assert!(yes_accounts
+      .iter()
+      .all(|yes_account| token_balance(yes_account) == 1));
+    // the outcome markets' own vaults are untouched
+    assert!(outcomes
+      .iter()
+      .all(|outcome| token_balance(&outcome.vault) == 0));
+  }
+
+  #[test]
+  fn judging_a_categorical_market_moves_the_complete_set_collateral_to_the_winning_outcome() {
+    let categorical_market = TestCategoricalMarket::new(3);
+    let outcomes: Vec<&TestMarket> = categorical_market.outcomes.iter().collect();
+    assert_eq!(categorical_market.init(&outcomes), Ok(()));
+    let (user, yes_accounts) = categorical_market.new_user(1, 1_000);
+    assert_eq!(categorical_market.split(&user, &yes_accounts, 3), Ok(()));
+
+    assert_eq!(
+      categorical_market.judge(3),
+      Err(BettingMarketError::InvalidOutcomeIndex.into())
+    );
+    assert_eq!(categorical_market.judge(1), Ok(()));
+    assert_eq!(token_balance(&categorical_market.vault), 0);
+    assert_eq!(token_balance(&outcomes[1].vault), 300);
+    let results: Vec<u8> = outcomes
+      .iter()
+      .map(|outcome| outcome.betting_market().result)
+      .collect();
+    assert_eq!(results, vec![2, 1, 2]);
+    assert_eq!(
+      categorical::get_result(&categorical_market.categorical_market.data.borrow()),
+      2
+    );
+
+    // the winning yes tokens redeem for 100 each out of the moved collateral
+    assert_eq!(outcomes[1].payout(&user), Ok(()));
+    assert_eq!(token_balance(&user.usd), 1_000);
+    assert_eq!(token_balance(&outcomes[1].vault), 0);
+  }
+
+  #[test]
+  fn splits_and_merges_complete_sets_against_the_vault() {
+    let market = TestMarket::new(|_| {});
+    let user = market.new_user(1_000);
+
+    assert_eq!(market.split_complete_set(&user, 5), Ok(()));
+    assert_eq!(token_balance(&user.usd), 500);
+    assert_eq!(token_balance(&market.vault), 500);
+    assert_eq!((token_balance(&user.yes), token_balance(&user.no)), (5, 5));
+
+    assert_eq!(market.merge_complete_set(&user, 3), Ok(()));
+    assert_eq!(token_balance(&user.usd), 800);
+    assert_eq!(token_balance(&market.vault), 200);
+    assert_eq!((token_balance(&user.yes), token_balance(&user.no)), (2, 2));
+    assert_eq!(
+      market.merge_complete_set(&user, 3),
+      Err(spl_token::error::TokenError::InsufficientFunds.into())
+    );
+
+    // complete sets redeem for 100 whatever the result, so they still merge but no longer split
+    market.update(|betting_market| {
+      betting_market.result = 1;
+      betting_market.yes_payout = 100;
+    });
+    assert_eq!(
+      market.split_complete_set(&user, 1),
+      Err(BettingMarketError::MarketAlreadyResolved.into())
+    );
+    assert_eq!(market.merge_complete_set(&user, 2), Ok(()));
+    assert_eq!(token_balance(&user.usd), 1_000);
+    assert_eq!(token_balance(&market.vault), 0);
+  }
+
+  #[test]
+  fn cancel_refunds_usd_with_the_fee_deposit_and_re_mints_inverse_tokens() {
+    let market = TestMarket::new(|betting_market| betting_market.maker_fee_bps = 100);
+    let (user, other) = (market.new_user(1_000), market.new_user(1_000));
+    assert_eq!(
+      market.offer_trade(&other, true, 40, 2, 0, &[], None),
+      Ok(())
+    );
+    assert_eq!(market.split_complete_set(&user, 5), Ok(()));
+
+    // the yes buy of 8 at 40 burns the 5 no tokens and takes 120 usd with a 2 usd maker fee deposit for the rest
+    assert_eq!(market.offer_trade(&user, true, 40, 8, 0, &[], None), Ok(()));
+    assert_eq!(token_balance(&user.no), 0);
+    assert_eq!(token_balance(&user.usd), 378);
+    assert_eq!(market.level_amount(true, 40), 10);
+
+    assert_eq!(
+      market.cancel_order(&user, true, 0),
+      Err(BettingMarketError::InvalidPrice.into())
+    );
+    assert_eq!(
+      market.cancel_order(&user, false, 60),
+      Err(BettingMarketError::OrderNotFound.into())
+    );
+
+    // cancelling refunds the 120 usd with the 1 usd of the deposit a fill would have taken and re-mints the
+    // 5 no tokens, leaving the other user's order at the price
+    assert_eq!(market.cancel_order(&user, true, 40), Ok(()));
+    assert_eq!(token_balance(&user.no), 5);
+    assert_eq!(token_balance(&user.yes), 5);
+    assert_eq!(token_balance(&user.usd), 499);
+    assert_eq!(market.level_amount(true, 40), 2);
+    assert_eq!(token_balance(&market.vault), 500 + 81 + 1);
+    assert_eq!(
+      market.cancel_order(&user, true, 40),
+      Err(BettingMarketError::OrderNotFound.into())
+    );
+  }
+
+  #[test]
+  fn sizes_immediate_or_cancel_fill_or_kill_and_post_only_orders_to_the_crossable_amount() {
+    let market = TestMarket::new(|_| {});
+    let (maker, taker) = (market.new_user(1_000), market.new_user(1_000));
+    assert_eq!(
+      market.offer_trade(&maker, false, 60, 5, 0, &[], None),
+      Ok(())
+    );
+
+    assert_eq!(
+      market.offer_trade(&taker, true, 40, 1, 3, &[&maker], None),
+      Err(BettingMarketError::OrderWouldCross.into())
+    );
+    assert_eq!(
+      market.offer_trade(&taker, true, 40, 6, 2, &[&maker], None),
+      Err(BettingMarketError::OrderWouldNotFill.into())
+    );
+    assert_eq!(token_balance(&taker.usd), 1_000);
+
+    // immediate-or-cancel only collects usd for the 5 that cross and leaves nothing on the book
+    assert_eq!(
+      market.offer_trade(&taker, true, 40, 8, 1, &[&maker], None),
+      Ok(())
+    );
+    assert_eq!(token_balance(&taker.usd), 800);
+    assert_eq!(taker.claimable(ClaimableToken::Yes), 5);
+    assert_eq!(maker.claimable(ClaimableToken::No), 5);
+    assert_eq!(market.level_amount(true, 40), 0);
+    assert_eq!(market.level_amount(false, 60), 0);
+
+    // a post-only order that does not cross rests in full
+    assert_eq!(
+      market.offer_trade(&taker, true, 40, 3, 3, &[], None),
+      Ok(())
+    );
+    assert_eq!(market.level_amount(true, 40), 3);
+    assert_eq!(token_balance(&taker.usd), 680);
+  }
+
+  #[test]
+  fn rejects_fill_or_kill_and_refunds_good_till_cancelled_orders_past_the_maker_limit() {
+    let market = TestMarket::new(|_| {});
+    let makers: Vec<TestUser> = (0..=MAX_MAKERS_PER_MATCH)
+      .map(|_| market.new_user(60))
+      .collect();
+    for maker in &makers {
+      assert_eq!(
+        market.offer_trade(maker, false, 60, 1, 0, &[], None),
+        Ok(())
+      );
+    }
+    let taker = market.new_user(1_000);
+    let others: Vec<&TestUser> = makers.iter().collect();
+    let amount = makers.len() as u64;
+
+    assert_eq!(
+      market.offer_trade(&taker, true, 40, amount, 2, &others, None),
+      Err(BettingMarketError::OrderWouldNotFill.into())
+    );
+    assert_eq!(token_balance(&taker.usd), 1_000);
+
+    // the order past the maker limit neither matches nor rests, its usd collateral is claimable again
+    assert_eq!(
+      market.offer_trade(&taker, true, 40, amount, 0, &others, None),
+      Ok(())
+    );
+    assert_eq!(token_balance(&taker.usd), 1_000 - 40 * amount);
+    assert_eq!(taker.claimable(ClaimableToken::Yes), amount - 1);
+    assert_eq!(taker.claimable(ClaimableToken::Usd), 40);
+    assert_eq!(market.level_amount(true, 40), 0);
+    assert_eq!(market.level_amount(false, 60), 1);
+    assert_eq!(
+      makers[MAX_MAKERS_PER_MATCH].claimable(ClaimableToken::No),
+      0
+    );
+  }
+
+  #[test]
+  fn reclaims_inverse_collateralized_orders_at_the_inverse_redemption_price() {
+    let market = TestMarket::new(|_| {});
+    let user = market.new_user(2_000);
+    assert_eq!(market.split_complete_set(&user, 10), Ok(()));
+    // the yes buy of 12 at 40 burns the 10 no tokens and takes 80 usd for the rest, the no buy of 3 at 50
+    // burns 3 yes tokens
+    assert_eq!(
+      market.offer_trade(&user, true, 40, 12, 0, &[], None),
+      Ok(())
+    );
+    assert_eq!(
+      market.offer_trade(&user, false, 50, 3, 0, &[], None),
+      Ok(())
+    );
+    assert_eq!(token_balance(&user.usd), 920);
+    assert_eq!(token_balance(&user.yes), 7);
+    assert_eq!(token_balance(&user.no), 0);
+
+    assert_eq!(
+      market.reclaim_open_orders(&user),
+      Err(BettingMarketError::MarketNotResolved.into())
+    );
+    market.update(|betting_market| {
+      betting_market.result = 3;
+      betting_market.yes_payout = 30;
+    });
+
+    // the burnt no tokens are refunded at 70 and the burnt yes tokens at 30, the usd collateral at its price
+    assert_eq!(market.reclaim_open_orders(&user), Ok(()));
+    assert_eq!(user.claimable(ClaimableToken::Usd), 10 * 70 + 80 + 3 * 30);
+    assert_eq!(market.level_amount(true, 40), 0);
+    assert_eq!(market.level_amount(false, 50), 0);
+    // the vault backs the refund and the 7 yes tokens left at 30
+    assert_eq!(token_balance(&market.vault), 870 + 7 * 30);
+  }
+
+  #[test]
+  fn pays_out_a_finalized_void_result_at_its_yes_payout() {
+    let market =
+      TestMarket::new(|betting_market| betting_market.dispute_window = MIN_DISPUTE_WINDOW_SECS);
+    let (maker, taker) = (market.new_user(1_000), market.new_user(1_000));
+    assert_eq!(
+      market.offer_trade(&maker, true, 30, 10, 0, &[], None),
+      Ok(())
+    );
+    assert_eq!(
+      market.offer_trade(&taker, false, 70, 10, 0, &[&maker], None),
+      Ok(())
+    );
+    assert_eq!(token_balance(&market.vault), 1_000);
+
+    set_unix_timestamp(NOW + 200);
+    assert_eq!(
+      market.propose_result(&maker, 3, 101),
+      Err(BettingMarketError::InvalidInstruction.into())
+    );
+    assert_eq!(market.propose_result(&maker, 3, 25), Ok(()));
+    set_unix_timestamp(NOW + 200 + MIN_DISPUTE_WINDOW_SECS);
+    assert_eq!(market.finalize_result(&maker), Ok(()));
+    assert_eq!(market.betting_market().result, 3);
+    assert_eq!(market.betting_market().yes_payout, 25);
+
+    // the 10 yes tokens redeem for 25 each and the 10 no tokens for 75 each
+    assert_eq!(market.payout(&maker), Ok(()));
+    assert_eq!(market.payout(&taker), Ok(()));
+    assert_eq!(token_balance(&maker.yes), 0);
+    assert_eq!(token_balance(&taker.no), 0);
+    assert_eq!(token_balance(&maker.usd), 700 + 250);
+    assert_eq!(token_balance(&taker.usd), 300 + 750);
+    assert_eq!(token_balance(&market.vault), 0);
+  }
+
+  #[test]
+  fn pays_out_a_scalar_result_judged_by_the_oracle_at_its_yes_payout() {
+    let oracle = new_account(
+      Pubkey::new_unique(),
+      false,
+      0,
+      new_mock_oracle(12_000, 10, -2, NOW + 199),
+      PROGRAM_ID,
+    );
+    let market = TestMarket::new(|betting_market| {
+      betting_market.market_type = SCALAR_MARKET_TYPE;
+      betting_market.lower_bound = 50;
+      betting_market.upper_bound = 150;
+      betting_market.oracle = *oracle.key;
+      betting_market.oracle_kind = MOCK_ORACLE_KIND;
+    });
+    let (maker, taker) = (market.new_user(1_000), market.new_user(1_000));
+    assert_eq!(
+      market.offer_trade(&maker, true, 40, 10, 0, &[], None),
+      Ok(())
+    );
+    assert_eq!(
+      market.offer_trade(&taker, false, 60, 10, 0, &[&maker], None),
+      Ok(())
+    );
+
+    // 120.00 is 70% of the way from the lower bound of 50 to the upper bound of 150
+    set_unix_timestamp(NOW + 200);
+    assert_eq!(run(&[&market.market, &oracle], &[7]), Ok(()));
+    assert_eq!(market.betting_market().result, 4);
+    assert_eq!(market.betting_market().yes_payout, 70);
+
+    assert_eq!(market.payout(&maker), Ok(()));
+    assert_eq!(market.payout(&taker), Ok(()));
+    assert_eq!(token_balance(&maker.usd), 600 + 700);
+    assert_eq!(token_balance(&taker.usd), 400 + 300);
+    assert_eq!(token_balance(&market.vault), 0);
+  }
+
+  // market without an oracle with a 100 usd resolution bond, where the proposer proposed yes at NOW + 200
+  fn proposed_market() -> (TestMarket, TestUser, TestUser) {
+    let market = TestMarket::new(|betting_market| {
+      betting_market.dispute_window = MIN_DISPUTE_WINDOW_SECS;
+      betting_market.resolution_bond = 100;
+    });
+    let (proposer, disputer) = (market.new_user(1_000), market.new_user(1_000));
+    assert_eq!(
+      market.propose_result(&proposer, 1, 0),
+      Err(BettingMarketError::ResolutionTimeNotReached.into())
+    );
+    set_unix_timestamp(NOW + 200);
+    assert_eq!(market.propose_result(&proposer, 1, 0), Ok(()));
+    assert_eq!(
+      market.propose_result(&disputer, 2, 0),
+      Err(BettingMarketError::ResultAlreadyProposed.into())
+    );
+    assert_eq!(token_balance(&proposer.usd), 900);
+    assert_eq!(token_balance(&market.vault), 100);
+    (market, proposer, disputer)
+  }
+
+  #[test]
+  fn finalizes_an_undisputed_result_once_the_dispute_window_closes() {
+    let (market, proposer, disputer) = proposed_market();
+    set_unix_timestamp(NOW + 200 + MIN_DISPUTE_WINDOW_SECS - 1);
+    assert_eq!(
+      market.finalize_result(&proposer),
+      Err(BettingMarketError::DisputeWindowOpen.into())
+    );
+    assert_eq!(market.betting_market().result, 0);
+
+    set_unix_timestamp(NOW + 200 + MIN_DISPUTE_WINDOW_SECS);
+    assert_eq!(
+      market.dispute_result(&disputer),
+      Err(BettingMarketError::DisputeWindowClosed.into())
+    );
+    assert_eq!(market.finalize_result(&proposer), Ok(()));
+    assert_eq!(market.betting_market().result, 1);
+    assert_eq!(market.betting_market().yes_payout, 100);
+    assert_eq!(proposer.claimable(ClaimableToken::Usd), 100);
+    assert_eq!(
+      market.finalize_result(&proposer),
+      Err(BettingMarketError::MarketAlreadyResolved.into())
+    );
+
+    assert_eq!(market.payout(&proposer), Ok(()));
+    assert_eq!(token_balance(&proposer.usd), 1_000);
+  }
+
+  #[test]
+  fn credits_both_bonds_to_the_proposer_when_the_judge_upholds_the_proposal() {
+    let (market, proposer, disputer) = proposed_market();
+    assert_eq!(
+      market.judge_result(1, 0, &proposer),
+      Err(BettingMarketError::ResultNotDisputed.into())
+    );
+    assert_eq!(market.dispute_result(&disputer), Ok(()));
+    assert_eq!(token_balance(&disputer.usd), 900);
+    assert_eq!(token_balance(&market.vault), 200);
+    set_unix_timestamp(NOW + 200 + MIN_DISPUTE_WINDOW_SECS);
+    assert_eq!(
+      market.finalize_result(&proposer),
+      Err(BettingMarketError::ResultAlreadyDisputed.into())
+    );
+
+    assert_eq!(market.judge_result(1, 0, &proposer), Ok(()));
+    assert_eq!(market.betting_market().result, 1);
+    assert_eq!(proposer.claimable(ClaimableToken::Usd), 200);
+    assert_eq!(disputer.claimable(ClaimableToken::Usd), 0);
+  }
+
+  #[test]
+  fn credits_both_bonds_to_the_disputer_when_the_judge_overturns_the_proposal() {
+    let (market, proposer, disputer) = proposed_market();
+    assert_eq!(market.dispute_result(&disputer), Ok(()));
+    assert_eq!(
+      market.dispute_result(&disputer),
+      Err(BettingMarketError::ResultAlreadyDisputed.into())
+    );
+
+    // the bonds go to the disputer, so the proposer's claimable balance account can't take them
+    assert_eq!(
+      market.judge_result(2, 0, &proposer),
+      Err(BettingMarketError::ClaimAccountNotFound.into())
+    );
+    assert_eq!(market.judge_result(2, 0, &disputer), Ok(()));
+    assert_eq!(market.betting_market().result, 2);
+    assert_eq!(market.betting_market().yes_payout, 0);
+    assert_eq!(disputer.claimable(ClaimableToken::Usd), 200);
+    assert_eq!(proposer.claimable(ClaimableToken::Usd), 0);
+
+    assert_eq!(market.payout(&disputer), Ok(()));
+    assert_eq!(token_balance(&disputer.usd), 1_100);
+    assert_eq!(token_balance(&market.vault), 0);
+  }
+
+  #[test]
+  fn pays_the_taker_fee_of_budget_market_orders_out_of_the_budget() {
+    let market = TestMarket::new(|betting_market| betting_market.taker_fee_bps = 100);
+    let (maker, taker) = (market.new_user(2_000), market.new_user(1_000));
+    assert_eq!(
+      market.offer_trade(&maker, false, 60, 20, 0, &[], None),
+      Ok(())
+    );
+
+    // 10 yes at 40 would cost 404 with the 1% taker fee, so a budget of 403 only buys 9 for 363
+    assert_eq!(
+      market.market_order(&taker, true, 40, MarketOrderSize::Budget(403), &[&maker]),
+      Ok(())
+    );
+    assert_eq!(taker.claimable(ClaimableToken::Yes), 9);
+    assert_eq!(token_balance(&taker.usd), 1_000 - 363);
+    assert_eq!(token_balance(&market.fee_vault), 3);
+    assert_eq!(market.level_amount(false, 60), 11);
+  }
 }
@@ -4,11 +4,28 @@ use solana_program::{
   pubkey::Pubkey,
 };
 
-use std::convert::TryFrom;
-
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 
-// data: is initialized, yes token mint, no token mint, usd token account, strike_price, result {0,1,2}, judge
+use crate::twap;
+use std::convert::TryFrom;
+
+// data: is initialized, yes token mint, no token mint, usd token account, strike_price, result {0,1,2,3}, judge, order book,
+// bump seed of the market pda derived from [b"betting", betting market data account],
+// usd a yes token redeems for once judged (a no token redeems for the rest of 100),
+// market type {0 binary, 1 scalar}, lower and upper bound of a scalar market,
+// categorical market the betting market is an outcome of (null if standalone),
+// maker and taker fee in bps of the usd value of a fill, usd token account fees accumulate in (owned by pda),
+// account allowed to withdraw fees, usd token account fees are withdrawn to,
+// market creator (null if none) and its share of all fees and a referrer's share of taker fees in bps,
+// unix timestamps (utc) trading closes at and oracle judging opens at (0 for none), whether anything traded yet,
+// pyth price account the market is judged by (null for manual judging only) and the comparison of the oracle
+// price against the strike price that makes yes win, decimal exponent of the strike price and scalar bounds,
+// length in seconds of the twap window ending at the resolution time (0 to judge on the spot price) and
+// the number of oracle samples needed to judge on the twap, kind of oracle account {0 pyth, 1 switchboard, 2 mock},
+// seconds a proposed result can be disputed for and the usd bond proposing or disputing it takes,
+// proposer and disputer of the pending result (null if none), proposed result and yes payout (result 0 if no
+// proposal) and the unix timestamp the result was proposed at
+// result 0 is open, 1 is yes, 2 is no, 3 is void and 4 is settled at the scalar yes payout
 // data account owned by this program and gets passed into all functions
 pub struct BettingMarket {
   pub is_initialized: bool,
@@ -18,6 +35,37 @@ pub struct BettingMarket {
   pub usd_token_account: Pubkey,
   pub strike_price: u64,
   pub judge: Pubkey,
+  pub order_book: Pubkey,
+  pub bump_seed: u8,
+  pub yes_payout: u8,
+  pub market_type: u8,
+  pub lower_bound: u64,
+  pub upper_bound: u64,
+  pub categorical_market: Pubkey,
+  pub maker_fee_bps: u16,
+  pub taker_fee_bps: u16,
+  pub fee_vault: Pubkey,
+  pub fee_authority: Pubkey,
+  pub fee_recipient: Pubkey,
+  pub creator: Pubkey,
+  pub creator_fee_share_bps: u16,
+  pub referrer_fee_share_bps: u16,
+  pub trading_close_ts: i64,
+  pub resolution_ts: i64,
+  pub has_traded: bool,
+  pub oracle: Pubkey,
+  pub comparison: u8,
+  pub strike_expo: i32,
+  pub twap_window: i64,
+  pub min_twap_samples: u8,
+  pub oracle_kind: u8,
+  pub dispute_window: i64,
+  pub resolution_bond: u64,
+  pub proposer: Pubkey,
+  pub disputer: Pubkey,
+  pub proposed_result: u8,
+  pub proposed_yes_payout: u8,
+  pub proposal_ts: i64,
 }
 
 impl Sealed for BettingMarket {}
@@ -28,8 +76,127 @@ impl IsInitialized for BettingMarket {
   }
 }
 
+// default split of a void market, yes and no tokens both redeem for half
+pub const DEFAULT_VOID_YES_PAYOUT: u64 = 50;
+
+// fee rates are in basis points of the usd value of a fill
+pub const MAX_FEE_BPS: u16 = 10_000;
+
+// a trade matches at most this many distinct makers, whose claimable balance accounts have to fit in the
+// same transaction, orders of further makers keep resting for the next trade
+pub const MAX_MAKERS_PER_MATCH: usize = 12;
+
+// a proposed result can be disputed for a day unless the market sets its own window of at least an hour
+pub const DEFAULT_DISPUTE_WINDOW_SECS: i64 = 86_400;
+pub const MIN_DISPUTE_WINDOW_SECS: i64 = 3_600;
+
+// the judge can settle an oracle market the oracle couldn't judge a week after the resolution timestamp
+pub const ORACLE_GRACE_PERIOD_SECS: i64 = 604_800;
+
+pub const BINARY_MARKET_TYPE: u8 = 0;
+pub const SCALAR_MARKET_TYPE: u8 = 1;
+
+// kinds of oracle account a betting market can be judged by, see oracle_adapter.rs
+pub const PYTH_ORACLE_KIND: u8 = 0;
+pub const SWITCHBOARD_ORACLE_KIND: u8 = 1;
+#[cfg(any(test, feature = "mock-oracle"))]
+pub const MOCK_ORACLE_KIND: u8 = 2;
+
+// yes wins if the oracle price is above, at or above, below or at or below the strike price
+pub const COMPARISON_ABOVE: u8 = 0;
+pub const COMPARISON_AT_OR_ABOVE: u8 = 1;
+pub const COMPARISON_BELOW: u8 = 2;
+pub const COMPARISON_AT_OR_BELOW: u8 = 3;
+
+impl BettingMarket {
+  // usd that a (yes, no) token redeems for, none if the market is not judged yet
+  // the judging instructions keep yes payout in line with the result
+  pub fn redemption_prices(&self) -> Option<(u64, u64)> {
+    if self.result == 0 {
+      return None;
+    }
+    Some((self.yes_payout as u64, 100 - self.yes_payout as u64))
+  }
+
+  // trading is closed from the trading close timestamp on, if there is one
+  pub fn is_trading_closed(&self, unix_timestamp: i64) -> bool {
+    self.trading_close_ts != 0 && unix_timestamp >= self.trading_close_ts
+  }
+
+  // the oracle can judge once the resolution timestamp is reached, if there is one
+  pub fn is_resolution_time(&self, unix_timestamp: i64) -> bool {
+    self.resolution_ts == 0 || unix_timestamp >= self.resolution_ts
+  }
+
+  // results of markets without an oracle can be proposed once trading closed and the resolution
+  // timestamp is reached, init makes sure both are set for those markets
+  pub fn is_proposal_time(&self, unix_timestamp: i64) -> bool {
+    self.resolution_ts != 0
+      && unix_timestamp >= self.resolution_ts
+      && self.is_trading_closed(unix_timestamp)
+  }
+
+  // whether the oracle had its grace period after the resolution timestamp to judge the market, the judge
+  // of a market without a resolution timestamp can step in right away
+  pub fn is_oracle_grace_period_over(&self, unix_timestamp: i64) -> bool {
+    unix_timestamp >= self.resolution_ts.saturating_add(ORACLE_GRACE_PERIOD_SECS)
+  }
+
+  // whether a result was proposed and not finalized or judged yet
+  pub fn has_proposal(&self) -> bool {
+    self.proposed_result != 0
+  }
+
+  // whether the pending proposal was disputed and waits for the judge
+  pub fn is_disputed(&self) -> bool {
+    self.disputer != NULL_PUBKEY
+  }
+
+  // disputes are accepted until the dispute window after the proposal ends
+  pub fn is_dispute_window_open(&self, unix_timestamp: i64) -> bool {
+    unix_timestamp < self.proposal_ts.saturating_add(self.dispute_window)
+  }
+
+  // whether yes wins a binary market with the oracle price compared against the strike price,
+  // both at the same exponent
+  pub fn is_yes_outcome(&self, oracle_price: i128, strike_price: i128) -> bool {
+    match self.comparison {
+      COMPARISON_AT_OR_ABOVE => oracle_price >= strike_price,
+      COMPARISON_BELOW => oracle_price < strike_price,
+      COMPARISON_AT_OR_BELOW => oracle_price <= strike_price,
+      _ => oracle_price > strike_price,
+    }
+  }
+
+  // fee on a fill worth usd_value at a rate in bps, rounded down
+  pub fn fee_for(usd_value: u64, fee_bps: u16) -> Option<u64> {
+    usd_value
+      .checked_mul(fee_bps as u64)
+      .map(|fee| fee / MAX_FEE_BPS as u64)
+  }
+
+  // how many tokens a budget buys at a price with the taker fee paid on top, fills at a price of 0 are free
+  pub fn taker_affordable_amount(&self, budget: u64, price: u64) -> u64 {
+    let cost_per_token_bps =
+      u128::from(price) * (u128::from(MAX_FEE_BPS) + u128::from(self.taker_fee_bps));
+    if cost_per_token_bps == 0 {
+      return u64::MAX;
+    }
+    u64::try_from(u128::from(budget) * u128::from(MAX_FEE_BPS) / cost_per_token_bps)
+      .unwrap_or(u64::MAX)
+  }
+
+  // maker fee deposited with a resting usd collateralized order worth usd_value, rounded up so the fees of
+  // its fills and the refund on cancelling, both rounded down, are always covered
+  pub fn maker_fee_deposit_for(&self, usd_value: u64) -> Option<u64> {
+    usd_value
+      .checked_mul(self.maker_fee_bps as u64)
+      .map(|fee| fee / MAX_FEE_BPS as u64 + (fee % MAX_FEE_BPS as u64 != 0) as u64)
+  }
+}
+
 impl Pack for BettingMarket {
-  const LEN: usize = 138;
+  const LEN: usize = 511;
 
   fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
     let src = array_ref![src, 0, BettingMarket::LEN];
@@ -41,7 +208,41 @@ impl Pack for BettingMarket {
       usd_token_account,
       strike_price,
       judge,
-    ) = array_refs![src, 1, 1, 32, 32, 32, 8, 32];
+      order_book,
+      bump_seed,
+      yes_payout,
+      market_type,
+      lower_bound,
+      upper_bound,
+      categorical_market,
+      maker_fee_bps,
+      taker_fee_bps,
+      fee_vault,
+      fee_authority,
+      fee_recipient,
+      creator,
+      creator_fee_share_bps,
+      referrer_fee_share_bps,
+      trading_close_ts,
+      resolution_ts,
+      has_traded,
+      oracle,
+      comparison,
+      strike_expo,
+      twap_window,
+      min_twap_samples,
+      oracle_kind,
+      dispute_window,
+      resolution_bond,
+      proposer,
+      disputer,
+      proposed_result,
+      proposed_yes_payout,
+      proposal_ts,
+    ) = array_refs![
+      src, 1, 1, 32, 32, 32, 8, 32, 32, 1, 1, 1, 8, 8, 32, 2, 2, 32, 32, 32, 32, 2, 2, 8, 8, 1, 32,
+      1, 4, 8, 1, 1, 8, 8, 32, 32, 1, 1, 8
+    ];
     let is_initialized = match is_initialized {
       [0] => false,
       [1] => true,
@@ -75,6 +276,37 @@ impl Pack for BettingMarket {
       usd_token_account: Pubkey::new_from_array(*usd_token_account),
       strike_price: u64::from_le_bytes(*strike_price),
       judge: Pubkey::new_from_array(*judge),
+      order_book: Pubkey::new_from_array(*order_book),
+      bump_seed: bump_seed[0],
+      yes_payout: yes_payout[0],
+      market_type: market_type[0],
+      lower_bound: u64::from_le_bytes(*lower_bound),
+      upper_bound: u64::from_le_bytes(*upper_bound),
+      categorical_market: Pubkey::new_from_array(*categorical_market),
+      maker_fee_bps: u16::from_le_bytes(*maker_fee_bps),
+      taker_fee_bps: u16::from_le_bytes(*taker_fee_bps),
+      fee_vault: Pubkey::new_from_array(*fee_vault),
+      fee_authority: Pubkey::new_from_array(*fee_authority),
+      fee_recipient: Pubkey::new_from_array(*fee_recipient),
+      creator: Pubkey::new_from_array(*creator),
+      creator_fee_share_bps: u16::from_le_bytes(*creator_fee_share_bps),
+      referrer_fee_share_bps: u16::from_le_bytes(*referrer_fee_share_bps),
+      trading_close_ts: i64::from_le_bytes(*trading_close_ts),
+      resolution_ts: i64::from_le_bytes(*resolution_ts),
+      has_traded: has_traded[0] == 1,
+      oracle: Pubkey::new_from_array(*oracle),
+      comparison: comparison[0],
+      strike_expo: i32::from_le_bytes(*strike_expo),
+      twap_window: i64::from_le_bytes(*twap_window),
+      min_twap_samples: min_twap_samples[0],
+      oracle_kind: oracle_kind[0],
+      dispute_window: i64::from_le_bytes(*dispute_window),
+      resolution_bond: u64::from_le_bytes(*resolution_bond),
+      proposer: Pubkey::new_from_array(*proposer),
+      disputer: Pubkey::new_from_array(*disputer),
+      proposed_result: proposed_result[0],
+      proposed_yes_payout: proposed_yes_payout[0],
+      proposal_ts: i64::from_le_bytes(*proposal_ts),
     })
   }
 
@@ -88,7 +320,41 @@ impl Pack for BettingMarket {
       usd_token_account_dst,
       strike_price_dst,
       judge_dst,
-    ) = mut_array_refs![dst, 1, 1, 32, 32, 32, 8, 32];
+      order_book_dst,
+      bump_seed_dst,
+      yes_payout_dst,
+      market_type_dst,
+      lower_bound_dst,
+      upper_bound_dst,
+      categorical_market_dst,
+      maker_fee_bps_dst,
+      taker_fee_bps_dst,
+      fee_vault_dst,
+      fee_authority_dst,
+      fee_recipient_dst,
+      creator_dst,
+      creator_fee_share_bps_dst,
+      referrer_fee_share_bps_dst,
+      trading_close_ts_dst,
+      resolution_ts_dst,
+      has_traded_dst,
+      oracle_dst,
+      comparison_dst,
+      strike_expo_dst,
+      twap_window_dst,
+      min_twap_samples_dst,
+      oracle_kind_dst,
+      dispute_window_dst,
+      resolution_bond_dst,
+      proposer_dst,
+      disputer_dst,
+      proposed_result_dst,
+      proposed_yes_payout_dst,
+      proposal_ts_dst,
+    ) = mut_array_refs![
+      dst, 1, 1, 32, 32, 32, 8, 32, 32, 1, 1, 1, 8, 8, 32, 2, 2, 32, 32, 32, 32, 2, 2, 8, 8, 1, 32,
+      1, 4, 8, 1, 1, 8, 8, 32, 32, 1, 1, 8
+    ];
     let BettingMarket {
       is_initialized,
       result,
@@ -97,6 +363,37 @@ impl Pack for BettingMarket {
       usd_token_account,
       strike_price,
       judge,
+      order_book,
+      bump_seed,
+      yes_payout,
+      market_type,
+      lower_bound,
+      upper_bound,
+      categorical_market,
+      maker_fee_bps,
+      taker_fee_bps,
+      fee_vault,
+      fee_authority,
+      fee_recipient,
+      creator,
+      creator_fee_share_bps,
+      referrer_fee_share_bps,
+      trading_close_ts,
+      resolution_ts,
+      has_traded,
+      oracle,
+      comparison,
+      strike_expo,
+      twap_window,
+      min_twap_samples,
+      oracle_kind,
+      dispute_window,
+      resolution_bond,
+      proposer,
+      disputer,
+      proposed_result,
+      proposed_yes_payout,
+      proposal_ts,
     } = self;
     is_initialized_dst[0] = *is_initialized as u8;
     *result_dst = result.to_le_bytes();
@@ -105,6 +402,37 @@ impl Pack for BettingMarket {
     usd_token_account_dst.copy_from_slice(usd_token_account.as_ref());
     *strike_price_dst = strike_price.to_le_bytes();
     judge_dst.copy_from_slice(judge.as_ref());
+    order_book_dst.copy_from_slice(order_book.as_ref());
+    bump_seed_dst[0] = *bump_seed;
+    yes_payout_dst[0] = *yes_payout;
+    market_type_dst[0] = *market_type;
+    *lower_bound_dst = lower_bound.to_le_bytes();
+    *upper_bound_dst = upper_bound.to_le_bytes();
+    categorical_market_dst.copy_from_slice(categorical_market.as_ref());
+    *maker_fee_bps_dst = maker_fee_bps.to_le_bytes();
+    *taker_fee_bps_dst = taker_fee_bps.to_le_bytes();
+    fee_vault_dst.copy_from_slice(fee_vault.as_ref());
+    fee_authority_dst.copy_from_slice(fee_authority.as_ref());
+    fee_recipient_dst.copy_from_slice(fee_recipient.as_ref());
+    creator_dst.copy_from_slice(creator.as_ref());
+    *creator_fee_share_bps_dst = creator_fee_share_bps.to_le_bytes();
+    *referrer_fee_share_bps_dst = referrer_fee_share_bps.to_le_bytes();
+    *trading_close_ts_dst = trading_close_ts.to_le_bytes();
+    *resolution_ts_dst = resolution_ts.to_le_bytes();
+    has_traded_dst[0] = *has_traded as u8;
+    oracle_dst.copy_from_slice(oracle.as_ref());
+    comparison_dst[0] = *comparison;
+    *strike_expo_dst = strike_expo.to_le_bytes();
+    *twap_window_dst = twap_window.to_le_bytes();
+    min_twap_samples_dst[0] = *min_twap_samples;
+    oracle_kind_dst[0] = *oracle_kind;
+    *dispute_window_dst = dispute_window.to_le_bytes();
+    *resolution_bond_dst = resolution_bond.to_le_bytes();
+    proposer_dst.copy_from_slice(proposer.as_ref());
+    disputer_dst.copy_from_slice(disputer.as_ref());
+    proposed_result_dst[0] = *proposed_result;
+    proposed_yes_payout_dst[0] = *proposed_yes_payout;
+    *proposal_ts_dst = proposal_ts.to_le_bytes();
     // for i in (0..404).step_by(4) {
     //   bid_amounts_dst[i..i + 4].copy_from_slice(&bid_amounts[i / 4].to_le_bytes());
     //   ask_amounts_dst[i..i + 4].copy_from_slice(&ask_amounts[i / 4].to_le_bytes());
@@ -117,169 +445,33 @@ impl Pack for BettingMarket {
 }
 
 // betting market data layout
-// 0..138 - betting market metadata
-// 1000..1808 - [u64; 101] - buy amounts for yes price
-// 2000..2808 - [u64; 101] - buy amounts for no price
-// 10000..42320 - [[Pubkey; 10]; 101] - user accounts associated with price (fifo)
-// 50000..58080 - [[u64; 10]; 101] - payout in usd instead of token bool {1, 2} (fifo)
-// 60000..68080 - [[u64; 10]; 101] - amounts associated with price (fifo)
-// 70000..73200 - [Pubkey; 100] - payout user acc
-// 80000..83200 - [Pubkey; 100] - payout mint
-// 90000..90800 - [u64; 100] - payout amount
-
-pub const YES_BUY_AMOUNT_START_OFFSET: isize = 1000;
-pub const NO_BUY_AMOUNT_START_OFFSET: isize = 2000;
-pub const USER_ACCOUNTS_FOR_PRICE_START_OFFSET: isize = 10000;
-pub const PAYOUT_IN_USD_FOR_PRICE_START_OFFSET: isize = 50000;
-pub const PAYOUT_AMOUNTS_FOR_PRICE_START_OFFSET: isize = 60000;
-pub const PAYOUT_USER_ACCOUNTS_OFFSET: isize = 70000;
-pub const PAYOUT_MINTS_OFFSET: isize = 80000;
-pub const PAYOUT_AMOUNTS_OFFSET: isize = 90000;
+// 0..511 - betting market metadata
+// 511..1295 - oracle samples for twap judging, see twap.rs
+// resting orders live in the separate order book account, see orderbook.rs
+// matched trades are credited to per user claimable balance accounts, see claim.rs
+
+// minimum size of the betting market data account so every section above fits
+pub const BETTING_MARKET_DATA_LEN: usize = BettingMarket::LEN + twap::TWAP_LEN;
 
 pub const PUBKEY_USIZE: usize = 32;
 pub const U64_USIZE: usize = 8;
-pub const PUBKEY_ISIZE: isize = 32;
-pub const PUBKEY_10_ISIZE: isize = 320;
-pub const U64_ISIZE: isize = 8;
-pub const U64_10_ISIZE: isize = 80;
 
 pub const NULL_PUBKEY: Pubkey = Pubkey::new_from_array([0; 32]);
 
-pub fn get_pubkey_10_at_ptr_offset(data_ptr: *const u8, offset: isize) -> [Pubkey; 10] {
-  let mut arr = [NULL_PUBKEY; 10];
-  for i in 0..10 {
-    arr[i] = get_pubkey_at_ptr_offset(data_ptr, offset + ((i * PUBKEY_USIZE) as isize));
-  }
-  arr
-}
-
-pub fn get_pubkey_at_ptr_offset(data_ptr: *const u8, offset: isize) -> Pubkey {
-  unsafe { get_pubkey_at_ptr(data_ptr.offset(offset)) }
+// little endian accessors for the fixed layouts of the program owned accounts, panicking on out of
+// bounds offsets like any other slice index
+pub fn get_pubkey(data: &[u8], offset: usize) -> Pubkey {
+  Pubkey::new_from_array(*array_ref![data, offset, PUBKEY_USIZE])
 }
 
-pub fn get_pubkey_at_ptr(pubkey_ptr: *const u8) -> Pubkey {
-  unsafe {
-    let pubkey_slice = std::slice::from_raw_parts(pubkey_ptr, PUBKEY_USIZE);
-    let pubkey_32_bytes = <[u8; PUBKEY_USIZE]>::try_from(pubkey_slice).unwrap();
-    let pubkey = Pubkey::new_from_array(pubkey_32_bytes);
-    pubkey
-  }
-}
-
-pub fn set_pubkey_10_at_ptr_offset(data_ptr: *mut u8, offset: isize, pubkey_10: [Pubkey; 10]) {
-  for i in 0..10 {
-    set_pubkey_at_ptr_offset(
-      data_ptr,
-      offset + ((i * PUBKEY_USIZE) as isize),
-      pubkey_10[i],
-    );
-  }
-}
-
-pub fn set_pubkey_at_ptr_offset(data_ptr: *mut u8, offset: isize, pubkey: Pubkey) {
-  unsafe {
-    set_pubkey_at_ptr(data_ptr.offset(offset), pubkey);
-  }
-}
-
-pub fn set_pubkey_at_ptr(pubkey_ptr: *mut u8, pubkey: Pubkey) {
-  unsafe {
-    let mutable_pubkey_slice = std::slice::from_raw_parts_mut(pubkey_ptr, PUBKEY_USIZE);
-    mutable_pubkey_slice.copy_from_slice(pubkey.as_ref());
-  }
-}
-
-pub fn get_u64_10_at_ptr_offset(data_ptr: *const u8, offset: isize) -> [u64; 10] {
-  let mut arr = [0; 10];
-  for i in 0..10 {
-    arr[i] = get_u64_at_ptr_offset(data_ptr, offset + ((i * U64_USIZE) as isize));
-  }
-  arr
-}
-
-pub fn get_u64_at_ptr_offset(data_ptr: *const u8, offset: isize) -> u64 {
-  unsafe { get_u64_at_ptr(data_ptr.offset(offset)) }
-}
-
-pub fn get_u64_at_ptr(num_ptr: *const u8) -> u64 {
-  unsafe {
-    let num_slice = std::slice::from_raw_parts(num_ptr, U64_USIZE);
-    let num_8_bytes = <[u8; U64_USIZE]>::try_from(num_slice).unwrap();
-    let num = u64::from_le_bytes(num_8_bytes);
-    num
-  }
-}
-
-pub fn set_u64_10_at_ptr_offset(data_ptr: *mut u8, offset: isize, num_10: [u64; 10]) {
-  for i in 0..10 {
-    set_u64_at_ptr_offset(data_ptr, offset + ((i * U64_USIZE) as isize), num_10[i]);
-  }
-}
-
-pub fn set_u64_at_ptr_offset(data_ptr: *mut u8, offset: isize, num: u64) {
-  unsafe {
-    set_u64_at_ptr(data_ptr.offset(offset), num);
-  }
-}
-
-pub fn set_u64_at_ptr(num_ptr: *mut u8, num: u64) {
-  unsafe {
-    let mutable_num_slice = std::slice::from_raw_parts_mut(num_ptr, U64_USIZE);
-    mutable_num_slice.copy_from_slice(num.to_le_bytes().as_ref());
-  }
-}
-
-pub fn get_u8_at_ptr_offset(data_ptr: *const u8, offset: isize) -> u8 {
-  unsafe { get_u8_at_ptr(data_ptr.offset(offset)) }
-}
-
-pub fn get_u8_at_ptr(u8_ptr: *const u8) -> u8 {
-  unsafe { *u8_ptr }
-}
-
-pub fn set_u8_at_ptr_offset(data_ptr: *mut u8, offset: isize, value: u8) {
-  unsafe {
-    set_u8_at_ptr(data_ptr.offset(offset), value);
-  }
-}
-
-pub fn set_u8_at_ptr(u8_ptr: *mut u8, value: u8) {
-  unsafe {
-    *u8_ptr = value;
-  }
-}
-
-pub fn payout_exists_at_index(data_ptr: *mut u8, index: isize) -> bool {
-  let payout_user_account_offset = PAYOUT_USER_ACCOUNTS_OFFSET + PUBKEY_ISIZE * (index as isize);
-  if get_pubkey_at_ptr_offset(data_ptr, payout_user_account_offset) == NULL_PUBKEY {
-    false
-  } else {
-    true
-  }
+pub fn set_pubkey(data: &mut [u8], offset: usize, pubkey: &Pubkey) {
+  array_mut_ref![data, offset, PUBKEY_USIZE].copy_from_slice(pubkey.as_ref());
 }
 
-pub fn get_payout_at_index(data_ptr: *mut u8, index: isize) -> (Pubkey, Pubkey, u64) {
-  let payout_user_account_offset = PAYOUT_USER_ACCOUNTS_OFFSET + PUBKEY_ISIZE * (index as isize);
-  let payout_mints_offset = PAYOUT_MINTS_OFFSET + PUBKEY_ISIZE * (index as isize);
-  let payout_amounts_offset = PAYOUT_AMOUNTS_OFFSET + U64_ISIZE * (index as isize);
-  (
-    get_pubkey_at_ptr_offset(data_ptr, payout_user_account_offset),
-    get_pubkey_at_ptr_offset(data_ptr, payout_mints_offset),
-    get_u64_at_ptr_offset(data_ptr, payout_amounts_offset),
-  )
+pub fn get_u64(data: &[u8], offset: usize) -> u64 {
+  u64::from_le_bytes(*array_ref![data, offset, U64_USIZE])
 }
 
-pub fn set_payout_at_index(
-  data_ptr: *mut u8,
-  index: isize,
-  payout_user_account: Pubkey,
-  payout_mint: Pubkey,
-  payout_amount: u64,
-) {
-  let payout_user_account_offset = PAYOUT_USER_ACCOUNTS_OFFSET + PUBKEY_ISIZE * (index as isize);
-  let payout_mints_offset = PAYOUT_MINTS_OFFSET + PUBKEY_ISIZE * (index as isize);
-  let payout_amounts_offset = PAYOUT_AMOUNTS_OFFSET + U64_ISIZE * (index as isize);
-  set_pubkey_at_ptr_offset(data_ptr, payout_user_account_offset, payout_user_account);
-  set_pubkey_at_ptr_offset(data_ptr, payout_mints_offset, payout_mint);
-  set_u64_at_ptr_offset(data_ptr, payout_amounts_offset, payout_amount);
+pub fn set_u64(data: &mut [u8], offset: usize, num: u64) {
+  *array_mut_ref![data, offset, U64_USIZE] = num.to_le_bytes();
 }
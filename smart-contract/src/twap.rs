@@ -0,0 +1,181 @@
+use crate::state::{get_u64, set_u64, BettingMarket, U64_USIZE};
+use solana_program::program_pack::Pack;
+
+// oracle sample layout
+// ring buffer in the betting market data account right after the betting market metadata
+// 0..8 - u64 - number of samples recorded, the next one goes to index count % TWAP_MAX_SAMPLES
+// 8..16 - i64 - exponent of the sampled prices, fixed by the first sample
+// 16..784 - [Sample; 32]
+//
+// sample layout
+// 0..8 - i64 - unix timestamp
+// 8..16 - i64 - aggregate price
+// 16..24 - u64 - aggregate confidence
+//
+// a permissionless crank records samples during the twap window that ends at the resolution time,
+// spaced at least window / 32 seconds apart so the buffer spans the whole window
+
+pub const TWAP_OFFSET: usize = BettingMarket::LEN;
+pub const TWAP_SAMPLE_COUNT_OFFSET: usize = TWAP_OFFSET;
+pub const TWAP_EXPO_OFFSET: usize = TWAP_OFFSET + U64_USIZE;
+pub const TWAP_SAMPLES_OFFSET: usize = TWAP_OFFSET + 2 * U64_USIZE;
+
+pub const SAMPLE_LEN: usize = 24;
+pub const SAMPLE_TIMESTAMP_OFFSET: usize = 0;
+pub const SAMPLE_PRICE_OFFSET: usize = U64_USIZE;
+pub const SAMPLE_CONF_OFFSET: usize = 2 * U64_USIZE;
+
+pub const TWAP_MAX_SAMPLES: usize = 32;
+
+pub const TWAP_LEN: usize = TWAP_SAMPLES_OFFSET - TWAP_OFFSET + SAMPLE_LEN * TWAP_MAX_SAMPLES;
+
+// an oracle price read at a point in time
+pub struct Sample {
+  pub unix_timestamp: i64,
+  pub price: i64,
+  pub conf: u64,
+}
+
+pub fn get_sample_count(data: &[u8]) -> u64 {
+  get_u64(data, TWAP_SAMPLE_COUNT_OFFSET)
+}
+
+pub fn get_expo(data: &[u8]) -> i32 {
+  get_u64(data, TWAP_EXPO_OFFSET) as i64 as i32
+}
+
+fn sample_offset(index: usize) -> usize {
+  TWAP_SAMPLES_OFFSET + SAMPLE_LEN * index
+}
+
+pub fn get_sample(data: &[u8], index: usize) -> Sample {
+  let offset = sample_offset(index);
+  Sample {
+    unix_timestamp: get_u64(data, offset + SAMPLE_TIMESTAMP_OFFSET) as i64,
+    price: get_u64(data, offset + SAMPLE_PRICE_OFFSET) as i64,
+    conf: get_u64(data, offset + SAMPLE_CONF_OFFSET),
+  }
+}
+
+pub fn get_last_sample(data: &[u8]) -> Option<Sample> {
+  let sample_count = get_sample_count(data);
+  if sample_count == 0 {
+    return None;
+  }
+  Some(get_sample(
+    data,
+    ((sample_count - 1) % TWAP_MAX_SAMPLES as u64) as usize,
+  ))
+}
+
+// write the sample over the oldest one once the buffer is full
+pub fn record_sample(data: &mut [u8], sample: &Sample, expo: i32) {
+  let sample_count = get_sample_count(data);
+  let offset = sample_offset((sample_count % TWAP_MAX_SAMPLES as u64) as usize);
+  set_u64(
+    data,
+    offset + SAMPLE_TIMESTAMP_OFFSET,
+    sample.unix_timestamp as u64,
+  );
+  set_u64(data, offset + SAMPLE_PRICE_OFFSET, sample.price as u64);
+  set_u64(data, offset + SAMPLE_CONF_OFFSET, sample.conf);
+  set_u64(data, TWAP_EXPO_OFFSET, expo as i64 as u64);
+  set_u64(data, TWAP_SAMPLE_COUNT_OFFSET, sample_count + 1);
+}
+
+// seconds that have to pass between two samples for the buffer to span the window
+pub fn min_sample_spacing(twap_window: i64) -> i64 {
+  std::cmp::max(1, twap_window / TWAP_MAX_SAMPLES as i64)
+}
+
+// time weighted average (price, confidence) of the samples in the buffer, every sample weighted by the
+// time until the next one or until the end of the window, none if no time is covered
+pub fn time_weighted_average(data: &[u8], window_end: i64) -> Option<(i128, i128)> {
+  let sample_count = get_sample_count(data);
+  let num_samples = std::cmp::min(sample_count, TWAP_MAX_SAMPLES as u64);
+  let first_index = sample_count - num_samples;
+  let samples: Vec<Sample> = (first_index..sample_count)
+    .map(|index| get_sample(data, (index % TWAP_MAX_SAMPLES as u64) as usize))
+    .collect();
+
+  let mut weighted_price: i128 = 0;
+  let mut weighted_conf: i128 = 0;
+  let mut total_weight: i128 = 0;
+  for (i, sample) in samples.iter().enumerate() {
+    let next_timestamp = samples
+      .get(i + 1)
+      .map_or(window_end, |next_sample| next_sample.unix_timestamp);
+    let weight = (next_timestamp as i128).checked_sub(sample.unix_timestamp as i128)?;
+    weighted_price = weighted_price.checked_add((sample.price as i128).checked_mul(weight)?)?;
+    weighted_conf = weighted_conf.checked_add((sample.conf as i128).checked_mul(weight)?)?;
+    total_weight = total_weight.checked_add(weight)?;
+  }
+  if total_weight <= 0 {
+    return None;
+  }
+  Some((weighted_price / total_weight, weighted_conf / total_weight))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::state::BETTING_MARKET_DATA_LEN;
+
+  fn record(data: &mut [u8], unix_timestamp: i64, price: i64, conf: u64) {
+    record_sample(
+      data,
+      &Sample {
+        unix_timestamp,
+        price,
+        conf,
+      },
+      -2,
+    );
+  }
+
+  #[test]
+  fn average_weights_samples_by_the_time_until_the_next_one() {
+    let mut data = vec![0; BETTING_MARKET_DATA_LEN];
+    assert_eq!(time_weighted_average(&data, 100), None);
+
+    record(&mut data, 0, 100, 2);
+    record(&mut data, 30, 200, 6);
+    // 100 for 30s and 200 for 10s
+    assert_eq!(time_weighted_average(&data, 40), Some((125, 3)));
+    assert_eq!(get_expo(&data), -2);
+    assert_eq!(get_last_sample(&data).unwrap().price, 200);
+  }
+
+  #[test]
+  fn average_is_none_without_covered_time() {
+    let mut data = vec![0; BETTING_MARKET_DATA_LEN];
+    record(&mut data, 50, 100, 1);
+    assert_eq!(time_weighted_average(&data, 50), None);
+  }
+
+  #[test]
+  fn ring_buffer_keeps_the_latest_samples_once_wrapped() {
+    let mut data = vec![0; BETTING_MARKET_DATA_LEN];
+    let num_samples = TWAP_MAX_SAMPLES as i64 + 8;
+    for i in 0..num_samples {
+      record(&mut data, i * 10, i, 1);
+    }
+    assert_eq!(get_sample_count(&data), num_samples as u64);
+    assert_eq!(get_last_sample(&data).unwrap().price, num_samples - 1);
+    // the 9th sample overwrote the first one, and is now the oldest in the buffer
+    assert_eq!(get_sample(&data, 0).price, TWAP_MAX_SAMPLES as i64);
+    assert_eq!(get_sample(&data, 8).price, 8);
+
+    // samples 8..40 each weighted 10s, averaging 23.5 rounded down
+    assert_eq!(
+      time_weighted_average(&data, num_samples * 10),
+      Some((23, 1))
+    );
+  }
+
+  #[test]
+  fn sample_spacing_spreads_the_buffer_over_the_window() {
+    assert_eq!(min_sample_spacing(3200), 100);
+    assert_eq!(min_sample_spacing(10), 1);
+  }
+}
@@ -0,0 +1,283 @@
+use crate::{
+  categorical,
+  error::BettingMarketError,
+  orderbook,
+  state::{BettingMarket, NULL_PUBKEY},
+};
+use solana_program::{
+  account_info::AccountInfo,
+  entrypoint::ProgramResult,
+  program_error::ProgramError,
+  program_pack::{IsInitialized, Pack},
+  pubkey::Pubkey,
+};
+use spl_token::state::Account as TokenAccount;
+use std::cell::RefMut;
+
+// verify the account signed the transaction
+pub fn validate_signer(account: &AccountInfo) -> ProgramResult {
+  if !account.is_signer {
+    return Err(BettingMarketError::MissingSigner.into());
+  }
+  Ok(())
+}
+
+// verify the token program account is the spl token program
+pub fn validate_token_program(token_program_account: &AccountInfo) -> ProgramResult {
+  if *token_program_account.key != spl_token::id() {
+    return Err(BettingMarketError::IncorrectTokenProgram.into());
+  }
+  Ok(())
+}
+
+// verify the betting market data account is owned by this program and initialized and unpack its metadata
+pub fn validate_betting_market_account(
+  betting_market_data_account: &AccountInfo,
+  program_id: &Pubkey,
+) -> Result<BettingMarket, ProgramError> {
+  if *betting_market_data_account.owner != *program_id {
+    return Err(BettingMarketError::IncorrectMarketOwner.into());
+  }
+  let data = betting_market_data_account.data.borrow();
+  let slice = data
+    .get(..BettingMarket::LEN)
+    .ok_or(BettingMarketError::MarketNotInitialized)?;
+  let betting_market_data = BettingMarket::unpack_unchecked(slice)?;
+  if !betting_market_data.is_initialized() {
+    return Err(BettingMarketError::MarketNotInitialized.into());
+  }
+  Ok(betting_market_data)
+}
+
+// verify the pda account is the betting market's pda derived from [b"betting", betting market data account]
+// and return the stored bump seed for signing
+pub fn validate_market_pda(
+  betting_market_data_account: &AccountInfo,
+  betting_market_data: &BettingMarket,
+  pda_account: &AccountInfo,
+  program_id: &Pubkey,
+) -> Result<u8, ProgramError> {
+  validate_pda_with_bump_seed(
+    betting_market_data_account.key,
+    betting_market_data.bump_seed,
+    pda_account,
+    program_id,
+  )?;
+  Ok(betting_market_data.bump_seed)
+}
+
+// verify the order book account is the one belonging to the betting market and return its data,
+// borrowed for as long as the caller holds it
+pub fn validate_order_book_account<'a, 'b>(
+  betting_market_data_account: &AccountInfo,
+  betting_market_data: &BettingMarket,
+  order_book_account: &'a AccountInfo<'b>,
+  program_id: &Pubkey,
+) -> Result<RefMut<'a, &'b mut [u8]>, ProgramError> {
+  if betting_market_data.order_book != *order_book_account.key
+    || *order_book_account.owner != *program_id
+  {
+    return Err(BettingMarketError::InvalidOrderBook.into());
+  }
+  let order_book_data = order_book_account.try_borrow_mut_data()?;
+  if !orderbook::is_initialized(&order_book_data)
+    || orderbook::get_market(&order_book_data) != *betting_market_data_account.key
+  {
+    return Err(BettingMarketError::InvalidOrderBook.into());
+  }
+  Ok(order_book_data)
+}
+
+// verify the yes and no token mints are the betting market's mints
+pub fn validate_market_mints(
+  betting_market_data: &BettingMarket,
+  yes_token_mint: &AccountInfo,
+  no_token_mint: &AccountInfo,
+) -> ProgramResult {
+  if *yes_token_mint.key != betting_market_data.yes_token_mint {
+    return Err(BettingMarketError::IncorrectYesTokenMint.into());
+  }
+  if *no_token_mint.key != betting_market_data.no_token_mint {
+    return Err(BettingMarketError::IncorrectNoTokenMint.into());
+  }
+  Ok(())
+}
+
+// verify the usd token account is the betting market's vault, owned by its pda and holding the usd mint
+pub fn validate_market_usd_token_account(
+  betting_market_data: &BettingMarket,
+  pda_usd_token_account: &AccountInfo,
+  usd_token_mint: &AccountInfo,
+  pda_account: &AccountInfo,
+) -> ProgramResult {
+  if *pda_usd_token_account.key != betting_market_data.usd_token_account
+    || *pda_usd_token_account.owner != spl_token::id()
+  {
+    return Err(BettingMarketError::IncorrectUsdTokenAccount.into());
+  }
+  let pda_usd_token_account_data = TokenAccount::unpack(&pda_usd_token_account.data.borrow())?;
+  if pda_usd_token_account_data.owner != *pda_account.key {
+    return Err(BettingMarketError::IncorrectUsdTokenAccount.into());
+  }
+  if pda_usd_token_account_data.mint != *usd_token_mint.key {
+    return Err(BettingMarketError::IncorrectUsdTokenMint.into());
+  }
+  Ok(())
+}
+
+// verify the token account is a token program account owned by the user for the mint
+pub fn validate_user_token_account(
+  token_account: &AccountInfo,
+  user_account: &AccountInfo,
+  token_mint: &AccountInfo,
+) -> Result<TokenAccount, ProgramError> {
+  if *token_account.owner != spl_token::id() {
+    return Err(BettingMarketError::IncorrectTokenAccountOwner.into());
+  }
+  let token_account_data = TokenAccount::unpack(&token_account.data.borrow())?;
+  if token_account_data.owner != *user_account.key {
+    return Err(BettingMarketError::IncorrectTokenAccountOwner.into());
+  }
+  if token_account_data.mint != *token_mint.key {
+    return Err(BettingMarketError::IncorrectTokenAccountMint.into());
+  }
+  Ok(token_account_data)
+}
+
+// verify the accounts shared by every instruction that moves a user's usd, yes and no tokens
+// and return the betting market metadata and the bump seed of its pda
+#[allow(clippy::too_many_arguments)]
+pub fn validate_trade_accounts(
+  program_id: &Pubkey,
+  user_account: &AccountInfo,
+  pda_account: &AccountInfo,
+  betting_market_data_account: &AccountInfo,
+  usd_token_mint: &AccountInfo,
+  yes_token_mint: &AccountInfo,
+  no_token_mint: &AccountInfo,
+  user_usd_token_account: &AccountInfo,
+  user_yes_token_account: &AccountInfo,
+  user_no_token_account: &AccountInfo,
+  pda_usd_token_account: &AccountInfo,
+  token_program_id: &AccountInfo,
+) -> Result<(BettingMarket, u8), ProgramError> {
+  validate_signer(user_account)?;
+  validate_token_program(token_program_id)?;
+  let betting_market_data =
+    validate_betting_market_account(betting_market_data_account, program_id)?;
+  let bump_seed = validate_market_pda(
+    betting_market_data_account,
+    &betting_market_data,
+    pda_account,
+    program_id,
+  )?;
+  validate_market_mints(&betting_market_data, yes_token_mint, no_token_mint)?;
+  validate_market_usd_token_account(
+    &betting_market_data,
+    pda_usd_token_account,
+    usd_token_mint,
+    pda_account,
+  )?;
+  validate_user_token_account(user_usd_token_account, user_account, usd_token_mint)?;
+  validate_user_token_account(user_yes_token_account, user_account, yes_token_mint)?;
+  validate_user_token_account(user_no_token_account, user_account, no_token_mint)?;
+  Ok((betting_market_data, bump_seed))
+}
+
+// verify the judge account is the betting market's judge and signed the transaction
+pub fn validate_judge(
+  betting_market_data: &BettingMarket,
+  judge_account: &AccountInfo,
+) -> ProgramResult {
+  validate_signer(judge_account)?;
+  if *judge_account.key != betting_market_data.judge {
+    return Err(BettingMarketError::InvalidJudge.into());
+  }
+  Ok(())
+}
+
+// verify the betting market is not an outcome of a categorical market, which are only judged together
+pub fn validate_standalone_market(betting_market_data: &BettingMarket) -> ProgramResult {
+  if betting_market_data.categorical_market != NULL_PUBKEY {
+    return Err(BettingMarketError::CategoricalOutcomeMarket.into());
+  }
+  Ok(())
+}
+
+// verify the categorical market data account is owned by this program and initialized and return its data,
+// borrowed for as long as the caller holds it
+pub fn validate_categorical_market_account<'a, 'b>(
+  categorical_market_account: &'a AccountInfo<'b>,
+  program_id: &Pubkey,
+) -> Result<RefMut<'a, &'b mut [u8]>, ProgramError> {
+  if *categorical_market_account.owner != *program_id {
+    return Err(BettingMarketError::InvalidCategoricalMarket.into());
+  }
+  let categorical_market_data = categorical_market_account.try_borrow_mut_data()?;
+  if !categorical::is_initialized(&categorical_market_data) {
+    return Err(BettingMarketError::InvalidCategoricalMarket.into());
+  }
+  Ok(categorical_market_data)
+}
+
+// verify the pda account is the pda of a betting market or categorical market data account with its stored bump seed
+pub fn validate_pda_with_bump_seed(
+  data_account_key: &Pubkey,
+  bump_seed: u8,
+  pda_account: &AccountInfo,
+  program_id: &Pubkey,
+) -> ProgramResult {
+  let pda = Pubkey::create_program_address(
+    &[b"betting", data_account_key.as_ref(), &[bump_seed]],
+    program_id,
+  )
+  .map_err(|_| BettingMarketError::InvalidPda)?;
+  if *pda_account.key != pda {
+    return Err(BettingMarketError::InvalidPda.into());
+  }
+  Ok(())
+}
+
+// verify the usd token account is the categorical market's complete set vault
+pub fn validate_categorical_usd_token_account(
+  categorical_market_data: &[u8],
+  usd_token_account: &AccountInfo,
+) -> ProgramResult {
+  if *usd_token_account.key != categorical::get_usd_token_account(categorical_market_data) {
+    return Err(BettingMarketError::IncorrectUsdTokenAccount.into());
+  }
+  Ok(())
+}
+
+// verify the fee vault is the betting market's fee vault
+pub fn validate_fee_vault(
+  betting_market_data: &BettingMarket,
+  fee_vault_account: &AccountInfo,
+) -> ProgramResult {
+  if *fee_vault_account.key != betting_market_data.fee_vault {
+    return Err(BettingMarketError::IncorrectFeeVault.into());
+  }
+  Ok(())
+}
+
+// verify the referrer's usd token account holds the usd mint and isn't the user's own
+// and return the referrer, whose claimable balance account earns the referrer share
+// this only stops the signing key from referring itself, a trader can still name a second wallet of theirs
+// as referrer, so the referrer share is effectively a discount for whoever picks the referrer
+pub fn validate_referrer(
+  referrer_usd_token_account: &AccountInfo,
+  user_account: &AccountInfo,
+  usd_token_mint: &AccountInfo,
+) -> Result<Pubkey, ProgramError> {
+  if *referrer_usd_token_account.owner != spl_token::id() {
+    return Err(BettingMarketError::InvalidReferrer.into());
+  }
+  let referrer_usd_token_account_data =
+    TokenAccount::unpack(&referrer_usd_token_account.data.borrow())?;
+  if referrer_usd_token_account_data.mint != *usd_token_mint.key
+    || referrer_usd_token_account_data.owner == *user_account.key
+  {
+    return Err(BettingMarketError::InvalidReferrer.into());
+  }
+  Ok(referrer_usd_token_account_data.owner)
+}